@@ -15,7 +15,6 @@
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
 use nrbf_parser::Decoder;
-use nrbf_parser::records::Record;
 use std::env;
 use std::fs::File;
 use std::io::{BufReader, Write};
@@ -31,13 +30,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let reader = BufReader::new(file);
     let mut decoder = Decoder::new(reader);
 
-    let mut records = Vec::new();
-    while let Some(record) = decoder.decode_next()? {
-        records.push(record);
-        if let Record::MessageEnd = records.last().unwrap() {
-            break;
-        }
-    }
+    let records = decoder.decode_message()?.unwrap_or_default();
 
     let output_path = args.get(2).map(|s| s.as_str()).unwrap_or("output.json");
     let json = serde_json::to_string_pretty(&records)?;
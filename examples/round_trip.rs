@@ -16,7 +16,8 @@
 
 use nrbf_parser::Decoder;
 use nrbf_parser::Encoder;
-use nrbf_parser::interleaved::{from_interleaved, to_interleaved};
+use nrbf_parser::interleaved::{InterleavedSerializer, from_interleaved};
+use nrbf_parser::lossless::Lossless;
 use nrbf_parser::records::Record;
 use std::env;
 use std::fs::File;
@@ -38,31 +39,26 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let reader = BufReader::new(file);
     let mut decoder = Decoder::new(reader);
 
-    let mut records = Vec::new();
-    while let Some(record) = decoder.decode_next()? {
-        let is_end = matches!(record, Record::MessageEnd);
-        records.push(record);
-        if is_end {
-            break;
-        }
-    }
+    let records = decoder.decode_message()?.unwrap_or_default();
     println!("Parsed {} records.", records.len());
 
     // Serialize to JSON
-    let json = serde_json::to_string_pretty(&records)?;
+    let json = serde_json::to_string_pretty(&Lossless(&records))?;
     let json_path = "output.json";
     std::fs::write(json_path, &json)?;
     println!("Saved records to {}", json_path);
 
     // Serialize to Interleaved JSON
-    let interleaved_json = to_interleaved(&records);
+    let interleaved_json = InterleavedSerializer::new()
+        .with_lossless(true)
+        .serialize(&records);
     let interleaved_json_str = serde_json::to_string_pretty(&interleaved_json)?;
     let interleaved_path = "interleaved.json";
     std::fs::write(interleaved_path, &interleaved_json_str)?;
     println!("Saved interleaved records to {}", interleaved_path);
 
     // Deserialize from JSON
-    let deserialized_records: Vec<Record> = serde_json::from_str(&json)?;
+    let Lossless(deserialized_records) = serde_json::from_str::<Lossless<Vec<Record>>>(&json)?;
     println!(
         "Deserialized {} records from JSON.",
         deserialized_records.len()
@@ -82,7 +78,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     // Interleaved reconstruction check
     println!("--- Interleaved Round Trip Check ---");
-    let interleaved_reconstructed_records = from_interleaved(interleaved_json);
+    let interleaved_reconstructed_records = from_interleaved(interleaved_json)?;
     println!(
         "Deserialized {} records from Interleaved JSON.",
         interleaved_reconstructed_records.len()
@@ -0,0 +1,137 @@
+// nrbf-parser - A high-performance MS-NRBF binary parser and encoder.
+// Copyright (C) 2026  driedpampas@proton.me
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Async counterparts of [`Decoder`](crate::Decoder) and [`Encoder`] over tokio I/O.
+//!
+//! Both types delegate to the synchronous implementations, so the records
+//! they produce and the bytes they write are identical.
+
+use crate::dedup::DedupOptions;
+use crate::encoder::Encoder;
+use crate::error::Result;
+use crate::push::PushDecoder;
+use crate::records::Record;
+use std::collections::HashMap;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+const CHUNK_SIZE: usize = 8192;
+
+/// An async decoder for MS-NRBF binary streams.
+pub struct AsyncDecoder<R: AsyncRead + Unpin> {
+    reader: R,
+    decoder: PushDecoder,
+    chunk: Vec<u8>,
+    eof: bool,
+}
+
+impl<R: AsyncRead + Unpin> AsyncDecoder<R> {
+    /// Creates a new decoder from an async reader.
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            decoder: PushDecoder::new(),
+            chunk: Vec::new(),
+            eof: false,
+        }
+    }
+
+    /// Current offset in the stream.
+    pub fn offset(&self) -> usize {
+        self.decoder.offset()
+    }
+
+    /// Registry of libraries by ID.
+    pub fn library_registry(&self) -> &HashMap<i32, String> {
+        self.decoder.library_registry()
+    }
+
+    /// Decodes the next record from the stream.
+    ///
+    /// Returns `Ok(Some(record))` if a record was successfully read,
+    /// `Ok(None)` if the end of the stream was reached,
+    /// or an `Err` if parsing failed.
+    pub async fn decode_next(&mut self) -> Result<Option<Record>> {
+        loop {
+            if let Some(record) = self.decoder.try_decode()? {
+                return Ok(Some(record));
+            }
+            if self.eof {
+                return Ok(None);
+            }
+
+            // An incomplete record is re-parsed after every read, so reads
+            // grow with the buffer to keep a large record from being
+            // re-parsed once per small chunk.
+            self.chunk
+                .resize(self.decoder.buffered().max(CHUNK_SIZE), 0);
+            let n = self.reader.read(&mut self.chunk).await?;
+            if n == 0 {
+                self.decoder.finish()?;
+                self.eof = true;
+            } else {
+                self.decoder.push(&self.chunk[..n]);
+            }
+        }
+    }
+}
+
+/// An async encoder for MS-NRBF binary streams.
+pub struct AsyncEncoder<W: AsyncWrite + Unpin> {
+    writer: W,
+    encoder: Encoder<Vec<u8>>,
+}
+
+impl<W: AsyncWrite + Unpin> AsyncEncoder<W> {
+    /// Creates a new encoder from an async writer.
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            encoder: Encoder::new(Vec::new()),
+        }
+    }
+
+    /// Enables or disables strict mode, see [`Encoder::with_strict`].
+    pub fn with_strict(mut self, strict: bool) -> Self {
+        self.encoder = self.encoder.with_strict(strict);
+        self
+    }
+
+    /// Shares repeated values, see [`Encoder::with_dedup`].
+    pub fn with_dedup(mut self, options: DedupOptions) -> Self {
+        self.encoder = self.encoder.with_dedup(options);
+        self
+    }
+
+    /// Encodes a record and writes it to the stream.
+    pub async fn encode(&mut self, record: &Record) -> Result<()> {
+        let result = self.encoder.encode(record);
+        let buffer = std::mem::take(self.encoder.writer_mut());
+        result?;
+        self.writer.write_all(&buffer).await?;
+        Ok(())
+    }
+
+    /// Flushes the underlying writer.
+    pub async fn flush(&mut self) -> Result<()> {
+        self.writer.flush().await?;
+        Ok(())
+    }
+
+    /// Consumes the encoder, returning the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
@@ -0,0 +1,497 @@
+// nrbf-parser - A high-performance MS-NRBF binary parser and encoder.
+// Copyright (C) 2026  driedpampas@proton.me
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Building streams without managing object ids or class metadata by hand.
+
+use crate::encoder::Encoder;
+use crate::error::{Error, Result};
+use crate::records::*;
+use std::collections::HashMap;
+use std::io::Write;
+
+/// The declared type of a class member.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MemberType {
+    Primitive(PrimitiveType),
+    String,
+    Object,
+    SystemClass(String),
+    /// A class from the named library.
+    Class {
+        name: String,
+        library: String,
+    },
+    ObjectArray,
+    StringArray,
+    PrimitiveArray(PrimitiveType),
+}
+
+/// Describes a class whose instances are written by [`StreamBuilder`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClassDef {
+    pub name: String,
+    /// Library name, or `None` for system classes.
+    pub library: Option<String>,
+    pub members: Vec<(String, MemberType)>,
+}
+
+impl ClassDef {
+    /// Creates a class definition with no members.
+    pub fn new(name: impl Into<String>, library: Option<&str>) -> Self {
+        Self {
+            name: name.into(),
+            library: library.map(str::to_string),
+            members: Vec::new(),
+        }
+    }
+
+    /// Appends a member.
+    pub fn member(mut self, name: impl Into<String>, member_type: MemberType) -> Self {
+        self.members.push((name.into(), member_type));
+        self
+    }
+}
+
+/// A value written by [`StreamBuilder`].
+#[derive(Debug, Clone)]
+pub enum Value {
+    Null,
+    Primitive(PrimitiveValue),
+    String(String),
+    /// An instance of a class, with one value per member.
+    Object(ClassDef, Vec<Value>),
+    PrimitiveArray(PrimitiveType, Vec<PrimitiveValue>),
+    ObjectArray(Vec<Value>),
+    StringArray(Vec<Option<String>>),
+    /// A reference to an object id from [`StreamBuilder::reserve_id`].
+    Reference(i32),
+}
+
+/// Writes a complete message on top of an [`Encoder`].
+///
+/// Object ids are allocated automatically, each library is emitted once
+/// before its first use, and classes after their first instance are written
+/// as `ClassWithId` records. The underlying encoder runs in strict mode.
+pub struct StreamBuilder<W: Write> {
+    encoder: Encoder<W>,
+    records: RecordBuilder,
+    started: bool,
+}
+
+impl<W: Write> StreamBuilder<W> {
+    /// Creates a new builder over a writer.
+    pub fn new(writer: W) -> Self {
+        Self {
+            encoder: Encoder::new(writer).with_strict(true),
+            records: RecordBuilder::new(1),
+            started: false,
+        }
+    }
+
+    /// Allocates an object id for a value written later with [`StreamBuilder::write`].
+    pub fn reserve_id(&mut self) -> i32 {
+        self.records.reserve_id()
+    }
+
+    /// Writes the `SerializationHeader` followed by the root value.
+    ///
+    /// Returns the root's object id.
+    pub fn write_root(&mut self, root: &Value) -> Result<i32> {
+        if self.started {
+            return Err(Error::Custom("Root has already been written".into()));
+        }
+        self.started = true;
+        let id = self.reserve_id();
+        self.encoder
+            .encode(&Record::SerializationHeader(SerializationHeader {
+                root_id: id,
+                header_id: -1,
+                major_version: 1,
+                minor_version: 0,
+            }))?;
+        self.write(id, root)?;
+        Ok(id)
+    }
+
+    /// Writes a top-level value under an id from [`StreamBuilder::reserve_id`].
+    pub fn write(&mut self, id: i32, value: &Value) -> Result<()> {
+        if !self.started {
+            return Err(Error::Custom("Root must be written first".into()));
+        }
+        // Classes and libraries of a record the encoder rejects were never
+        // written, so later records must not refer to them.
+        let checkpoint = self.records.checkpoint();
+        let result = self.encode(id, value);
+        if result.is_err() {
+            self.records.rollback(checkpoint);
+        }
+        result
+    }
+
+    fn encode(&mut self, id: i32, value: &Value) -> Result<()> {
+        let record = self.records.build_record(Some(id), value)?.ok_or_else(|| {
+            Error::Custom(format!(
+                "{:?} cannot be written as a top-level object",
+                value
+            ))
+        })?;
+        while let Some(library) = self.records.pending_libraries.first() {
+            self.encoder
+                .encode(&Record::BinaryLibrary(library.clone()))?;
+            self.records.pending_libraries.remove(0);
+        }
+        self.encoder.encode(&record)
+    }
+
+    /// Appends `MessageEnd`, completing the stream.
+    pub fn finish(mut self) -> Result<()> {
+        if !self.started {
+            return Err(Error::Custom("Root must be written first".into()));
+        }
+        self.encoder.encode(&Record::MessageEnd)
+    }
+}
+
+/// Converts [`Value`]s into records, allocating object ids, libraries and
+/// class metadata.
+pub(crate) struct RecordBuilder {
+    next_id: i32,
+    pub(crate) libraries: HashMap<String, i32>,
+    /// Metadata ids of built classes, keyed by class and library name.
+    classes: HashMap<(String, Option<String>), (i32, ClassDef)>,
+    /// Keys of `classes` in the order the classes were defined.
+    defined: Vec<(String, Option<String>)>,
+    /// Libraries allocated since the last [`RecordBuilder::take_libraries`].
+    pending_libraries: Vec<BinaryLibrary>,
+}
+
+/// The state of a [`RecordBuilder`] to return to when the records built
+/// since are not written.
+#[derive(Clone, Copy)]
+struct Checkpoint {
+    next_id: i32,
+    classes: usize,
+    libraries: usize,
+}
+
+impl RecordBuilder {
+    /// Creates a builder that allocates ids from `next_id` upwards.
+    pub(crate) fn new(next_id: i32) -> Self {
+        Self {
+            next_id,
+            libraries: HashMap::new(),
+            classes: HashMap::new(),
+            defined: Vec::new(),
+            pending_libraries: Vec::new(),
+        }
+    }
+
+    pub(crate) fn reserve_id(&mut self) -> i32 {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+
+    /// Returns the libraries allocated since the last call, which must be
+    /// written before the records that use them.
+    pub(crate) fn take_libraries(&mut self) -> Vec<BinaryLibrary> {
+        std::mem::take(&mut self.pending_libraries)
+    }
+
+    fn library_id(&mut self, name: &str) -> i32 {
+        if let Some(id) = self.libraries.get(name) {
+            return *id;
+        }
+        let id = self.reserve_id();
+        self.libraries.insert(name.to_string(), id);
+        self.pending_libraries.push(BinaryLibrary {
+            library_id: id,
+            library_name: name.to_string(),
+        });
+        id
+    }
+
+    fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            next_id: self.next_id,
+            classes: self.defined.len(),
+            libraries: self.pending_libraries.len(),
+        }
+    }
+
+    /// Forgets the classes defined since `checkpoint` and the libraries
+    /// allocated since that are still pending, and frees their ids.
+    fn rollback(&mut self, checkpoint: Checkpoint) {
+        for key in self.defined.drain(checkpoint.classes..) {
+            self.classes.remove(&key);
+        }
+        let pending = checkpoint.libraries.min(self.pending_libraries.len());
+        for library in self.pending_libraries.drain(pending..) {
+            self.libraries.remove(&library.library_name);
+        }
+        // Libraries that were written keep their ids.
+        self.next_id = self
+            .libraries
+            .values()
+            .filter(|&&id| id >= checkpoint.next_id)
+            .max()
+            .map_or(checkpoint.next_id, |id| id + 1);
+    }
+
+    /// Runs `build`, forgetting the classes and libraries it defined if it
+    /// fails, as the records that define them are never written.
+    fn undo_on_error<T>(&mut self, build: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
+        let checkpoint = self.checkpoint();
+        let result = build(self);
+        if result.is_err() {
+            self.rollback(checkpoint);
+        }
+        result
+    }
+
+    /// Returns `id` if given, otherwise allocates a fresh one.
+    fn object_id(&mut self, id: Option<i32>) -> i32 {
+        id.unwrap_or_else(|| self.reserve_id())
+    }
+
+    /// Converts a value into the record that stands for it.
+    ///
+    /// `id` is given for top-level values, which must be objects, strings or
+    /// arrays; `None` is returned for anything else.
+    pub(crate) fn build_record(
+        &mut self,
+        id: Option<i32>,
+        value: &Value,
+    ) -> Result<Option<Record>> {
+        self.undo_on_error(|builder| builder.record(id, value))
+    }
+
+    fn record(&mut self, id: Option<i32>, value: &Value) -> Result<Option<Record>> {
+        let record = match value {
+            Value::Primitive(_) => return Ok(None),
+            Value::Null | Value::Reference(_) if id.is_some() => return Ok(None),
+            Value::Null => Record::ObjectNull,
+            Value::Reference(id_ref) => Record::MemberReference { id_ref: *id_ref },
+            Value::String(s) => Record::BinaryObjectString {
+                object_id: self.object_id(id),
+                value: s.clone(),
+            },
+            Value::PrimitiveArray(pt, values) => {
+                Record::ArraySinglePrimitive(ArraySinglePrimitive {
+                    object_id: self.object_id(id),
+                    length: values.len() as i32,
+                    primitive_type_enum: *pt,
+                    element_values: values.clone(),
+                })
+            }
+            Value::ObjectArray(values) => {
+                let object_id = self.object_id(id);
+                Record::ArraySingleObject(ArraySingleObject {
+                    object_id,
+                    length: values.len() as i32,
+                    element_values: self.build_elements(values)?,
+                })
+            }
+            Value::StringArray(values) => {
+                let object_id = self.object_id(id);
+                let values: Vec<Value> = values
+                    .iter()
+                    .map(|v| v.clone().map_or(Value::Null, Value::String))
+                    .collect();
+                Record::ArraySingleString(ArraySingleString {
+                    object_id,
+                    length: values.len() as i32,
+                    element_values: self.build_elements(&values)?,
+                })
+            }
+            Value::Object(class, values) => {
+                let object_id = self.object_id(id);
+                self.build_object(object_id, class, values)?
+            }
+        };
+        Ok(Some(record))
+    }
+
+    fn build_object(
+        &mut self,
+        object_id: i32,
+        class: &ClassDef,
+        values: &[Value],
+    ) -> Result<Record> {
+        if values.len() != class.members.len() {
+            return Err(Error::Custom(format!(
+                "Class {} has {} members but {} values given",
+                class.name,
+                class.members.len(),
+                values.len()
+            )));
+        }
+
+        let key = (class.name.clone(), class.library.clone());
+        match self.classes.get(&key) {
+            Some((metadata_id, known)) if known == class => {
+                let metadata_id = *metadata_id;
+                Ok(Record::ClassWithId(ClassWithId {
+                    object_id,
+                    metadata_id,
+                    member_values: self.build_members(class, values)?,
+                }))
+            }
+            Some(_) => Err(Error::Custom(format!(
+                "Conflicting definitions for class {}",
+                class.name
+            ))),
+            None => {
+                let library_id = class.library.as_deref().map(|name| self.library_id(name));
+                let member_type_info = self.member_type_info(class);
+                self.classes.insert(key.clone(), (object_id, class.clone()));
+                self.defined.push(key);
+                let class_info = class_info(object_id, class);
+                let member_values = self.build_members(class, values)?;
+                Ok(match library_id {
+                    Some(library_id) => {
+                        Record::ClassWithMembersAndTypes(ClassWithMembersAndTypes {
+                            class_info,
+                            member_type_info,
+                            library_id,
+                            member_values,
+                        })
+                    }
+                    None => {
+                        Record::SystemClassWithMembersAndTypes(SystemClassWithMembersAndTypes {
+                            class_info,
+                            member_type_info,
+                            member_values,
+                        })
+                    }
+                })
+            }
+        }
+    }
+
+    fn build_members(&mut self, class: &ClassDef, values: &[Value]) -> Result<Vec<ObjectValue>> {
+        let mut member_values = Vec::with_capacity(values.len());
+        for ((name, member_type), value) in class.members.iter().zip(values) {
+            member_values.push(match (member_type, value) {
+                (MemberType::Primitive(pt), Value::Primitive(p)) if p.primitive_type() == *pt => {
+                    ObjectValue::Primitive(p.clone())
+                }
+                (MemberType::Primitive(pt), _) => {
+                    return Err(Error::Custom(format!(
+                        "Member {} of class {} expects a {:?} value, found {:?}",
+                        name, class.name, pt, value
+                    )));
+                }
+                _ => self.value(value)?,
+            });
+        }
+        Ok(member_values)
+    }
+
+    fn member_type_info(&mut self, class: &ClassDef) -> MemberTypeInfo {
+        let mut binary_type_enums = Vec::with_capacity(class.members.len());
+        let mut additional_infos = Vec::with_capacity(class.members.len());
+        for (_, member_type) in &class.members {
+            let (bt, info) = match member_type {
+                MemberType::Primitive(pt) => {
+                    (BinaryType::Primitive, AdditionalTypeInfo::Primitive(*pt))
+                }
+                MemberType::String => (BinaryType::String, AdditionalTypeInfo::None),
+                MemberType::Object => (BinaryType::Object, AdditionalTypeInfo::None),
+                MemberType::SystemClass(name) => (
+                    BinaryType::SystemClass,
+                    AdditionalTypeInfo::SystemClass(name.clone()),
+                ),
+                MemberType::Class { name, library } => (
+                    BinaryType::Class,
+                    AdditionalTypeInfo::Class(ClassTypeInfo {
+                        type_name: name.clone(),
+                        library_id: self.library_id(library),
+                    }),
+                ),
+                MemberType::ObjectArray => (BinaryType::ObjectArray, AdditionalTypeInfo::None),
+                MemberType::StringArray => (BinaryType::StringArray, AdditionalTypeInfo::None),
+                MemberType::PrimitiveArray(pt) => (
+                    BinaryType::PrimitiveArray,
+                    AdditionalTypeInfo::Primitive(*pt),
+                ),
+            };
+            binary_type_enums.push(bt);
+            additional_infos.push(info);
+        }
+        MemberTypeInfo {
+            binary_type_enums,
+            additional_infos,
+        }
+    }
+
+    /// Builds a value for a slot that holds records.
+    ///
+    /// Primitives in such slots are wrapped in `MemberPrimitiveTyped`.
+    pub(crate) fn build_value(&mut self, value: &Value) -> Result<ObjectValue> {
+        self.undo_on_error(|builder| builder.value(value))
+    }
+
+    fn value(&mut self, value: &Value) -> Result<ObjectValue> {
+        let record = match value {
+            Value::Primitive(p) => Record::MemberPrimitiveTyped {
+                primitive_type_enum: p.primitive_type(),
+                value: p.clone(),
+            },
+            _ => self.record(None, value)?.unwrap_or(Record::ObjectNull),
+        };
+        Ok(ObjectValue::Record(Box::new(record)))
+    }
+
+    /// Builds array elements, collapsing runs of nulls the way .NET does.
+    fn build_elements(&mut self, values: &[Value]) -> Result<Vec<ObjectValue>> {
+        let mut elements = Vec::with_capacity(values.len());
+        let mut nulls = 0;
+        for (i, value) in values.iter().enumerate() {
+            if let Value::Null = value {
+                nulls += 1;
+                if values.get(i + 1).is_some_and(|v| matches!(v, Value::Null)) {
+                    continue;
+                }
+                elements.push(ObjectValue::Record(Box::new(null_run(nulls))));
+                nulls = 0;
+            } else {
+                elements.push(self.value(value)?);
+            }
+        }
+        Ok(elements)
+    }
+}
+
+/// Returns the record .NET writes for `count` consecutive null elements.
+pub(crate) fn null_run(count: i32) -> Record {
+    match count {
+        1 => Record::ObjectNull,
+        2..=255 => Record::ObjectNullMultiple256(ObjectNullMultiple256 {
+            null_count: count as u8,
+        }),
+        _ => Record::ObjectNullMultiple(ObjectNullMultiple { null_count: count }),
+    }
+}
+
+fn class_info(object_id: i32, class: &ClassDef) -> ClassInfo {
+    ClassInfo {
+        object_id,
+        name: class.name.clone(),
+        member_count: class.members.len() as i32,
+        member_names: class.members.iter().map(|(name, _)| name.clone()).collect(),
+    }
+}
@@ -0,0 +1,283 @@
+// nrbf-parser - A high-performance MS-NRBF binary parser and encoder.
+// Copyright (C) 2026  driedpampas@proton.me
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Canonical form of messages, for comparing and hashing object graphs.
+//!
+//! Two saves of the same graph can number their objects differently, order
+//! their records differently, write a value inline or refer to it, and
+//! split nulls into runs differently. [`canonicalize`] rewrites a message so
+//! that such saves come out identical, walking the graph breadth-first from
+//! the root and laying the records out the way BinaryFormatter writes them:
+//!
+//! - Objects are numbered from 1 in the order they are reached. Libraries
+//!   share the counter and are numbered when first needed.
+//! - Strings are written inline where they are first reached, and every
+//!   other object as a top-level record referred to by `MemberReference`.
+//! - The first object of each class defines the class and later ones use
+//!   `ClassWithId`. Classes and libraries are matched by content and name,
+//!   not by id.
+//! - Consecutive nulls in arrays become one null run, and null members are
+//!   written as `ObjectNull`.
+//!
+//! Objects that cannot be reached from the root are dropped.
+
+use crate::decoder::ClassInfoWithTypes;
+use crate::error::{Error, Result};
+use crate::patch::{class_record, compress, expand};
+use crate::plain::Graph;
+use crate::records::*;
+use std::collections::{HashMap, VecDeque};
+
+/// Rewrites a message into its canonical form.
+///
+/// Records implement `Eq` and `Hash`, so canonical forms can be compared
+/// and hashed directly. Fails if the message has no header, or refers to
+/// objects, class metadata or libraries it does not define.
+pub fn canonicalize(records: &[Record]) -> Result<Vec<Record>> {
+    let Some(Record::SerializationHeader(header)) = records.first() else {
+        return Err(Error::Custom(
+            "Message must start with SerializationHeader".into(),
+        ));
+    };
+    let mut canonical = Canonicalizer {
+        graph: Graph::new(records),
+        library_names: records
+            .iter()
+            .filter_map(|record| match record {
+                Record::BinaryLibrary(l) => Some((l.library_id, l.library_name.as_str())),
+                _ => None,
+            })
+            .collect(),
+        ids: HashMap::new(),
+        next_id: 1,
+        queue: VecDeque::new(),
+        classes: HashMap::new(),
+        libraries: HashMap::new(),
+        records: Vec::new(),
+    };
+    if !canonical.graph.objects.contains_key(&header.root_id) {
+        return Err(Error::Custom(format!(
+            "root_id {} does not refer to any object",
+            header.root_id
+        )));
+    }
+    let root_id = canonical.next_id();
+    canonical.ids.insert(header.root_id, root_id);
+    canonical.queue.push_back(header.root_id);
+    canonical
+        .records
+        .push(Record::SerializationHeader(SerializationHeader {
+            root_id,
+            ..header.clone()
+        }));
+
+    while let Some(id) = canonical.queue.pop_front() {
+        let record = canonical.object(id)?;
+        canonical.records.push(record);
+    }
+    canonical.records.push(Record::MessageEnd);
+    Ok(canonical.records)
+}
+
+struct Canonicalizer<'a> {
+    graph: Graph<'a>,
+    library_names: HashMap<i32, &'a str>,
+    /// Old object ids and their canonical ids.
+    ids: HashMap<i32, i32>,
+    next_id: i32,
+    /// Old ids of the objects still to be written at the top level.
+    queue: VecDeque<i32>,
+    /// Classes defined so far, with canonical library ids and no object id.
+    classes: HashMap<ClassInfoWithTypes, i32>,
+    /// Library names and their canonical ids.
+    libraries: HashMap<&'a str, i32>,
+    records: Vec<Record>,
+}
+
+impl<'a> Canonicalizer<'a> {
+    fn next_id(&mut self) -> i32 {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+
+    /// Rewrites the record of object `old` for the top level.
+    fn object(&mut self, old: i32) -> Result<Record> {
+        let record = self.graph.objects[&old];
+        let object_id = self.ids[&old];
+        Ok(match record {
+            Record::BinaryObjectString { value, .. } => Record::BinaryObjectString {
+                object_id,
+                value: value.clone(),
+            },
+            Record::ArraySinglePrimitive(a) => Record::ArraySinglePrimitive(ArraySinglePrimitive {
+                object_id,
+                ..a.clone()
+            }),
+            Record::ArraySingleObject(a) => Record::ArraySingleObject(ArraySingleObject {
+                object_id,
+                length: a.length,
+                element_values: self.elements(&a.element_values)?,
+            }),
+            Record::ArraySingleString(a) => Record::ArraySingleString(ArraySingleString {
+                object_id,
+                length: a.length,
+                element_values: self.elements(&a.element_values)?,
+            }),
+            Record::BinaryArray(a) => {
+                let additional_type_info = self.type_info(&a.additional_type_info)?;
+                Record::BinaryArray(BinaryArray {
+                    object_id,
+                    binary_array_type_enum: a.binary_array_type_enum,
+                    rank: a.rank,
+                    lengths: a.lengths.clone(),
+                    lower_bounds: a.lower_bounds.clone(),
+                    type_enum: a.type_enum,
+                    additional_type_info,
+                    element_values: self.elements(&a.element_values)?,
+                })
+            }
+            _ => self.class(record, object_id)?,
+        })
+    }
+
+    /// Rewrites a class record, defining its class if this is the first
+    /// object of it.
+    fn class(&mut self, record: &Record, object_id: i32) -> Result<Record> {
+        let class = self.graph.class_of(record).cloned().ok_or_else(|| {
+            Error::Custom(format!(
+                "Object {} has no class metadata",
+                record.object_id().unwrap_or_default()
+            ))
+        })?;
+        let library_id = class.library_id.map(|id| self.library(id)).transpose()?;
+        let member_type_info = match class.member_type_info {
+            Some(mut info) => {
+                for additional in &mut info.additional_infos {
+                    *additional = self.type_info(additional)?;
+                }
+                Some(info)
+            }
+            None => None,
+        };
+        let class = ClassInfoWithTypes {
+            class_info: ClassInfo {
+                object_id: 0,
+                ..class.class_info
+            },
+            member_type_info,
+            library_id,
+        };
+        let member_values = expand(record.member_values().unwrap_or_default())
+            .iter()
+            .map(|value| self.value(value))
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(match self.classes.get(&class) {
+            Some(&metadata_id) => Record::ClassWithId(ClassWithId {
+                object_id,
+                metadata_id,
+                member_values,
+            }),
+            None => {
+                let record = class_record(object_id, &class, member_values);
+                self.classes.insert(class, object_id);
+                record
+            }
+        })
+    }
+
+    fn elements(&mut self, values: &[ObjectValue]) -> Result<Vec<ObjectValue>> {
+        let values = expand(values)
+            .iter()
+            .map(|value| self.value(value))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(compress(values))
+    }
+
+    fn value(&mut self, value: &ObjectValue) -> Result<ObjectValue> {
+        match value {
+            ObjectValue::Primitive(PrimitiveValue::Null) => {
+                Ok(ObjectValue::Record(Box::new(Record::ObjectNull)))
+            }
+            ObjectValue::Primitive(_) => Ok(value.clone()),
+            ObjectValue::Record(record) => match record.as_ref() {
+                Record::MemberReference { id_ref } => self.reference(*id_ref),
+                record => match record.object_id() {
+                    Some(id) => self.reference(id),
+                    None => Ok(value.clone()),
+                },
+            },
+        }
+    }
+
+    /// Refers to object `old`, numbering it and writing or queueing it the
+    /// first time it is reached.
+    fn reference(&mut self, old: i32) -> Result<ObjectValue> {
+        if let Some(&id_ref) = self.ids.get(&old) {
+            return Ok(ObjectValue::Record(Box::new(Record::MemberReference {
+                id_ref,
+            })));
+        }
+        let record = self
+            .graph
+            .objects
+            .get(&old)
+            .copied()
+            .ok_or_else(|| Error::Custom(format!("Reference to unknown object id {}", old)))?;
+        let object_id = self.next_id();
+        self.ids.insert(old, object_id);
+        if let Record::BinaryObjectString { value, .. } = record {
+            return Ok(ObjectValue::Record(Box::new(Record::BinaryObjectString {
+                object_id,
+                value: value.clone(),
+            })));
+        }
+        self.queue.push_back(old);
+        Ok(ObjectValue::Record(Box::new(Record::MemberReference {
+            id_ref: object_id,
+        })))
+    }
+
+    fn type_info(&mut self, info: &AdditionalTypeInfo) -> Result<AdditionalTypeInfo> {
+        Ok(match info {
+            AdditionalTypeInfo::Class(c) => AdditionalTypeInfo::Class(ClassTypeInfo {
+                type_name: c.type_name.clone(),
+                library_id: self.library(c.library_id)?,
+            }),
+            info => info.clone(),
+        })
+    }
+
+    /// Returns the canonical id of library `old`, writing the library the
+    /// first time it is needed.
+    fn library(&mut self, old: i32) -> Result<i32> {
+        let name = *self
+            .library_names
+            .get(&old)
+            .ok_or_else(|| Error::Custom(format!("Library id {} is not defined", old)))?;
+        if let Some(&id) = self.libraries.get(name) {
+            return Ok(id);
+        }
+        let library_id = self.next_id();
+        self.libraries.insert(name, library_id);
+        self.records.push(Record::BinaryLibrary(BinaryLibrary {
+            library_id,
+            library_name: name.to_string(),
+        }));
+        Ok(library_id)
+    }
+}
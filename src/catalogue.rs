@@ -0,0 +1,274 @@
+// nrbf-parser - A high-performance MS-NRBF binary parser and encoder.
+// Copyright (C) 2026  driedpampas@proton.me
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Class catalogues inferred from many decoded streams.
+//!
+//! A [`Catalogue`] collects the class metadata of every file added to it,
+//! keyed by class name and library name, and records for each member which
+//! files had it and with which type. Library ids only mean something within
+//! one message, so libraries are identified by name throughout, and classes
+//! whose library is not defined are kept apart from system classes.
+
+use crate::decoder::ClassInfoWithTypes;
+use crate::records::*;
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// Every class seen across a set of files.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Catalogue {
+    /// Names of the files added, in order.
+    pub files: Vec<String>,
+    /// Classes in the order they were first seen.
+    pub classes: Vec<ClassEntry>,
+    #[serde(skip)]
+    index: HashMap<(String, Library), usize>,
+}
+
+/// The library a class belongs to.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Library {
+    /// A system class, which has no library.
+    System,
+    Named(String),
+    /// A library id that no `BinaryLibrary` record of the message defines.
+    Unresolved,
+}
+
+/// A class, identified by its name and the name of its library.
+#[derive(Debug, Clone, Serialize)]
+pub struct ClassEntry {
+    pub name: String,
+    pub library: Library,
+    /// Files defining the class.
+    pub files: Vec<String>,
+    /// Members in the order they were first seen.
+    pub members: Vec<MemberEntry>,
+}
+
+/// A member seen in at least one definition of its class.
+#[derive(Debug, Clone, Serialize)]
+pub struct MemberEntry {
+    pub name: String,
+    /// Number of files whose definition of the class has the member.
+    pub present_in: usize,
+    /// Files defining the class without the member.
+    pub missing_from: Vec<String>,
+    /// Each distinct type the member was declared with.
+    pub types: Vec<TypeEntry>,
+}
+
+/// A member type and the files that declared it.
+#[derive(Debug, Clone, Serialize)]
+pub struct TypeEntry {
+    /// `None` when the class record carries no `MemberTypeInfo`.
+    pub binary_type: Option<BinaryType>,
+    /// For `Class` members, `library_id` is the id in the first file seen;
+    /// compare `library` instead.
+    pub additional_info: Option<AdditionalTypeInfo>,
+    /// The library name of a `Class` member's type.
+    pub library: Option<String>,
+    pub files: Vec<String>,
+}
+
+impl Catalogue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the classes defined by the records of one file.
+    ///
+    /// `records` may hold several messages; library ids are resolved within
+    /// the message that defines them.
+    pub fn add_file(&mut self, file: impl Into<String>, records: &[Record]) {
+        let file = file.into();
+        let mut libraries = HashMap::new();
+        let mut classes = Vec::new();
+        for record in records {
+            if let Record::SerializationHeader(_) = record {
+                libraries.clear();
+            }
+            collect_classes(record, &mut libraries, &mut classes);
+        }
+
+        // A class defined several times in a file counts once.
+        let mut seen = Vec::new();
+        for (class, library, members) in classes {
+            let key = (class.class_info.name.clone(), library);
+            if seen.contains(&key) {
+                continue;
+            }
+            seen.push(key.clone());
+            let index = *self.index.entry(key.clone()).or_insert_with(|| {
+                self.classes.push(ClassEntry {
+                    name: key.0,
+                    library: key.1,
+                    files: Vec::new(),
+                    members: Vec::new(),
+                });
+                self.classes.len() - 1
+            });
+            self.classes[index].add(&file, &members);
+        }
+        self.files.push(file);
+    }
+
+    /// Looks up a class by name and library.
+    pub fn class(&self, name: &str, library: &Library) -> Option<&ClassEntry> {
+        let key = (name.to_string(), library.clone());
+        self.index.get(&key).map(|&i| &self.classes[i])
+    }
+}
+
+impl ClassEntry {
+    fn add(&mut self, file: &str, members: &[ObservedMember]) {
+        for member in &mut self.members {
+            if !members.iter().any(|m| m.name == member.name) {
+                member.missing_from.push(file.to_string());
+            }
+        }
+        for observed in members {
+            let index = match self.members.iter().position(|m| m.name == observed.name) {
+                Some(index) => index,
+                None => {
+                    self.members.push(MemberEntry {
+                        name: observed.name.clone(),
+                        present_in: 0,
+                        missing_from: self.files.clone(),
+                        types: Vec::new(),
+                    });
+                    self.members.len() - 1
+                }
+            };
+            self.members[index].add(file, observed);
+        }
+        self.files.push(file.to_string());
+    }
+
+    /// Returns whether every file defined the class with the same members
+    /// and types.
+    pub fn is_consistent(&self) -> bool {
+        self.members
+            .iter()
+            .all(|m| m.missing_from.is_empty() && m.types.len() <= 1)
+    }
+}
+
+impl MemberEntry {
+    fn add(&mut self, file: &str, observed: &ObservedMember) {
+        self.present_in += 1;
+        let existing = self.types.iter_mut().find(|t| {
+            t.binary_type == observed.binary_type
+                && t.library == observed.library
+                && same_additional_info(
+                    t.additional_info.as_ref(),
+                    observed.additional_info.as_ref(),
+                )
+        });
+        match existing {
+            Some(entry) => entry.files.push(file.to_string()),
+            None => self.types.push(TypeEntry {
+                binary_type: observed.binary_type,
+                additional_info: observed.additional_info.clone(),
+                library: observed.library.clone(),
+                files: vec![file.to_string()],
+            }),
+        }
+    }
+
+    /// Returns the share of the files defining the class that have the
+    /// member, from 0 to 1.
+    pub fn frequency(&self) -> f64 {
+        let total = self.present_in + self.missing_from.len();
+        if total == 0 {
+            return 0.0;
+        }
+        self.present_in as f64 / total as f64
+    }
+}
+
+struct ObservedMember {
+    name: String,
+    binary_type: Option<BinaryType>,
+    additional_info: Option<AdditionalTypeInfo>,
+    library: Option<String>,
+}
+
+type ObservedClass = (ClassInfoWithTypes, Library, Vec<ObservedMember>);
+
+/// Collects the classes of `record` and its children in stream order,
+/// registering the libraries defined along the way.
+fn collect_classes(
+    record: &Record,
+    libraries: &mut HashMap<i32, String>,
+    classes: &mut Vec<ObservedClass>,
+) {
+    if let Record::BinaryLibrary(l) = record {
+        libraries.insert(l.library_id, l.library_name.clone());
+    }
+    if let Some(class) = ClassInfoWithTypes::from_record(record) {
+        let library = match class.library_id {
+            None => Library::System,
+            Some(id) => libraries
+                .get(&id)
+                .cloned()
+                .map_or(Library::Unresolved, Library::Named),
+        };
+        let members = class
+            .class_info
+            .member_names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| {
+                let mti = class.member_type_info.as_ref();
+                let binary_type = mti.and_then(|mti| mti.binary_type_enums.get(i).copied());
+                let additional_info = mti.and_then(|mti| mti.additional_infos.get(i).cloned());
+                let library = match &additional_info {
+                    Some(AdditionalTypeInfo::Class(c)) => libraries.get(&c.library_id).cloned(),
+                    _ => None,
+                };
+                ObservedMember {
+                    name: name.clone(),
+                    binary_type,
+                    additional_info,
+                    library,
+                }
+            })
+            .collect();
+        classes.push((class, library, members));
+    }
+    for value in record.child_values() {
+        if let ObjectValue::Record(r) = value {
+            collect_classes(r, libraries, classes);
+        }
+    }
+}
+
+/// Compares type info, ignoring the message-scoped library id of classes.
+fn same_additional_info(a: Option<&AdditionalTypeInfo>, b: Option<&AdditionalTypeInfo>) -> bool {
+    match (a, b) {
+        (Some(AdditionalTypeInfo::Primitive(a)), Some(AdditionalTypeInfo::Primitive(b))) => a == b,
+        (Some(AdditionalTypeInfo::SystemClass(a)), Some(AdditionalTypeInfo::SystemClass(b))) => {
+            a == b
+        }
+        (Some(AdditionalTypeInfo::Class(a)), Some(AdditionalTypeInfo::Class(b))) => {
+            a.type_name == b.type_name
+        }
+        (Some(AdditionalTypeInfo::None), Some(AdditionalTypeInfo::None)) | (None, None) => true,
+        _ => false,
+    }
+}
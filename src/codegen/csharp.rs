@@ -0,0 +1,345 @@
+// nrbf-parser - A high-performance MS-NRBF binary parser and encoder.
+// Copyright (C) 2026  driedpampas@proton.me
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! C# `[Serializable]` class definitions.
+//!
+//! Classes from a library are emitted in their namespaces, nested types
+//! inside `partial` declarations of their outer types, and members
+//! declared as auto-properties when their name is a compiler-generated
+//! backing field. System classes are left to the framework. A closed generic
+//! class cannot be recovered from one instantiation, so its open generic
+//! type is declared without members, e.g. `Box<T1>` for `Box<int>`.
+
+use super::{TypeName, backing_field_property, enum_underlying_type};
+use crate::builder::{ClassDef, MemberType};
+use crate::records::PrimitiveType;
+use std::fmt::Write;
+
+const KEYWORDS: &str = "\
+    abstract as base bool break byte case catch char checked class const continue decimal \
+    default delegate do double else enum event explicit extern false finally fixed float for \
+    foreach goto if implicit in int interface internal is lock long namespace new null \
+    object operator out override params private protected public readonly ref return sbyte \
+    sealed short sizeof stackalloc static string struct switch this throw true try typeof \
+    uint ulong unchecked unsafe ushort using virtual void volatile while";
+
+/// A class to emit, or a stub for a class only seen as a member type or
+/// as a closed generic type.
+struct Declaration<'a> {
+    /// The name, without generic arguments.
+    name: TypeName,
+    /// The number of generic parameters.
+    arity: usize,
+    class: Option<&'a ClassDef>,
+}
+
+/// Generates a C# source file declaring `classes`.
+///
+/// Class types referenced by members but never defined in the stream are
+/// declared as empty stubs, so the output compiles on its own.
+pub fn generate(classes: &[ClassDef]) -> String {
+    let mut declarations: Vec<Declaration> = Vec::new();
+    let mut stubs = Vec::new();
+    let mut generic = Vec::new();
+    for class in classes.iter().filter(|c| c.library.is_some()) {
+        match TypeName::parse(&class.name) {
+            Some(name) if name.args.is_empty() && name.array_ranks.is_empty() => {
+                declarations.push(Declaration {
+                    name,
+                    arity: 0,
+                    class: Some(class),
+                })
+            }
+            Some(name) if name.array_ranks.is_empty() => {
+                generic.push(class.name.as_str());
+                stubs.push(name);
+            }
+            _ => {}
+        }
+        for (_, member_type) in &class.members {
+            if let MemberType::Class { name, .. } = member_type
+                && let Some(mut name) = TypeName::parse(name)
+            {
+                name.array_ranks.clear();
+                stubs.push(name);
+            }
+        }
+    }
+    for mut name in stubs {
+        let arity = std::mem::take(&mut name.args).len();
+        if !declarations
+            .iter()
+            .any(|d| d.name == name && d.arity == arity)
+        {
+            declarations.push(Declaration {
+                name,
+                arity,
+                class: None,
+            });
+        }
+    }
+
+    let mut out = String::from("// <auto-generated />\n");
+    for name in &generic {
+        let _ = writeln!(
+            out,
+            "// Declared without members (closed generic type): {}",
+            name
+        );
+    }
+    out.push_str("using System;\n");
+
+    let mut namespaces: Vec<Option<&str>> = Vec::new();
+    for declaration in &declarations {
+        let namespace = declaration.name.namespace.as_deref();
+        if !namespaces.contains(&namespace) {
+            namespaces.push(namespace);
+        }
+    }
+    for namespace in namespaces {
+        out.push('\n');
+        let indent = match namespace {
+            Some(namespace) => {
+                let _ = writeln!(out, "namespace {}\n{{", namespace);
+                1
+            }
+            None => 0,
+        };
+        let mut first = true;
+        for declaration in declarations
+            .iter()
+            .filter(|d| d.name.namespace.as_deref() == namespace)
+        {
+            if !first {
+                out.push('\n');
+            }
+            first = false;
+            write_declaration(&mut out, declaration, indent);
+        }
+        if namespace.is_some() {
+            out.push_str("}\n");
+        }
+    }
+    out
+}
+
+fn write_declaration(out: &mut String, declaration: &Declaration, indent: usize) {
+    let path = &declaration.name.path;
+    let (outer, name) = path.split_at(path.len() - 1);
+    for (depth, outer) in outer.iter().enumerate() {
+        line(
+            out,
+            indent + depth,
+            &format!("public partial class {}", identifier(outer)),
+        );
+        line(out, indent + depth, "{");
+    }
+    let indent = indent + outer.len();
+    let mut name = identifier(&name[0]);
+    let namespace = declaration.name.namespace.as_deref();
+
+    match declaration.class {
+        Some(class) => {
+            if let Some(library) = &class.library {
+                line(out, indent, &format!("// {}", library));
+            }
+            if let Some(underlying) = enum_underlying_type(class) {
+                line(
+                    out,
+                    indent,
+                    &format!("public enum {} : {}", name, primitive(underlying)),
+                );
+                line(out, indent, "{");
+                line(out, indent, "}");
+            } else {
+                line(out, indent, "[Serializable]");
+                line(out, indent, &format!("public partial class {}", name));
+                line(out, indent, "{");
+                write_members(out, class, &name, namespace, indent + 1);
+                line(out, indent, "}");
+            }
+        }
+        None if declaration.arity > 0 => {
+            let parameters: Vec<String> =
+                (1..=declaration.arity).map(|i| format!("T{}", i)).collect();
+            let _ = write!(name, "<{}>", parameters.join(", "));
+            line(out, indent, "// Generic type; members unknown.");
+            line(out, indent, "[Serializable]");
+            line(out, indent, &format!("public partial class {}", name));
+            line(out, indent, "{");
+            line(out, indent, "}");
+        }
+        None => {
+            line(
+                out,
+                indent,
+                "// No instance in the stream; members unknown.",
+            );
+            line(out, indent, "[Serializable]");
+            line(out, indent, &format!("public partial class {}", name));
+            line(out, indent, "{");
+            line(out, indent, "}");
+        }
+    }
+
+    for depth in (0..outer.len()).rev() {
+        line(out, indent - outer.len() + depth, "}");
+    }
+}
+
+/// Writes the members of a class, renaming those that would clash with
+/// each other or with the class name, which C# does not allow.
+fn write_members(
+    out: &mut String,
+    class: &ClassDef,
+    class_name: &str,
+    namespace: Option<&str>,
+    indent: usize,
+) {
+    let mut used: Vec<String> = Vec::new();
+    for (member, member_type) in &class.members {
+        let property = backing_field_property(member);
+        let mut name = identifier(property.unwrap_or(member));
+        while used.contains(&name) || name == class_name {
+            name.push('_');
+        }
+        used.push(name.clone());
+        let type_name = type_name(member_type, namespace);
+        if name.trim_start_matches('@') != property.unwrap_or(member) {
+            line(out, indent, &format!("// Serialized as \"{}\".", member));
+        }
+        let declaration = match property {
+            Some(_) => format!("public {} {} {{ get; set; }}", type_name, name),
+            None => format!("public {} {};", type_name, name),
+        };
+        line(out, indent, &declaration);
+    }
+}
+
+fn type_name(member_type: &MemberType, namespace: Option<&str>) -> String {
+    match member_type {
+        MemberType::Primitive(t) => primitive(*t).to_string(),
+        MemberType::String => "string".to_string(),
+        MemberType::Object => "object".to_string(),
+        MemberType::SystemClass(name) | MemberType::Class { name, .. } => {
+            TypeName::parse(name).map_or_else(|| "object".to_string(), |t| render(&t, namespace))
+        }
+        MemberType::ObjectArray => "object[]".to_string(),
+        MemberType::StringArray => "string[]".to_string(),
+        MemberType::PrimitiveArray(t) => format!("{}[]", primitive(*t)),
+    }
+}
+
+/// Renders a parsed .NET type name as a C# type.
+fn render(t: &TypeName, namespace: Option<&str>) -> String {
+    if let Some(element) = t.element() {
+        let rank = t.array_ranks.last().copied().unwrap_or(1);
+        return format!("{}[{}]", render(&element, namespace), ",".repeat(rank - 1));
+    }
+    if let Some(keyword) = system_keyword(t) {
+        return keyword.to_string();
+    }
+    let mut rendered = String::new();
+    if let Some(ns) = &t.namespace
+        && Some(ns.as_str()) != namespace
+    {
+        rendered.push_str(ns);
+        rendered.push('.');
+    }
+    let path: Vec<String> = t.path.iter().map(|segment| identifier(segment)).collect();
+    rendered.push_str(&path.join("."));
+    if !t.args.is_empty() {
+        let args: Vec<String> = t.args.iter().map(|arg| render(arg, namespace)).collect();
+        let _ = write!(rendered, "<{}>", args.join(", "));
+    }
+    rendered
+}
+
+fn system_keyword(t: &TypeName) -> Option<&'static str> {
+    const KEYWORD_TYPES: [(&str, &str); 16] = [
+        ("Boolean", "bool"),
+        ("Byte", "byte"),
+        ("SByte", "sbyte"),
+        ("Char", "char"),
+        ("Decimal", "decimal"),
+        ("Double", "double"),
+        ("Single", "float"),
+        ("Int16", "short"),
+        ("UInt16", "ushort"),
+        ("Int32", "int"),
+        ("UInt32", "uint"),
+        ("Int64", "long"),
+        ("UInt64", "ulong"),
+        ("String", "string"),
+        ("Object", "object"),
+        ("Void", "void"),
+    ];
+    KEYWORD_TYPES
+        .iter()
+        .find(|(name, _)| t.is_system(name))
+        .map(|(_, keyword)| *keyword)
+}
+
+fn primitive(t: PrimitiveType) -> &'static str {
+    match t {
+        PrimitiveType::Boolean => "bool",
+        PrimitiveType::Byte => "byte",
+        PrimitiveType::Char => "char",
+        PrimitiveType::Decimal => "decimal",
+        PrimitiveType::Double => "double",
+        PrimitiveType::Int16 => "short",
+        PrimitiveType::Int32 => "int",
+        PrimitiveType::Int64 => "long",
+        PrimitiveType::SByte => "sbyte",
+        PrimitiveType::Single => "float",
+        PrimitiveType::TimeSpan => "TimeSpan",
+        PrimitiveType::DateTime => "DateTime",
+        PrimitiveType::UInt16 => "ushort",
+        PrimitiveType::UInt32 => "uint",
+        PrimitiveType::UInt64 => "ulong",
+        PrimitiveType::String => "string",
+        PrimitiveType::Null => "object",
+    }
+}
+
+/// Turns a name into a valid C# identifier.
+fn identifier(name: &str) -> String {
+    let mut id: String = name
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if id.is_empty() || id.starts_with(|c: char| c.is_ascii_digit()) {
+        id.insert(0, '_');
+    }
+    if KEYWORDS.split_whitespace().any(|keyword| keyword == id) {
+        id.insert(0, '@');
+    }
+    id
+}
+
+fn line(out: &mut String, indent: usize, text: &str) {
+    for _ in 0..indent {
+        out.push_str("    ");
+    }
+    out.push_str(text);
+    out.push('\n');
+}
@@ -0,0 +1,336 @@
+// nrbf-parser - A high-performance MS-NRBF binary parser and encoder.
+// Copyright (C) 2026  driedpampas@proton.me
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Source code generation from the classes of a decoded stream.
+//!
+//! [`classes`] collects the class metadata of a stream as
+//! [`ClassDef`]s, and the submodules turn those into source code.
+
+pub mod csharp;
+pub mod rust;
+pub mod typescript;
+
+use crate::builder::{ClassDef, MemberType};
+use crate::decoder::ClassInfoWithTypes;
+use crate::records::*;
+use std::collections::HashMap;
+
+/// Collects the classes defined in `records`, in stream order.
+///
+/// Library ids are resolved to library names, and members of classes
+/// written without `MemberTypeInfo`, or whose class type names a library
+/// that is not defined, are typed as [`MemberType::Object`]. Classes from
+/// undefined libraries have no library.
+/// Only the first definition of each class and library is kept.
+pub fn classes(records: &[Record]) -> Vec<ClassDef> {
+    let mut libraries = HashMap::new();
+    let mut classes = Vec::new();
+    for record in records {
+        match record {
+            Record::SerializationHeader(_) => libraries.clear(),
+            Record::BinaryLibrary(l) => {
+                libraries.insert(l.library_id, l.library_name.clone());
+            }
+            _ => {}
+        }
+        collect_classes(record, &libraries, &mut classes);
+    }
+    classes
+}
+
+fn collect_classes(record: &Record, libraries: &HashMap<i32, String>, classes: &mut Vec<ClassDef>) {
+    if let Some(class) = ClassInfoWithTypes::from_record(record) {
+        let def = class_def(&class, |id| libraries.get(&id).cloned());
+        if !classes
+            .iter()
+            .any(|c: &ClassDef| c.name == def.name && c.library == def.library)
+        {
+            classes.push(def);
+        }
+    }
+    for value in record.child_values() {
+        if let ObjectValue::Record(r) = value {
+            collect_classes(r, libraries, classes);
+        }
+    }
+}
+
+/// Builds the definition of a class, naming libraries with `library`.
+///
+/// Members without type info are typed as [`MemberType::Object`].
+pub(crate) fn class_def(
+    class: &ClassInfoWithTypes,
+    library: impl Fn(i32) -> Option<String>,
+) -> ClassDef {
+    let mut def = ClassDef::new(class.class_info.name.clone(), None);
+    def.library = class.library_id.and_then(&library);
+    for (i, name) in class.class_info.member_names.iter().enumerate() {
+        let member_type = class
+            .member_type_info
+            .as_ref()
+            .and_then(|mti| {
+                member_type(
+                    mti.binary_type_enums.get(i)?,
+                    mti.additional_infos.get(i)?,
+                    &library,
+                )
+            })
+            .unwrap_or(MemberType::Object);
+        def = def.member(name.clone(), member_type);
+    }
+    def
+}
+
+fn member_type(
+    binary_type: &BinaryType,
+    additional: &AdditionalTypeInfo,
+    library: impl Fn(i32) -> Option<String>,
+) -> Option<MemberType> {
+    Some(match (binary_type, additional) {
+        (BinaryType::Primitive, AdditionalTypeInfo::Primitive(t)) => MemberType::Primitive(*t),
+        (BinaryType::String, _) => MemberType::String,
+        (BinaryType::Object, _) => MemberType::Object,
+        (BinaryType::SystemClass, AdditionalTypeInfo::SystemClass(name)) => {
+            MemberType::SystemClass(name.clone())
+        }
+        (BinaryType::Class, AdditionalTypeInfo::Class(c)) => MemberType::Class {
+            name: c.type_name.clone(),
+            library: library(c.library_id)?,
+        },
+        (BinaryType::ObjectArray, _) => MemberType::ObjectArray,
+        (BinaryType::StringArray, _) => MemberType::StringArray,
+        (BinaryType::PrimitiveArray, AdditionalTypeInfo::Primitive(t)) => {
+            MemberType::PrimitiveArray(*t)
+        }
+        _ => return None,
+    })
+}
+
+/// Returns the property name of a compiler-generated backing field, e.g.
+/// `Level` for `<Level>k__BackingField`.
+pub(crate) fn backing_field_property(member: &str) -> Option<&str> {
+    member
+        .strip_prefix('<')?
+        .strip_suffix(">k__BackingField")
+        .filter(|name| !name.is_empty())
+}
+
+/// Returns whether a class is a boxed enum, whose only member is `value__`.
+pub(crate) fn enum_underlying_type(class: &ClassDef) -> Option<PrimitiveType> {
+    match class.members.as_slice() {
+        [(name, MemberType::Primitive(t))] if name == "value__" => Some(*t),
+        _ => None,
+    }
+}
+
+/// Names a type for each non-generic class from a library, in
+/// UpperCamelCase, prefixing the namespace where two classes would share a
+/// name.
+pub(crate) fn type_names(classes: &[ClassDef]) -> Vec<(&ClassDef, String)> {
+    let parsed: Vec<(&ClassDef, TypeName)> = classes
+        .iter()
+        .filter(|c| c.library.is_some())
+        .filter_map(|c| Some((c, TypeName::parse(&c.name)?)))
+        .filter(|(_, t)| t.args.is_empty() && t.array_ranks.is_empty())
+        .collect();
+    let short = |t: &TypeName| t.path.iter().map(|s| type_name(s)).collect::<String>();
+    let mut names: Vec<(&ClassDef, String)> = Vec::new();
+    for (class, t) in &parsed {
+        let mut name = short(t);
+        if parsed
+            .iter()
+            .filter(|(_, other)| short(other) == name)
+            .count()
+            > 1
+        {
+            let namespace = t.namespace.as_deref().unwrap_or_default();
+            name = namespace.split('.').map(type_name).collect::<String>() + &name;
+        }
+        while names.iter().any(|(_, other)| *other == name) {
+            name.push('_');
+        }
+        names.push((class, name));
+    }
+    names
+}
+
+/// Splits a name into lowercase words at case changes and non-alphanumerics.
+pub(crate) fn words(name: &str) -> Vec<String> {
+    let mut words: Vec<String> = Vec::new();
+    let mut current = String::new();
+    let chars: Vec<char> = name.chars().collect();
+    for (i, &c) in chars.iter().enumerate() {
+        if !c.is_alphanumeric() {
+            if !current.is_empty() {
+                words.push(std::mem::take(&mut current));
+            }
+            continue;
+        }
+        let boundary = c.is_uppercase()
+            && i > 0
+            && (chars[i - 1].is_lowercase()
+                || chars[i - 1].is_ascii_digit()
+                || (chars[i - 1].is_uppercase()
+                    && chars.get(i + 1).is_some_and(|n| n.is_lowercase())));
+        if boundary && !current.is_empty() {
+            words.push(std::mem::take(&mut current));
+        }
+        current.extend(c.to_lowercase());
+    }
+    if !current.is_empty() {
+        words.push(current);
+    }
+    words
+}
+
+/// Turns a .NET type name segment into an UpperCamelCase type name.
+fn type_name(name: &str) -> String {
+    let mut type_name: String = words(name)
+        .iter()
+        .map(|word| {
+            let mut chars = word.chars();
+            chars.next().map_or_else(String::new, |first| {
+                first.to_uppercase().chain(chars).collect()
+            })
+        })
+        .collect();
+    if type_name.is_empty() || type_name.starts_with(|c: char| c.is_ascii_digit()) {
+        type_name.insert(0, '_');
+    }
+    type_name
+}
+
+/// A parsed .NET type name, e.g.
+/// ``System.Collections.Generic.List`1[[System.Int32, mscorlib]][]``.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct TypeName {
+    pub namespace: Option<String>,
+    /// The type and the types it is nested in, outermost first, without
+    /// generic arity.
+    pub path: Vec<String>,
+    pub args: Vec<TypeName>,
+    /// Ranks of the array suffixes, e.g. `[2]` for `[,]`.
+    pub array_ranks: Vec<usize>,
+}
+
+impl TypeName {
+    /// Parses a type name, ignoring assembly names of generic arguments.
+    pub fn parse(name: &str) -> Option<Self> {
+        let mut parser = TypeNameParser {
+            chars: name.chars().collect(),
+            pos: 0,
+        };
+        let parsed = parser.type_name()?;
+        (parser.pos == parser.chars.len()).then_some(parsed)
+    }
+
+    /// Returns whether the name refers to `System.<name>`.
+    pub fn is_system(&self, name: &str) -> bool {
+        self.namespace.as_deref() == Some("System")
+            && self.path.len() == 1
+            && self.path[0] == name
+            && self.args.is_empty()
+            && self.array_ranks.is_empty()
+    }
+
+    /// Returns the element type of an array type.
+    pub fn element(&self) -> Option<TypeName> {
+        let mut element = self.clone();
+        element.array_ranks.pop()?;
+        Some(element)
+    }
+}
+
+struct TypeNameParser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl TypeNameParser {
+    fn peek(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.pos + offset).copied()
+    }
+
+    fn type_name(&mut self) -> Option<TypeName> {
+        let start = self.pos;
+        while let Some(c) = self.peek(0) {
+            if matches!(c, '[' | ']' | ',') {
+                break;
+            }
+            self.pos += 1;
+        }
+        let full: String = self.chars[start..self.pos].iter().collect();
+        let full = full.trim();
+        if full.is_empty() {
+            return None;
+        }
+        // The namespace ends at the last dot before the first nested type.
+        let outer_end = full.find('+').unwrap_or(full.len());
+        let (namespace, nested) = match full[..outer_end].rfind('.') {
+            Some(dot) => (Some(full[..dot].to_string()), &full[dot + 1..]),
+            None => (None, full),
+        };
+        let path = nested
+            .split('+')
+            .map(|segment| segment.split('`').next().unwrap_or(segment).to_string())
+            .collect();
+
+        let mut args = Vec::new();
+        if self.peek(0) == Some('[') && !matches!(self.peek(1), Some(']' | ',')) {
+            self.pos += 1;
+            loop {
+                if self.peek(0) == Some('[') {
+                    // An assembly-qualified argument: `[Name, Assembly]`.
+                    self.pos += 1;
+                    args.push(self.type_name()?);
+                    while self.peek(0)? != ']' {
+                        self.pos += 1;
+                    }
+                    self.pos += 1;
+                } else {
+                    args.push(self.type_name()?);
+                }
+                match self.peek(0)? {
+                    ',' => self.pos += 1,
+                    ']' => {
+                        self.pos += 1;
+                        break;
+                    }
+                    _ => return None,
+                }
+            }
+        }
+
+        let mut array_ranks = Vec::new();
+        while self.peek(0) == Some('[') && matches!(self.peek(1), Some(']' | ',')) {
+            self.pos += 1;
+            let mut rank = 1;
+            while self.peek(0)? == ',' {
+                rank += 1;
+                self.pos += 1;
+            }
+            self.pos += 1;
+            array_ranks.push(rank);
+        }
+
+        Some(TypeName {
+            namespace,
+            path,
+            args,
+            array_ranks,
+        })
+    }
+}
@@ -0,0 +1,207 @@
+// Records as serialized by serde, e.g. `serde_json::to_string(&records)`.
+//
+// Wrapped in `lossless::Lossless`, NaNs, infinities and integers beyond
+// 2^53 - 1 are written as strings; otherwise non-finite floats are written
+// as null.
+
+export type RecordType =
+  | "SerializedStreamHeader"
+  | "ClassWithId"
+  | "SystemClassWithMembers"
+  | "ClassWithMembers"
+  | "SystemClassWithMembersAndTypes"
+  | "ClassWithMembersAndTypes"
+  | "BinaryObjectString"
+  | "BinaryArray"
+  | "MemberPrimitiveTyped"
+  | "MemberReference"
+  | "ObjectNull"
+  | "MessageEnd"
+  | "BinaryLibrary"
+  | "ObjectNullMultiple256"
+  | "ObjectNullMultiple"
+  | "ArraySinglePrimitive"
+  | "ArraySingleObject"
+  | "ArraySingleString"
+  | "BinaryMethodCall"
+  | "BinaryMethodReturn";
+
+export type BinaryType =
+  | "Primitive"
+  | "String"
+  | "Object"
+  | "SystemClass"
+  | "Class"
+  | "ObjectArray"
+  | "StringArray"
+  | "PrimitiveArray";
+
+export type PrimitiveType =
+  | "Boolean"
+  | "Byte"
+  | "Char"
+  | "Decimal"
+  | "Double"
+  | "Int16"
+  | "Int32"
+  | "Int64"
+  | "SByte"
+  | "Single"
+  | "TimeSpan"
+  | "DateTime"
+  | "UInt16"
+  | "UInt32"
+  | "UInt64"
+  | "Null"
+  | "String";
+
+export interface SerializationHeader {
+  root_id: number;
+  header_id: number;
+  major_version: number;
+  minor_version: number;
+}
+
+export interface BinaryLibrary {
+  library_id: number;
+  library_name: string;
+}
+
+export interface ClassInfo {
+  object_id: number;
+  name: string;
+  member_count: number;
+  member_names: string[];
+}
+
+export interface ClassTypeInfo {
+  type_name: string;
+  library_id: number;
+}
+
+export type AdditionalTypeInfo =
+  | { Primitive: PrimitiveType }
+  | { SystemClass: string }
+  | { Class: ClassTypeInfo }
+  | "None";
+
+export interface MemberTypeInfo {
+  binary_type_enums: BinaryType[];
+  additional_infos: AdditionalTypeInfo[];
+}
+
+export interface ClassWithMembersAndTypes {
+  class_info: ClassInfo;
+  member_type_info: MemberTypeInfo;
+  library_id: number;
+  member_values: ObjectValue[];
+}
+
+export interface SystemClassWithMembersAndTypes {
+  class_info: ClassInfo;
+  member_type_info: MemberTypeInfo;
+  member_values: ObjectValue[];
+}
+
+export interface SystemClassWithMembers {
+  class_info: ClassInfo;
+  member_values: ObjectValue[];
+}
+
+export interface ClassWithMembers {
+  class_info: ClassInfo;
+  library_id: number;
+  member_values: ObjectValue[];
+}
+
+export interface ClassWithId {
+  object_id: number;
+  metadata_id: number;
+  member_values: ObjectValue[];
+}
+
+export interface BinaryArray {
+  object_id: number;
+  binary_array_type_enum: number;
+  rank: number;
+  lengths: number[];
+  lower_bounds: number[] | null;
+  type_enum: BinaryType;
+  additional_type_info: AdditionalTypeInfo;
+  element_values: ObjectValue[];
+}
+
+export interface ArraySingleObject {
+  object_id: number;
+  length: number;
+  element_values: ObjectValue[];
+}
+
+export interface ArraySinglePrimitive {
+  object_id: number;
+  length: number;
+  primitive_type_enum: PrimitiveType;
+  element_values: PrimitiveValue[];
+}
+
+export interface ArraySingleString {
+  object_id: number;
+  length: number;
+  element_values: ObjectValue[];
+}
+
+export interface ObjectNullMultiple {
+  null_count: number;
+}
+
+export interface ObjectNullMultiple256 {
+  null_count: number;
+}
+
+/** A 64-bit integer, as a string when beyond 2^53 - 1 in lossless mode. */
+export type LosslessInteger = number | string;
+
+/** A float: a string in lossless mode or null otherwise when not finite. */
+export type LosslessFloat = number | string | null;
+
+export type PrimitiveValue =
+  | { Boolean: boolean }
+  | { Byte: number }
+  | { Char: string }
+  /** 16 bytes as 32 hexadecimal digits. */
+  | { Decimal: string }
+  | { Double: LosslessFloat }
+  | { Int16: number }
+  | { Int32: number }
+  | { Int64: LosslessInteger }
+  | { SByte: number }
+  | { Single: LosslessFloat }
+  | { TimeSpan: LosslessInteger }
+  | { DateTime: LosslessInteger }
+  | { UInt16: number }
+  | { UInt32: number }
+  | { UInt64: LosslessInteger }
+  | { String: string }
+  | "Null";
+
+export type ObjectValue = { Primitive: PrimitiveValue } | { Record: Record };
+
+export type Record =
+  | { SerializationHeader: SerializationHeader }
+  | { BinaryLibrary: BinaryLibrary }
+  | { ClassWithMembersAndTypes: ClassWithMembersAndTypes }
+  | { SystemClassWithMembersAndTypes: SystemClassWithMembersAndTypes }
+  | { SystemClassWithMembers: SystemClassWithMembers }
+  | { ClassWithMembers: ClassWithMembers }
+  | { ClassWithId: ClassWithId }
+  | { BinaryObjectString: { object_id: number; value: string } }
+  | { BinaryArray: BinaryArray }
+  | { ArraySingleObject: ArraySingleObject }
+  | { ArraySinglePrimitive: ArraySinglePrimitive }
+  | { ArraySingleString: ArraySingleString }
+  | { MemberPrimitiveTyped: { primitive_type_enum: PrimitiveType; value: PrimitiveValue } }
+  | { MemberReference: { id_ref: number } }
+  | "ObjectNull"
+  | { ObjectNullMultiple: ObjectNullMultiple }
+  | { ObjectNullMultiple256: ObjectNullMultiple256 }
+  | "MessageEnd";
@@ -0,0 +1,248 @@
+// nrbf-parser - A high-performance MS-NRBF binary parser and encoder.
+// Copyright (C) 2026  driedpampas@proton.me
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Rust struct definitions, for use from a `build.rs`.
+//!
+//! Each class from a library becomes a struct with one snake_case field per
+//! member, and each boxed enum a newtype over its integer. With serde
+//! enabled, fields are renamed to their .NET member names so the structs
+//! read and write the JSON of [`to_plain`](crate::plain::to_plain): class
+//! members are [`RefOr`](crate::plain::RefOr)s, since the JSON may refer
+//! back to an object as `{"$ref": id}`, and enum members go through
+//! [`boxed_enum`](crate::plain::boxed_enum). The output contains no inner
+//! attributes, so it can be pulled in with `include!`.
+//!
+//! Values the metadata does not type precisely (`object` members, system
+//! classes, generic classes and classes without an instance in the stream)
+//! are kept as `serde_json::Value`, or as `()` without serde.
+
+use super::{backing_field_property, enum_underlying_type, type_names, words};
+use crate::builder::{ClassDef, MemberType};
+use crate::records::PrimitiveType;
+use std::fmt::Write;
+
+const KEYWORDS: &str = "\
+    as async await break const continue crate dyn else enum extern false fn for if impl in \
+    let loop match mod move mut pub ref return self Self static struct super trait true type \
+    unsafe use where while abstract become box do final gen macro override priv try typeof \
+    unsized virtual yield";
+
+/// Generates Rust structs with serde attributes for `classes`.
+pub fn generate(classes: &[ClassDef]) -> String {
+    RustGenerator::new().generate(classes)
+}
+
+/// Generates Rust struct definitions.
+pub struct RustGenerator {
+    serde: bool,
+}
+
+impl Default for RustGenerator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RustGenerator {
+    /// Creates a generator that emits serde attributes.
+    pub fn new() -> Self {
+        Self { serde: true }
+    }
+
+    /// Sets whether to derive `Serialize`/`Deserialize` and rename fields to
+    /// their .NET member names.
+    ///
+    /// Without serde, the .NET member name is kept in each field's doc comment.
+    pub fn with_serde(mut self, serde: bool) -> Self {
+        self.serde = serde;
+        self
+    }
+
+    /// Generates the structs as Rust source.
+    pub fn generate(&self, classes: &[ClassDef]) -> String {
+        let structs = type_names(classes);
+        let mut out = String::from("// Generated by nrbf-parser. Do not edit.\n");
+        for (class, name) in &structs {
+            out.push('\n');
+            self.write_struct(&mut out, class, name, &structs);
+        }
+        out
+    }
+
+    fn write_struct(
+        &self,
+        out: &mut String,
+        class: &ClassDef,
+        name: &str,
+        structs: &[(&ClassDef, String)],
+    ) {
+        let _ = writeln!(out, "/// `{}`", class.name);
+        if let Some(library) = &class.library {
+            let _ = writeln!(out, "///");
+            let _ = writeln!(out, "/// From `{}`.", library);
+        }
+        if let Some(underlying) = enum_underlying_type(class) {
+            let _ = writeln!(out, "///");
+            let _ = writeln!(
+                out,
+                "/// A boxed enum. Variant names are not part of the stream."
+            );
+            if self.serde {
+                out.push_str("#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]\n");
+                out.push_str("#[serde(transparent)]\n");
+            } else {
+                out.push_str("#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]\n");
+            }
+            let with = match self.with(&MemberType::Primitive(underlying)) {
+                Some(with) => format!("#[serde(with = {:?})] ", with),
+                None => String::new(),
+            };
+            let _ = writeln!(
+                out,
+                "pub struct {}({}pub {});",
+                name,
+                with,
+                primitive(underlying)
+            );
+            return;
+        }
+        if self.serde {
+            out.push_str("#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]\n");
+        } else {
+            out.push_str("#[derive(Debug, Clone)]\n");
+        }
+        let _ = writeln!(out, "pub struct {} {{", name);
+
+        let mut used: Vec<String> = Vec::new();
+        for (member, member_type) in &class.members {
+            let mut field = field_name(backing_field_property(member).unwrap_or(member));
+            while used.contains(&field) {
+                field.push('_');
+            }
+            used.push(field.clone());
+            let field_type = self.field_type(member_type, structs);
+            if self.serde {
+                if field.trim_start_matches("r#") != member {
+                    let _ = writeln!(out, "    #[serde(rename = {:?})]", member);
+                }
+                let with = match target(member_type, structs) {
+                    Some((class, _)) if enum_underlying_type(class).is_some() => {
+                        Some("nrbf_parser::plain::boxed_enum")
+                    }
+                    _ => self.with(member_type),
+                };
+                if let Some(with) = with {
+                    let _ = writeln!(out, "    #[serde(with = {:?})]", with);
+                }
+            } else {
+                let _ = writeln!(out, "    /// `{}`", member);
+            }
+            let _ = writeln!(out, "    pub {}: {},", field, field_type);
+        }
+        out.push_str("}\n");
+    }
+
+    /// Returns the serde helper for primitives plain JSON may write as text.
+    fn with(&self, member_type: &MemberType) -> Option<&'static str> {
+        matches!(
+            member_type,
+            MemberType::Primitive(
+                PrimitiveType::Int64
+                    | PrimitiveType::UInt64
+                    | PrimitiveType::TimeSpan
+                    | PrimitiveType::DateTime
+                    | PrimitiveType::Single
+                    | PrimitiveType::Double
+            )
+        )
+        .then_some("nrbf_parser::plain::number")
+    }
+
+    fn field_type(&self, member_type: &MemberType, structs: &[(&ClassDef, String)]) -> String {
+        let untyped = if self.serde {
+            "serde_json::Value"
+        } else {
+            "()"
+        };
+        match member_type {
+            MemberType::Primitive(t) => primitive(*t).to_string(),
+            MemberType::String => "Option<String>".to_string(),
+            MemberType::Object | MemberType::SystemClass(_) => untyped.to_string(),
+            MemberType::Class { .. } => match target(member_type, structs) {
+                Some((class, name)) if enum_underlying_type(class).is_some() => {
+                    format!("Option<{}>", name)
+                }
+                Some((_, name)) if self.serde => {
+                    format!("Option<nrbf_parser::plain::RefOr<Box<{}>>>", name)
+                }
+                Some((_, name)) => format!("Option<Box<{}>>", name),
+                None => untyped.to_string(),
+            },
+            MemberType::ObjectArray => format!("Option<Vec<{}>>", untyped),
+            MemberType::StringArray => "Option<Vec<Option<String>>>".to_string(),
+            MemberType::PrimitiveArray(t) => format!("Option<Vec<{}>>", primitive(*t)),
+        }
+    }
+}
+
+/// Returns the generated struct of a class member's class.
+fn target<'a>(
+    member_type: &MemberType,
+    structs: &'a [(&'a ClassDef, String)],
+) -> Option<&'a (&'a ClassDef, String)> {
+    let MemberType::Class { name, library } = member_type else {
+        return None;
+    };
+    structs
+        .iter()
+        .find(|(c, _)| c.name == *name && c.library.as_deref() == Some(library))
+}
+
+fn primitive(t: PrimitiveType) -> &'static str {
+    match t {
+        PrimitiveType::Boolean => "bool",
+        PrimitiveType::Byte => "u8",
+        PrimitiveType::Char => "char",
+        PrimitiveType::Decimal => "String",
+        PrimitiveType::Double => "f64",
+        PrimitiveType::Int16 => "i16",
+        PrimitiveType::Int32 => "i32",
+        PrimitiveType::Int64 | PrimitiveType::TimeSpan => "i64",
+        PrimitiveType::SByte => "i8",
+        PrimitiveType::Single => "f32",
+        PrimitiveType::DateTime | PrimitiveType::UInt64 => "u64",
+        PrimitiveType::UInt16 => "u16",
+        PrimitiveType::UInt32 => "u32",
+        PrimitiveType::String => "String",
+        PrimitiveType::Null => "()",
+    }
+}
+
+/// Turns a .NET member name into a snake_case field name.
+fn field_name(name: &str) -> String {
+    let mut field = words(name).join("_");
+    if field.is_empty() || field.starts_with(|c: char| c.is_ascii_digit()) {
+        field.insert(0, '_');
+    }
+    match field.as_str() {
+        "self" | "super" | "crate" | "Self" => field.push('_'),
+        _ if KEYWORDS.split_whitespace().any(|keyword| keyword == field) => {
+            field.insert_str(0, "r#")
+        }
+        _ => {}
+    }
+    field
+}
@@ -0,0 +1,244 @@
+// nrbf-parser - A high-performance MS-NRBF binary parser and encoder.
+// Copyright (C) 2026  driedpampas@proton.me
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! TypeScript declarations for the JSON outputs.
+//!
+//! [`RECORD_DECLARATIONS`] describes `Record`, `ObjectValue` and
+//! `PrimitiveValue` as serde writes them. [`generate`] adds an interface per
+//! class for the interleaved JSON of
+//! [`to_interleaved`](crate::interleaved::to_interleaved), keyed by the
+//! .NET member names.
+
+use super::type_names;
+use crate::builder::{ClassDef, MemberType};
+use crate::interleaved::type_tag;
+use crate::records::PrimitiveType;
+use std::fmt::Write;
+
+/// Declarations of the serde JSON shape of records, independent of any
+/// stream.
+pub const RECORD_DECLARATIONS: &str = include_str!("records.d.ts");
+
+/// Declarations of the interleaved helpers, which class names must not
+/// shadow.
+const INTERLEAVED_DECLARATIONS: &str = r#"// Interleaved JSON, e.g. `interleaved::to_interleaved(&records)`.
+
+export interface InterleavedMemberReference {
+  $record: "MemberReference";
+  id_ref: number;
+}
+
+export interface InterleavedObjectNull {
+  $record: "ObjectNull";
+}
+
+/** A run of null array elements. */
+export interface InterleavedObjectNullMultiple {
+  $record: "ObjectNullMultiple" | "ObjectNullMultiple256";
+  null_count: number;
+}
+
+export interface InterleavedString {
+  $record: "BinaryObjectString";
+  object_id: number;
+  value: string;
+}
+
+/**
+ * Any reference-typed slot may instead refer to another object or be null.
+ * In arrays, a slot may also stand for a run of nulls.
+ */
+export type InterleavedSlot<T> =
+  | T
+  | InterleavedMemberReference
+  | InterleavedObjectNull
+  | InterleavedObjectNullMultiple
+  | null;
+
+export interface InterleavedArray<T> {
+  $record: "BinaryArray" | "ArraySingleObject" | "ArraySinglePrimitive" | "ArraySingleString";
+  object_id: number;
+  $values: T[];
+  [field: string]: unknown;
+}
+
+/** A record without a generated interface. */
+export interface InterleavedOther {
+  $record: string;
+  [field: string]: unknown;
+}
+
+/** A value whose type the metadata does not record. */
+export type InterleavedValue =
+  | null
+  | boolean
+  | number
+  | string
+  | TaggedPrimitive
+  | InterleavedRecord;
+"#;
+
+const PRIMITIVE_TYPES: [PrimitiveType; 17] = [
+    PrimitiveType::Boolean,
+    PrimitiveType::Byte,
+    PrimitiveType::Char,
+    PrimitiveType::Decimal,
+    PrimitiveType::Double,
+    PrimitiveType::Int16,
+    PrimitiveType::Int32,
+    PrimitiveType::Int64,
+    PrimitiveType::SByte,
+    PrimitiveType::Single,
+    PrimitiveType::TimeSpan,
+    PrimitiveType::DateTime,
+    PrimitiveType::UInt16,
+    PrimitiveType::UInt32,
+    PrimitiveType::UInt64,
+    PrimitiveType::Null,
+    PrimitiveType::String,
+];
+
+/// Generates a declaration file for the record JSON and the interleaved
+/// JSON of `classes`.
+pub fn generate(classes: &[ClassDef]) -> String {
+    let mut names = type_names(classes);
+    let reserved = declared_names();
+    for (_, name) in &mut names {
+        while reserved.contains(&name.as_str()) {
+            name.push('_');
+        }
+    }
+
+    let mut out = String::from("// Generated by nrbf-parser. Do not edit.\n\n");
+    out.push_str(RECORD_DECLARATIONS);
+    out.push('\n');
+    out.push_str(INTERLEAVED_DECLARATIONS);
+
+    out.push_str("\n/** A primitive with its type, e.g. `{ $int64: \"42\" }`. */\n");
+    out.push_str("export type TaggedPrimitive =\n");
+    for (i, t) in PRIMITIVE_TYPES.iter().enumerate() {
+        let payload = match t {
+            PrimitiveType::Boolean => "boolean",
+            PrimitiveType::Null => "null",
+            _ => "string",
+        };
+        let end = if i + 1 == PRIMITIVE_TYPES.len() {
+            ";"
+        } else {
+            ""
+        };
+        let _ = writeln!(out, "  | {{ {}: {} }}{}", type_tag(*t), payload, end);
+    }
+
+    out.push_str("\nexport type InterleavedRecord =\n");
+    for (_, name) in &names {
+        let _ = writeln!(out, "  | {}", name);
+    }
+    out.push_str("  | InterleavedOther;\n");
+    out.push_str("\nexport type Interleaved = InterleavedRecord[];\n");
+
+    for (class, name) in &names {
+        out.push('\n');
+        write_interface(&mut out, class, name, &names);
+    }
+    out
+}
+
+fn write_interface(out: &mut String, class: &ClassDef, name: &str, names: &[(&ClassDef, String)]) {
+    let _ = writeln!(out, "/** `{}` */", class.name);
+    let _ = writeln!(out, "export interface {} {{", name);
+    let _ = writeln!(out, "  $type: {};", string_literal(&class.name));
+    out.push_str("  $id: number;\n");
+    out.push_str(
+        "  $record: \"ClassWithMembersAndTypes\" | \"ClassWithMembers\" | \"ClassWithId\";\n",
+    );
+    out.push_str("  $metadata_id?: number;\n");
+    out.push_str("  $member_type_info?: MemberTypeInfo;\n");
+    out.push_str("  library_id?: number;\n");
+    for (member, member_type) in &class.members {
+        let _ = writeln!(
+            out,
+            "  {}: {};",
+            string_literal(member),
+            slot_type(member_type, names)
+        );
+    }
+    out.push_str("}\n");
+}
+
+fn slot_type(member_type: &MemberType, names: &[(&ClassDef, String)]) -> String {
+    match member_type {
+        MemberType::Primitive(t) => primitive(*t),
+        MemberType::String => "InterleavedSlot<InterleavedString>".to_string(),
+        MemberType::Object | MemberType::SystemClass(_) => "InterleavedValue".to_string(),
+        MemberType::Class { name, library } => {
+            let target = names
+                .iter()
+                .find(|(c, _)| c.name == *name && c.library.as_deref() == Some(library));
+            match target {
+                Some((_, name)) => format!("InterleavedSlot<{}>", name),
+                None => "InterleavedSlot<InterleavedOther>".to_string(),
+            }
+        }
+        MemberType::ObjectArray => {
+            "InterleavedSlot<InterleavedArray<InterleavedValue>>".to_string()
+        }
+        MemberType::StringArray => {
+            "InterleavedSlot<InterleavedArray<InterleavedSlot<InterleavedString>>>".to_string()
+        }
+        MemberType::PrimitiveArray(t) => {
+            format!("InterleavedSlot<InterleavedArray<{}>>", primitive(*t))
+        }
+    }
+}
+
+/// The interleaved form of a primitive whose type the metadata records.
+fn primitive(t: PrimitiveType) -> String {
+    let tagged = || format!("{{ {}: string }}", type_tag(t));
+    match t {
+        PrimitiveType::Boolean => "boolean".to_string(),
+        PrimitiveType::Char | PrimitiveType::Decimal | PrimitiveType::String => {
+            "string".to_string()
+        }
+        PrimitiveType::Null => "null".to_string(),
+        PrimitiveType::Int64
+        | PrimitiveType::UInt64
+        | PrimitiveType::TimeSpan
+        | PrimitiveType::DateTime => format!("number | {}", tagged()),
+        PrimitiveType::Single | PrimitiveType::Double => format!("number | null | {}", tagged()),
+        _ => "number".to_string(),
+    }
+}
+
+/// Returns the names exported by the fixed declarations.
+fn declared_names() -> Vec<&'static str> {
+    RECORD_DECLARATIONS
+        .lines()
+        .chain(INTERLEAVED_DECLARATIONS.lines())
+        .filter_map(|line| {
+            let rest = line
+                .strip_prefix("export type ")
+                .or_else(|| line.strip_prefix("export interface "))?;
+            rest.split(|c: char| !c.is_alphanumeric() && c != '_')
+                .next()
+        })
+        .chain(["TaggedPrimitive", "InterleavedRecord", "Interleaved"])
+        .collect()
+}
+
+fn string_literal(value: &str) -> String {
+    serde_json::to_string(value).unwrap_or_default()
+}
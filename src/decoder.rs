@@ -30,13 +30,35 @@ pub struct Decoder<R: Read> {
 }
 
 /// Metadata for a class including its types if available.
-#[derive(Clone)]
+#[derive(Clone, PartialEq, Eq, Hash)]
 pub struct ClassInfoWithTypes {
     pub class_info: ClassInfo,
     pub member_type_info: Option<MemberTypeInfo>,
     pub library_id: Option<i32>,
 }
 
+impl ClassInfoWithTypes {
+    /// Extracts the metadata defined by a class record.
+    ///
+    /// Returns `None` for records that do not define class metadata.
+    pub fn from_record(record: &Record) -> Option<Self> {
+        let (member_type_info, library_id) = match record {
+            Record::ClassWithMembersAndTypes(c) => {
+                (Some(c.member_type_info.clone()), Some(c.library_id))
+            }
+            Record::SystemClassWithMembersAndTypes(c) => (Some(c.member_type_info.clone()), None),
+            Record::SystemClassWithMembers(_) => (None, None),
+            Record::ClassWithMembers(c) => (None, Some(c.library_id)),
+            _ => return None,
+        };
+        Some(Self {
+            class_info: record.class_info()?.clone(),
+            member_type_info,
+            library_id,
+        })
+    }
+}
+
 impl<R: Read> Decoder<R> {
     /// Creates a new decoder from a reader.
     pub fn new(reader: R) -> Self {
@@ -48,6 +70,21 @@ impl<R: Read> Decoder<R> {
         }
     }
 
+    /// Replaces the underlying reader, keeping the registries and offset.
+    ///
+    /// Returns the decoder over the new reader together with the old one.
+    pub(crate) fn replace_reader<S: Read>(self, reader: S) -> (Decoder<S>, R) {
+        (
+            Decoder {
+                reader,
+                metadata_registry: self.metadata_registry,
+                library_registry: self.library_registry,
+                offset: self.offset,
+            },
+            self.reader,
+        )
+    }
+
     /// Decodes the next record from the stream.
     ///
     /// Returns `Ok(Some(record))` if a record was successfully read,
@@ -59,70 +96,118 @@ impl<R: Read> Decoder<R> {
             return Ok(None);
         }
         self.offset += 1;
+        self.read_record_body(header[0]).map(Some)
+    }
+
+    /// Decodes one complete message, from its `SerializationHeader` through `MessageEnd`.
+    ///
+    /// Object, metadata and library ids are scoped to a single message, so the
+    /// registries are cleared before reading. Returns `Ok(None)` if the stream
+    /// ends before another message starts.
+    pub fn decode_message(&mut self) -> Result<Option<Vec<Record>>> {
+        self.metadata_registry.clear();
+        self.library_registry.clear();
+
+        let mut records = Vec::new();
+        while let Some(record) = self.decode_next()? {
+            if records.is_empty() && !matches!(record, Record::SerializationHeader(_)) {
+                return Err(Error::Custom(format!(
+                    "Message must start with SerializationHeader, found {:?}",
+                    RecordType::from(&record)
+                )));
+            }
+            let is_end = matches!(record, Record::MessageEnd);
+            records.push(record);
+            if is_end {
+                return Ok(Some(records));
+            }
+        }
+
+        if records.is_empty() {
+            Ok(None)
+        } else {
+            Err(Error::Io(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                "stream ended before MessageEnd",
+            )))
+        }
+    }
+
+    /// Returns an iterator over the remaining messages in the stream.
+    pub fn messages(&mut self) -> Messages<'_, R> {
+        Messages { decoder: self }
+    }
 
-        let record_type = RecordType::try_from(header[0])?;
+    /// Reads a record that must be present, such as a member or array element.
+    ///
+    /// Unlike `decode_next`, running out of input here is an error.
+    fn read_record(&mut self) -> Result<Record> {
+        let header = self.read_u8()?;
+        self.read_record_body(header)
+    }
+
+    fn read_record_body(&mut self, header: u8) -> Result<Record> {
+        let record_type = RecordType::try_from(header)?;
         match record_type {
             RecordType::SerializedStreamHeader => {
                 let rec = self.read_serialization_header()?;
-                Ok(Some(Record::SerializationHeader(rec)))
+                Ok(Record::SerializationHeader(rec))
             }
             RecordType::BinaryLibrary => {
                 let lib = self.read_binary_library()?;
                 self.library_registry
                     .insert(lib.library_id, lib.library_name.clone());
-                Ok(Some(Record::BinaryLibrary(lib)))
+                Ok(Record::BinaryLibrary(lib))
             }
             RecordType::ClassWithMembersAndTypes => {
                 let rec = self.read_class_with_members_and_types()?;
-                Ok(Some(Record::ClassWithMembersAndTypes(rec)))
+                Ok(Record::ClassWithMembersAndTypes(rec))
             }
             RecordType::SystemClassWithMembersAndTypes => {
                 let rec = self.read_system_class_with_members_and_types()?;
-                Ok(Some(Record::SystemClassWithMembersAndTypes(rec)))
+                Ok(Record::SystemClassWithMembersAndTypes(rec))
             }
             RecordType::SystemClassWithMembers => {
                 let rec = self.read_system_class_with_members()?;
-                Ok(Some(Record::SystemClassWithMembers(rec)))
+                Ok(Record::SystemClassWithMembers(rec))
             }
             RecordType::ClassWithMembers => {
                 let rec = self.read_class_with_members()?;
-                Ok(Some(Record::ClassWithMembers(rec)))
+                Ok(Record::ClassWithMembers(rec))
             }
             RecordType::ClassWithId => {
                 let rec = self.read_class_with_id()?;
-                Ok(Some(Record::ClassWithId(rec)))
+                Ok(Record::ClassWithId(rec))
             }
             RecordType::BinaryObjectString => {
                 let object_id = self.read_i32()?;
                 let value = self.read_length_prefixed_string()?;
-                Ok(Some(Record::BinaryObjectString { object_id, value }))
+                Ok(Record::BinaryObjectString { object_id, value })
             }
             RecordType::BinaryArray => {
                 let rec = self.read_binary_array_full()?;
-                Ok(Some(Record::BinaryArray(rec)))
+                Ok(Record::BinaryArray(rec))
             }
             RecordType::MemberPrimitiveTyped => {
                 let pt = PrimitiveType::try_from(self.read_u8()?)?;
                 let value = self.read_primitive_value(pt)?;
-                Ok(Some(Record::MemberPrimitiveTyped {
+                Ok(Record::MemberPrimitiveTyped {
                     primitive_type_enum: pt,
                     value,
-                }))
+                })
             }
-            RecordType::MemberReference => Ok(Some(Record::MemberReference {
+            RecordType::MemberReference => Ok(Record::MemberReference {
                 id_ref: self.read_i32()?,
-            })),
-            RecordType::ObjectNull => Ok(Some(Record::ObjectNull)),
+            }),
+            RecordType::ObjectNull => Ok(Record::ObjectNull),
             RecordType::ObjectNullMultiple256 => {
-                Ok(Some(Record::ObjectNullMultiple256(ObjectNullMultiple256 {
+                Ok(Record::ObjectNullMultiple256(ObjectNullMultiple256 {
                     null_count: self.read_u8()?,
-                })))
-            }
-            RecordType::ObjectNullMultiple => {
-                Ok(Some(Record::ObjectNullMultiple(ObjectNullMultiple {
-                    null_count: self.read_i32()?,
-                })))
+                }))
             }
+            RecordType::ObjectNullMultiple => Ok(Record::ObjectNullMultiple(ObjectNullMultiple {
+                null_count: self.read_i32()?,
+            })),
             RecordType::ArraySinglePrimitive => {
                 let object_id = self.read_i32()?;
                 let length = self.read_i32()?;
@@ -131,39 +216,39 @@ impl<R: Read> Decoder<R> {
                 for _ in 0..length {
                     values.push(self.read_primitive_value(pt)?);
                 }
-                Ok(Some(Record::ArraySinglePrimitive(ArraySinglePrimitive {
+                Ok(Record::ArraySinglePrimitive(ArraySinglePrimitive {
                     object_id,
                     length,
                     primitive_type_enum: pt,
                     element_values: values,
-                })))
+                }))
             }
             RecordType::ArraySingleObject => {
                 let object_id = self.read_i32()?;
                 let length = self.read_i32()?;
                 let values =
                     self.read_all_elements(length, BinaryType::Object, &AdditionalTypeInfo::None)?;
-                Ok(Some(Record::ArraySingleObject(ArraySingleObject {
+                Ok(Record::ArraySingleObject(ArraySingleObject {
                     object_id,
                     length,
                     element_values: values,
-                })))
+                }))
             }
             RecordType::ArraySingleString => {
                 let object_id = self.read_i32()?;
                 let length = self.read_i32()?;
                 let values =
                     self.read_all_elements(length, BinaryType::String, &AdditionalTypeInfo::None)?;
-                Ok(Some(Record::ArraySingleString(ArraySingleString {
+                Ok(Record::ArraySingleString(ArraySingleString {
                     object_id,
                     length,
                     element_values: values,
-                })))
+                }))
             }
-            RecordType::MessageEnd => Ok(Some(Record::MessageEnd)),
+            RecordType::MessageEnd => Ok(Record::MessageEnd),
             _ => Err(Error::Custom(format!(
                 "Unimplemented record type 0x{:02x}",
-                header[0]
+                header
             ))),
         }
     }
@@ -255,7 +340,7 @@ impl<R: Read> Decoder<R> {
         for i in 0..count {
             let bt = binary_type_enums[i as usize];
             let info = match bt {
-                BinaryType::Primitive => {
+                BinaryType::Primitive | BinaryType::PrimitiveArray => {
                     AdditionalTypeInfo::Primitive(PrimitiveType::try_from(self.read_u8()?)?)
                 }
                 BinaryType::SystemClass => {
@@ -405,7 +490,7 @@ impl<R: Read> Decoder<R> {
 
         let type_enum = BinaryType::try_from(self.read_u8()?)?;
         let additional_type_info = match type_enum {
-            BinaryType::Primitive => {
+            BinaryType::Primitive | BinaryType::PrimitiveArray => {
                 AdditionalTypeInfo::Primitive(PrimitiveType::try_from(self.read_u8()?)?)
             }
             BinaryType::SystemClass => {
@@ -525,13 +610,7 @@ impl<R: Read> Decoder<R> {
                     Err(Error::Custom("Expected primitive type info".into()))
                 }
             }
-            _ => {
-                if let Some(record) = self.decode_next()? {
-                    Ok(ObjectValue::Record(Box::new(record)))
-                } else {
-                    Err(Error::Custom("Expected record for object value".into()))
-                }
-            }
+            _ => Ok(ObjectValue::Record(Box::new(self.read_record()?))),
         }
     }
 
@@ -546,10 +625,8 @@ impl<R: Read> Decoder<R> {
                 let bt = mti.binary_type_enums[i as usize];
                 let add_info = &mti.additional_infos[i as usize];
                 values.push(self.read_object_value(bt, add_info)?);
-            } else if let Some(record) = self.decode_next()? {
-                values.push(ObjectValue::Record(Box::new(record)));
             } else {
-                return Err(Error::Custom("Expected record for member value".into()));
+                values.push(ObjectValue::Record(Box::new(self.read_record()?)));
             }
         }
         Ok(values)
@@ -597,3 +674,16 @@ impl<R: Read> Decoder<R> {
         Ok(values)
     }
 }
+
+/// Iterator over the messages of a stream, created by [`Decoder::messages`].
+pub struct Messages<'a, R: Read> {
+    decoder: &'a mut Decoder<R>,
+}
+
+impl<R: Read> Iterator for Messages<'_, R> {
+    type Item = Result<Vec<Record>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.decoder.decode_message().transpose()
+    }
+}
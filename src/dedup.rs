@@ -0,0 +1,246 @@
+// nrbf-parser - A high-performance MS-NRBF binary parser and encoder.
+// Copyright (C) 2026  driedpampas@proton.me
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Sharing repeated values through `MemberReference`, and expanding them back.
+//!
+//! BinaryFormatter writes each distinct string object once and refers back to
+//! it afterwards. [`Encoder::with_dedup`](crate::Encoder::with_dedup) does the
+//! same for records that repeat a value, and [`inline_references`] undoes it.
+
+use crate::encoder::Encoder;
+use crate::records::*;
+use std::collections::{HashMap, HashSet};
+
+/// Which repeated values to share.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DedupOptions {
+    /// Share `BinaryObjectString` records with equal values.
+    pub strings: bool,
+    /// Share identical objects whose members are all primitives, nulls or
+    /// references, and identical `ArraySinglePrimitive` records.
+    ///
+    /// This changes object identity, so only enable it for immutable data.
+    pub objects: bool,
+}
+
+#[derive(PartialEq, Eq, Hash)]
+enum Key {
+    String(String),
+    Object(Vec<u8>),
+}
+
+/// Streaming deduplication state used by the encoder.
+pub(crate) struct Deduplicator {
+    options: DedupOptions,
+    seen: HashMap<Key, i32>,
+    /// Ids of dropped records and the ids now written in their place.
+    aliases: HashMap<i32, i32>,
+    /// Ids already referenced, which must keep their own record.
+    referenced: HashSet<i32>,
+}
+
+impl Deduplicator {
+    pub(crate) fn new(options: DedupOptions) -> Self {
+        Self {
+            options,
+            seen: HashMap::new(),
+            aliases: HashMap::new(),
+            referenced: HashSet::new(),
+        }
+    }
+
+    /// Registers a record about to be written.
+    ///
+    /// If `replaceable` and an equal record was written before, returns the
+    /// id to reference instead of writing `record`.
+    pub(crate) fn check(&mut self, record: &Record, replaceable: bool) -> Option<i32> {
+        let id = record.object_id()?;
+        let key = self.key(record)?;
+        // Class records that define metadata must always be written.
+        let replaceable =
+            replaceable && record.class_info().is_none() && !self.referenced.contains(&id);
+        match self.seen.get(&key) {
+            Some(kept) if replaceable => {
+                self.aliases.insert(id, *kept);
+                Some(*kept)
+            }
+            Some(_) => None,
+            None => {
+                self.seen.insert(key, id);
+                None
+            }
+        }
+    }
+
+    /// Returns the id a reference to `id` should be written with.
+    pub(crate) fn reference(&mut self, id: i32) -> i32 {
+        self.referenced.insert(id);
+        self.aliases.get(&id).copied().unwrap_or(id)
+    }
+
+    fn key(&self, record: &Record) -> Option<Key> {
+        match record {
+            Record::BinaryObjectString { value, .. } if self.options.strings => {
+                Some(Key::String(value.clone()))
+            }
+            _ if self.options.objects => leaf_object_key(record).map(Key::Object),
+            _ => None,
+        }
+    }
+}
+
+/// Encodes a leaf object without its object id, so equal objects share a key.
+fn leaf_object_key(record: &Record) -> Option<Vec<u8>> {
+    let anonymous = match record {
+        Record::ArraySinglePrimitive(a) => Record::ArraySinglePrimitive(ArraySinglePrimitive {
+            object_id: 0,
+            ..a.clone()
+        }),
+        _ => {
+            let values = record.member_values()?;
+            if !values.iter().all(is_leaf_value) {
+                return None;
+            }
+            let metadata_id = match record {
+                Record::ClassWithId(c) => c.metadata_id,
+                _ => record.object_id()?,
+            };
+            Record::ClassWithId(ClassWithId {
+                object_id: 0,
+                metadata_id,
+                member_values: values.to_vec(),
+            })
+        }
+    };
+    let mut bytes = Vec::new();
+    Encoder::new(&mut bytes).encode(&anonymous).ok()?;
+    Some(bytes)
+}
+
+fn is_leaf_value(value: &ObjectValue) -> bool {
+    match value {
+        ObjectValue::Primitive(_) => true,
+        ObjectValue::Record(r) => matches!(
+            r.as_ref(),
+            Record::ObjectNull
+                | Record::ObjectNullMultiple(_)
+                | Record::ObjectNullMultiple256(_)
+                | Record::MemberReference { .. }
+                | Record::MemberPrimitiveTyped { .. }
+        ),
+    }
+}
+
+/// Replaces references to shared values with inline copies.
+///
+/// Each copy gets a fresh object id above the largest id in the stream.
+/// Objects are only copied when their definition precedes the reference,
+/// since a copy of a class refers back to its metadata.
+pub fn inline_references(records: &[Record], options: DedupOptions) -> Vec<Record> {
+    let mut expander = Expander {
+        options,
+        next_id: 1,
+        targets: HashMap::new(),
+        defined: HashSet::new(),
+    };
+    for record in records {
+        expander.collect(record);
+    }
+    records.iter().map(|r| expander.expand(r)).collect()
+}
+
+struct Expander {
+    options: DedupOptions,
+    next_id: i32,
+    /// Copyable records by object id.
+    targets: HashMap<i32, Record>,
+    /// Ids of the records expanded so far, in stream order.
+    defined: HashSet<i32>,
+}
+
+impl Expander {
+    fn collect(&mut self, record: &Record) {
+        if let Some(id) = record.object_id() {
+            self.next_id = self.next_id.max(id.saturating_add(1));
+            let copyable = match record {
+                Record::BinaryObjectString { .. } => self.options.strings,
+                _ => self.options.objects && leaf_object_key(record).is_some(),
+            };
+            if copyable {
+                self.targets.insert(id, record.clone());
+            }
+        }
+        for value in record.child_values() {
+            if let ObjectValue::Record(r) = value {
+                self.collect(r);
+            }
+        }
+    }
+
+    fn expand(&mut self, record: &Record) -> Record {
+        let mut record = record.clone();
+        self.expand_in_place(&mut record);
+        record
+    }
+
+    fn expand_in_place(&mut self, record: &mut Record) {
+        if let Some(id) = record.object_id() {
+            self.defined.insert(id);
+        }
+        for value in record.child_values_mut() {
+            let ObjectValue::Record(r) = value else {
+                continue;
+            };
+            if let Record::MemberReference { id_ref } = r.as_ref()
+                && let Some(copy) = self.copy_of(*id_ref)
+            {
+                **r = copy;
+            } else {
+                self.expand_in_place(r);
+            }
+        }
+    }
+
+    fn copy_of(&mut self, id: i32) -> Option<Record> {
+        let target = self.targets.get(&id)?;
+        let is_string = matches!(target, Record::BinaryObjectString { .. });
+        if !is_string && !self.defined.contains(&id) {
+            return None;
+        }
+        let object_id = self.next_id;
+        let copy = match target {
+            Record::BinaryObjectString { value, .. } => Record::BinaryObjectString {
+                object_id,
+                value: value.clone(),
+            },
+            Record::ArraySinglePrimitive(a) => Record::ArraySinglePrimitive(ArraySinglePrimitive {
+                object_id,
+                ..a.clone()
+            }),
+            Record::ClassWithId(c) => Record::ClassWithId(ClassWithId {
+                object_id,
+                ..c.clone()
+            }),
+            other => Record::ClassWithId(ClassWithId {
+                object_id,
+                metadata_id: id,
+                member_values: other.member_values()?.to_vec(),
+            }),
+        };
+        self.next_id += 1;
+        Some(copy)
+    }
+}
@@ -0,0 +1,255 @@
+// nrbf-parser - A high-performance MS-NRBF binary parser and encoder.
+// Copyright (C) 2026  driedpampas@proton.me
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Semantic differences between two messages.
+//!
+//! Object ids are not stable between two saves of the same data, so objects
+//! are matched by where they are in the graph instead: both messages are
+//! walked from their root object, class members are paired by name and
+//! array elements by index. Every change is reported with the
+//! [query](crate::query) path of the value from the root, e.g.
+//! `$.inventory[0].itemId`, which selects it in both messages.
+//!
+//! An object reached along several paths is compared at the first one.
+
+use crate::query::{Evaluator, Match, Node, Path};
+use crate::records::*;
+use std::collections::HashSet;
+use std::fmt;
+
+/// Compares two messages and returns their differences in graph order.
+pub fn diff<'a>(old: &'a [Record], new: &'a [Record]) -> Vec<Change<'a>> {
+    let mut differ = Differ {
+        old: Evaluator::new(old),
+        new: Evaluator::new(new),
+        compared: HashSet::new(),
+        steps: Vec::new(),
+        changes: Vec::new(),
+    };
+    let root = |evaluator: &Evaluator<'a>| {
+        evaluator.root().map(|root| Match {
+            path: Path::root(),
+            ..root
+        })
+    };
+    match (root(&differ.old), root(&differ.new)) {
+        (Some(a), Some(b)) => {
+            differ.steps.push(Step::Compare(a, b));
+            differ.run();
+        }
+        (Some(a), None) => differ.push(&a.path(), ChangeKind::Removed(a.node)),
+        (None, Some(b)) => differ.push(&b.path(), ChangeKind::Added(b.node)),
+        (None, None) => {}
+    }
+    differ.changes
+}
+
+/// A difference between two messages.
+#[derive(Debug, Clone)]
+pub struct Change<'a> {
+    /// The path of the value from the root, e.g. `$.inventory[0].itemId`.
+    pub path: String,
+    pub kind: ChangeKind<'a>,
+}
+
+/// What changed at a path.
+#[derive(Debug, Clone, Copy)]
+pub enum ChangeKind<'a> {
+    /// A member or element that only the new message has.
+    Added(Node<'a>),
+    /// A member or element that only the old message has.
+    Removed(Node<'a>),
+    /// A value that changed, or became or stopped being null.
+    Changed(Node<'a>, Node<'a>),
+    /// A value of another primitive type, class or array type.
+    TypeChanged(Node<'a>, Node<'a>),
+}
+
+impl fmt::Display for Change<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            ChangeKind::Added(node) => write!(f, "+ {} = {}", self.path, node),
+            ChangeKind::Removed(node) => write!(f, "- {} = {}", self.path, node),
+            ChangeKind::Changed(old, new) => write!(f, "~ {}: {} -> {}", self.path, old, new),
+            ChangeKind::TypeChanged(old, new) => {
+                write!(
+                    f,
+                    "! {}: {} -> {}",
+                    self.path,
+                    type_name(old),
+                    type_name(new)
+                )
+            }
+        }
+    }
+}
+
+struct Differ<'a> {
+    old: Evaluator<'a>,
+    new: Evaluator<'a>,
+    /// Pairs of old and new object ids already compared.
+    compared: HashSet<(i32, i32)>,
+    /// Work still to do, the next step last, so long reference chains do
+    /// not recurse.
+    steps: Vec<Step<'a>>,
+    changes: Vec<Change<'a>>,
+}
+
+enum Step<'a> {
+    Compare(Match<'a>, Match<'a>),
+    Change(String, ChangeKind<'a>),
+}
+
+impl<'a> Differ<'a> {
+    fn run(&mut self) {
+        while let Some(step) = self.steps.pop() {
+            match step {
+                Step::Compare(a, b) => self.compare(&a, &b),
+                Step::Change(path, kind) => self.push(&path, kind),
+            }
+        }
+    }
+
+    /// Schedules `steps` to run next, in order.
+    fn schedule(&mut self, steps: Vec<Step<'a>>) {
+        self.steps.extend(steps.into_iter().rev());
+    }
+
+    fn compare(&mut self, a: &Match<'a>, b: &Match<'a>) {
+        match (a.node, b.node) {
+            (Node::Null | Node::Primitive(PrimitiveValue::Null), new) => {
+                if !is_null(new) {
+                    self.push(&a.path(), ChangeKind::Changed(a.node, new));
+                }
+            }
+            (old, new) if is_null(new) => self.push(&a.path(), ChangeKind::Changed(old, new)),
+            (Node::Primitive(x), Node::Primitive(y)) => {
+                if x.primitive_type() != y.primitive_type() {
+                    self.push(&a.path(), ChangeKind::TypeChanged(a.node, b.node));
+                } else if x.to_text() != y.to_text() {
+                    self.push(&a.path(), ChangeKind::Changed(a.node, b.node));
+                }
+            }
+            (Node::Class(x, old_class), Node::Class(y, new_class)) => {
+                if old_class.name != new_class.name {
+                    self.push(&a.path(), ChangeKind::TypeChanged(a.node, b.node));
+                } else if self.first_visit(x, y) {
+                    self.members(a, b);
+                }
+            }
+            (Node::Object(x), Node::Object(y)) => match (x, y) {
+                (
+                    Record::BinaryObjectString { value: old, .. },
+                    Record::BinaryObjectString { value: new, .. },
+                ) => {
+                    if old != new {
+                        self.push(&a.path(), ChangeKind::Changed(a.node, b.node));
+                    }
+                }
+                _ if type_name(&a.node) != type_name(&b.node) => {
+                    self.push(&a.path(), ChangeKind::TypeChanged(a.node, b.node));
+                }
+                _ => {
+                    if self.first_visit(x, y) {
+                        self.elements(a, b);
+                    }
+                }
+            },
+            _ => self.push(&a.path(), ChangeKind::TypeChanged(a.node, b.node)),
+        }
+    }
+
+    /// Pairs members by name.
+    fn members(&mut self, a: &Match<'a>, b: &Match<'a>) {
+        let old = self.old.children(a);
+        let new = self.new.children(b);
+        let old_names = self.old.member_names(a);
+        let new_names = self.new.member_names(b);
+        let mut steps = Vec::new();
+        for (name, old) in old_names.iter().zip(&old) {
+            steps.push(match new_names.iter().position(|n| n == name) {
+                Some(i) => Step::Compare(old.clone(), new[i].clone()),
+                None => Step::Change(old.path(), ChangeKind::Removed(old.node)),
+            });
+        }
+        for (name, new) in new_names.iter().zip(&new) {
+            if !old_names.contains(name) {
+                steps.push(Step::Change(new.path(), ChangeKind::Added(new.node)));
+            }
+        }
+        self.schedule(steps);
+    }
+
+    /// Pairs elements by index.
+    fn elements(&mut self, a: &Match<'a>, b: &Match<'a>) {
+        let old = self.old.elements(a).unwrap_or_default();
+        let new = self.new.elements(b).unwrap_or_default();
+        let mut steps = Vec::new();
+        for old in old.iter().skip(new.len()) {
+            steps.push(Step::Change(old.path(), ChangeKind::Removed(old.node)));
+        }
+        for new in new.iter().skip(old.len()) {
+            steps.push(Step::Change(new.path(), ChangeKind::Added(new.node)));
+        }
+        let pairs = old.into_iter().zip(new).map(|(a, b)| Step::Compare(a, b));
+        self.schedule(pairs.chain(steps).collect());
+    }
+
+    /// Returns true the first time two objects are compared, so shared
+    /// objects and cycles are walked once.
+    fn first_visit(&mut self, old: &Record, new: &Record) -> bool {
+        let old = old.object_id().unwrap_or_default();
+        let new = new.object_id().unwrap_or_default();
+        self.compared.insert((old, new))
+    }
+
+    fn push(&mut self, path: &str, kind: ChangeKind<'a>) {
+        self.changes.push(Change {
+            path: path.to_string(),
+            kind,
+        });
+    }
+}
+
+fn is_null(node: Node) -> bool {
+    matches!(node, Node::Null | Node::Primitive(PrimitiveValue::Null))
+}
+
+/// Names the type of a value, e.g. `Int64`, `Game.Item` or `Int32[]`.
+pub(crate) fn type_name(node: &Node) -> String {
+    match node {
+        Node::Null => "null".to_string(),
+        Node::Primitive(p) => format!("{:?}", p.primitive_type()),
+        Node::Class(_, class) => class.name.clone(),
+        Node::Object(record) => match record {
+            Record::BinaryObjectString { .. } => "String".to_string(),
+            Record::ArraySinglePrimitive(a) => format!("{:?}[]", a.primitive_type_enum),
+            Record::ArraySingleObject(_) => "Object[]".to_string(),
+            Record::ArraySingleString(_) => "String[]".to_string(),
+            Record::BinaryArray(a) => {
+                let element = match &a.additional_type_info {
+                    AdditionalTypeInfo::Primitive(t) => format!("{:?}", t),
+                    AdditionalTypeInfo::SystemClass(name) => name.clone(),
+                    AdditionalTypeInfo::Class(c) => c.type_name.clone(),
+                    AdditionalTypeInfo::None => format!("{:?}", a.type_enum),
+                };
+                let rank = ",".repeat(usize::try_from(a.rank).unwrap_or(1).saturating_sub(1));
+                format!("{}[{}]", element, rank)
+            }
+            record => format!("{:?}", RecordType::from(*record)),
+        },
+    }
+}
@@ -14,23 +14,71 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-use crate::error::Result;
+use crate::dedup::{DedupOptions, Deduplicator};
+use crate::error::{Error, Result};
 use crate::records::*;
+use crate::validate::Validator;
 use std::io::Write;
 
 /// An encoder for MS-NRBF binary streams.
 pub struct Encoder<W: Write> {
     writer: W,
+    validator: Option<Validator>,
+    dedup: Option<Deduplicator>,
 }
 
 impl<W: Write> Encoder<W> {
     /// Creates a new encoder from a writer.
     pub fn new(writer: W) -> Self {
-        Self { writer }
+        Self {
+            writer,
+            validator: None,
+            dedup: None,
+        }
+    }
+
+    /// Enables or disables strict mode.
+    ///
+    /// In strict mode each record is checked against its own metadata, and
+    /// `ClassWithId` records against previously written metadata, before any
+    /// of its bytes are written. Inconsistent records are rejected with
+    /// [`Error::Validation`].
+    pub fn with_strict(mut self, strict: bool) -> Self {
+        self.validator = strict.then(Validator::default);
+        self
+    }
+
+    /// Shares repeated values by writing `MemberReference` records in their place.
+    ///
+    /// Only records nested in a member or element slot are replaced, and only
+    /// if their own id has not been referenced yet. Later references to a
+    /// dropped id are redirected to the record that was kept. See
+    /// [`inline_references`](crate::dedup::inline_references) for the reverse.
+    pub fn with_dedup(mut self, options: DedupOptions) -> Self {
+        self.dedup = (options.strings || options.objects).then(|| Deduplicator::new(options));
+        self
+    }
+
+    /// Returns a mutable reference to the underlying writer.
+    #[cfg(feature = "tokio")]
+    pub(crate) fn writer_mut(&mut self) -> &mut W {
+        &mut self.writer
     }
 
     /// Encodes a record and writes it to the stream.
     pub fn encode(&mut self, record: &Record) -> Result<()> {
+        if let Some(validator) = &mut self.validator
+            && let Some(diagnostic) = validator.check_next(record)
+        {
+            return Err(Error::Validation(diagnostic));
+        }
+        if let Some(dedup) = &mut self.dedup {
+            dedup.check(record, false);
+        }
+        self.write_record(record)
+    }
+
+    fn write_record(&mut self, record: &Record) -> Result<()> {
         match record {
             Record::SerializationHeader(rec) => {
                 self.write_u8(RecordType::SerializedStreamHeader as u8)?;
@@ -103,8 +151,12 @@ impl<W: Write> Encoder<W> {
                 self.write_primitive_value(value)?;
             }
             Record::MemberReference { id_ref } => {
+                let id_ref = match &mut self.dedup {
+                    Some(dedup) => dedup.reference(*id_ref),
+                    None => *id_ref,
+                };
                 self.write_u8(RecordType::MemberReference as u8)?;
-                self.write_i32(*id_ref)?;
+                self.write_i32(id_ref)?;
             }
             Record::ObjectNull => {
                 self.write_u8(RecordType::ObjectNull as u8)?;
@@ -292,11 +344,10 @@ impl<W: Write> Encoder<W> {
             PrimitiveValue::UInt64(v) => self.writer.write_all(&v.to_le_bytes())?,
             PrimitiveValue::String(s) => self.write_length_prefixed_string(s)?,
             PrimitiveValue::Decimal(s) => {
-                let bytes = hex::decode(s).map_err(|e| {
-                    crate::error::Error::Custom(format!("Invalid hex for Decimal: {}", e))
-                })?;
+                let bytes = hex::decode(s)
+                    .map_err(|e| Error::Custom(format!("Invalid hex for Decimal: {}", e)))?;
                 if bytes.len() != 16 {
-                    return Err(crate::error::Error::Custom(format!(
+                    return Err(Error::Custom(format!(
                         "Decimal must be 16 bytes, got {}",
                         bytes.len()
                     )));
@@ -325,7 +376,13 @@ impl<W: Write> Encoder<W> {
                 }
             }
             ObjectValue::Record(r) => {
-                self.encode(r)?;
+                match self.dedup.as_mut().and_then(|dedup| dedup.check(r, true)) {
+                    Some(id_ref) => {
+                        self.write_u8(RecordType::MemberReference as u8)?;
+                        self.write_i32(id_ref)?;
+                    }
+                    None => self.write_record(r)?,
+                }
             }
         }
         Ok(())
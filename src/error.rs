@@ -41,6 +41,21 @@ pub enum Error {
     #[error("Invalid length-prefixed string: {0}")]
     InvalidStringLength(i32),
 
+    #[error("Validation failed: {0}")]
+    Validation(crate::validate::Diagnostic),
+
+    #[error("Invalid interleaved JSON at '{pointer}': {message}")]
+    Interleaved { pointer: String, message: String },
+
+    #[error("Invalid plain JSON at '{pointer}': {message}")]
+    Plain { pointer: String, message: String },
+
+    #[error("Invalid query at offset {offset}: {message}")]
+    Query { offset: usize, message: String },
+
+    #[error("Cannot patch '{path}': {message}")]
+    Patch { path: String, message: String },
+
     #[error("Custom error: {0}")]
     Custom(String),
 }
@@ -0,0 +1,121 @@
+// nrbf-parser - A high-performance MS-NRBF binary parser and encoder.
+// Copyright (C) 2026  driedpampas@proton.me
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Extracting one object and everything it refers to as a message of its own.
+//!
+//! The records are copied as they are, keeping their ids and stream order,
+//! so a broken object can be shared without the rest of the message and
+//! still shows the same problem. Only what the extracted stream needs to
+//! stand alone changes:
+//!
+//! - Reachable objects written inline in records that are left out move to
+//!   the top level.
+//! - A `ClassWithId` whose class is defined by a record that is left out
+//!   becomes a full class record, and later objects of the class refer to it.
+//! - The libraries the records use are written after a fresh header whose
+//!   root is the extracted object, and the message ends with `MessageEnd`.
+//!
+//! References to objects the original message does not have are kept.
+
+use crate::error::{Error, Result};
+use crate::patch::{Patch, library_uses};
+use crate::plain::Graph;
+use crate::records::*;
+use std::collections::HashSet;
+
+/// Returns a message rooted at object `object_id` with the objects it can
+/// reach.
+pub fn extract(records: &[Record], object_id: i32) -> Result<Vec<Record>> {
+    let graph = Graph::new(records);
+    let root = graph
+        .objects
+        .get(&object_id)
+        .ok_or_else(|| Error::Custom(format!("No object has id {}", object_id)))?;
+    let reachable = reachable(&graph, root);
+
+    let mut extracted = vec![Record::SerializationHeader(SerializationHeader {
+        root_id: object_id,
+        header_id: -1,
+        major_version: 1,
+        minor_version: 0,
+    })];
+    for record in records {
+        collect(record, &reachable, &mut extracted);
+    }
+    extracted.push(Record::MessageEnd);
+
+    let mut patch = Patch::new(extracted);
+    for (id, metadata) in &graph.metadata {
+        patch
+            .metadata
+            .entry(*id)
+            .or_insert_with(|| metadata.clone());
+    }
+    patch.fix_up();
+    let mut extracted = patch.into_records();
+
+    let mut used = Vec::new();
+    for record in &extracted {
+        library_uses(record, &mut used);
+    }
+    let used: HashSet<i32> = used.into_iter().collect();
+    let libraries = records
+        .iter()
+        .filter(|record| matches!(record, Record::BinaryLibrary(l) if used.contains(&l.library_id)))
+        .cloned();
+    extracted.splice(1..1, libraries);
+    Ok(extracted)
+}
+
+/// Returns the ids of the objects `root` refers to, directly or not, and
+/// of the objects written inside them.
+fn reachable(graph: &Graph, root: &Record) -> HashSet<i32> {
+    let mut reachable = HashSet::new();
+    let mut stack = vec![root];
+    while let Some(record) = stack.pop() {
+        if let Some(id) = record.object_id()
+            && !reachable.insert(id)
+        {
+            continue;
+        }
+        for value in record.child_values() {
+            match value {
+                ObjectValue::Record(r) => match r.as_ref() {
+                    Record::MemberReference { id_ref } => {
+                        stack.extend(graph.objects.get(id_ref).copied());
+                    }
+                    r => stack.push(r),
+                },
+                ObjectValue::Primitive(_) => {}
+            }
+        }
+    }
+    reachable
+}
+
+/// Copies the reachable objects in `record`, moving those written inside
+/// records that are left out to the top level.
+fn collect(record: &Record, reachable: &HashSet<i32>, out: &mut Vec<Record>) {
+    if record.object_id().is_some_and(|id| reachable.contains(&id)) {
+        out.push(record.clone());
+        return;
+    }
+    for value in record.child_values() {
+        if let ObjectValue::Record(r) = value {
+            collect(r, reachable, out);
+        }
+    }
+}
@@ -1,183 +1,343 @@
+use crate::decoder::ClassInfoWithTypes;
+use crate::error::{Error, Result};
 use crate::records::{
-    AdditionalTypeInfo, BinaryArray, BinaryType, ClassInfo, ClassWithId, ClassWithMembers,
-    ClassWithMembersAndTypes, MemberTypeInfo, ObjectValue, PrimitiveType, PrimitiveValue, Record,
-    SystemClassWithMembers, SystemClassWithMembersAndTypes,
+    AdditionalTypeInfo, ArraySingleObject, ArraySinglePrimitive, ArraySingleString, BinaryArray,
+    BinaryLibrary, BinaryType, ClassInfo, ClassWithId, ClassWithMembers, ClassWithMembersAndTypes,
+    MemberTypeInfo, ObjectNullMultiple, ObjectNullMultiple256, ObjectValue, PrimitiveType,
+    PrimitiveValue, Record, SerializationHeader, SystemClassWithMembers,
+    SystemClassWithMembersAndTypes,
 };
+use serde::de::DeserializeOwned;
 use serde_json::{Map, Value, json};
+use std::collections::HashMap;
 
+/// Converts records into interleaved JSON, with member names as keys.
 pub fn to_interleaved(records: &[Record]) -> Value {
-    let mut result = Vec::new();
-    for record in records {
-        if let Some(val) = record_to_value(record) {
-            result.push(val);
-        }
+    InterleavedSerializer::new().serialize(records)
+}
+
+/// A writer for interleaved JSON.
+pub struct InterleavedSerializer {
+    lossless: bool,
+    metadata_registry: HashMap<i32, ClassInfoWithTypes>,
+}
+
+impl Default for InterleavedSerializer {
+    fn default() -> Self {
+        Self::new()
     }
-    Value::Array(result)
 }
 
-fn record_to_value(record: &Record) -> Option<Value> {
-    match record {
-        Record::SerializationHeader(h) => Some(json!({
-            "$record": "SerializationHeader",
-            "root_id": h.root_id,
-            "header_id": h.header_id,
-            "major_version": h.major_version,
-            "minor_version": h.minor_version,
-        })),
-        Record::BinaryLibrary(l) => Some(json!({
-            "$record": "BinaryLibrary",
-            "library_id": l.library_id,
-            "library_name": l.library_name,
-        })),
-        Record::ClassWithMembersAndTypes(c) => {
-            let mut val = class_to_value(
-                &c.class_info.name,
-                c.class_info.object_id,
-                &c.class_info.member_names,
-                &c.member_values,
-                Some(c.library_id),
-            );
-            if let Value::Object(ref mut map) = val {
-                map.insert("$record".to_string(), json!("ClassWithMembersAndTypes"));
-                map.insert("$member_type_info".to_string(), json!(c.member_type_info));
-            }
-            Some(val)
+impl InterleavedSerializer {
+    /// Creates a new serializer.
+    pub fn new() -> Self {
+        Self {
+            lossless: false,
+            metadata_registry: HashMap::new(),
+        }
+    }
+
+    /// Enables or disables lossless numbers.
+    ///
+    /// When enabled, non-finite floats and integers beyond 2^53 - 1 are
+    /// written with a type tag and a string payload, e.g. `{"$double": "inf"}`,
+    /// even where the metadata records their type. Otherwise they are plain
+    /// JSON numbers, and NaNs and infinities become `null`.
+    pub fn with_lossless(mut self, lossless: bool) -> Self {
+        self.lossless = lossless;
+        self
+    }
+
+    /// Converts records into an array of interleaved records.
+    pub fn serialize(&mut self, records: &[Record]) -> Value {
+        let mut result = Vec::new();
+        for record in records {
+            if let Some(val) = self.record_to_value(record) {
+                result.push(val);
+            }
+        }
+        Value::Array(result)
+    }
+
+    fn record_to_value(&mut self, record: &Record) -> Option<Value> {
+        // Registered before the members, which may reuse the metadata.
+        if let Some(metadata) = ClassInfoWithTypes::from_record(record) {
+            self.metadata_registry
+                .insert(metadata.class_info.object_id, metadata);
         }
-        Record::SystemClassWithMembersAndTypes(c) => {
-            let mut val = class_to_value(
-                &c.class_info.name,
-                c.class_info.object_id,
-                &c.class_info.member_names,
-                &c.member_values,
-                None,
-            );
-            if let Value::Object(ref mut map) = val {
-                map.insert(
-                    "$record".to_string(),
-                    json!("SystemClassWithMembersAndTypes"),
+        match record {
+            Record::SerializationHeader(h) => Some(json!({
+                "$record": "SerializationHeader",
+                "root_id": h.root_id,
+                "header_id": h.header_id,
+                "major_version": h.major_version,
+                "minor_version": h.minor_version,
+            })),
+            Record::BinaryLibrary(l) => Some(json!({
+                "$record": "BinaryLibrary",
+                "library_id": l.library_id,
+                "library_name": l.library_name,
+            })),
+            Record::ClassWithMembersAndTypes(c) => {
+                let mut val = self.class_to_value(
+                    &c.class_info.name,
+                    c.class_info.object_id,
+                    &c.class_info.member_names,
+                    &c.member_values,
+                    Some(c.library_id),
+                    Some(&c.member_type_info),
+                );
+                if let Value::Object(ref mut map) = val {
+                    map.insert("$record".to_string(), json!("ClassWithMembersAndTypes"));
+                    map.insert("$member_type_info".to_string(), json!(c.member_type_info));
+                }
+                Some(val)
+            }
+            Record::SystemClassWithMembersAndTypes(c) => {
+                let mut val = self.class_to_value(
+                    &c.class_info.name,
+                    c.class_info.object_id,
+                    &c.class_info.member_names,
+                    &c.member_values,
+                    None,
+                    Some(&c.member_type_info),
+                );
+                if let Value::Object(ref mut map) = val {
+                    map.insert(
+                        "$record".to_string(),
+                        json!("SystemClassWithMembersAndTypes"),
+                    );
+                    map.insert("$member_type_info".to_string(), json!(c.member_type_info));
+                }
+                Some(val)
+            }
+            Record::SystemClassWithMembers(c) => {
+                let mut val = self.class_to_value(
+                    &c.class_info.name,
+                    c.class_info.object_id,
+                    &c.class_info.member_names,
+                    &c.member_values,
+                    None,
+                    None,
+                );
+                if let Value::Object(ref mut map) = val {
+                    map.insert("$record".to_string(), json!("SystemClassWithMembers"));
+                }
+                Some(val)
+            }
+            Record::ClassWithMembers(c) => {
+                let mut val = self.class_to_value(
+                    &c.class_info.name,
+                    c.class_info.object_id,
+                    &c.class_info.member_names,
+                    &c.member_values,
+                    Some(c.library_id),
+                    None,
                 );
-                map.insert("$member_type_info".to_string(), json!(c.member_type_info));
+                if let Value::Object(ref mut map) = val {
+                    map.insert("$record".to_string(), json!("ClassWithMembers"));
+                }
+                Some(val)
             }
-            Some(val)
+            Record::ClassWithId(c) => {
+                let metadata = self
+                    .metadata_registry
+                    .get(&c.metadata_id)
+                    .filter(|m| m.class_info.member_names.len() == c.member_values.len())
+                    .cloned();
+                // Without usable metadata the values can only be listed in order,
+                // with the keys of a raw record like the arrays below.
+                let Some(metadata) = metadata else {
+                    return Some(json!({
+                        "$record": "ClassWithId",
+                        "object_id": c.object_id,
+                        "metadata_id": c.metadata_id,
+                        "$values": c.member_values.iter().map(|v| self.object_value_to_json(v)).collect::<Vec<_>>(),
+                    }));
+                };
+                let mut val = self.class_to_value(
+                    &metadata.class_info.name,
+                    c.object_id,
+                    &metadata.class_info.member_names,
+                    &c.member_values,
+                    None,
+                    metadata.member_type_info.as_ref(),
+                );
+                if let Value::Object(ref mut map) = val {
+                    map.insert("$metadata_id".to_string(), json!(c.metadata_id));
+                    map.insert("$record".to_string(), json!("ClassWithId"));
+                }
+                Some(val)
+            }
+            Record::BinaryObjectString { object_id, value } => Some(json!({
+                "$record": "BinaryObjectString",
+                "object_id": *object_id,
+                "value": value,
+            })),
+            Record::BinaryArray(a) => Some(json!({
+                "$record": "BinaryArray",
+                "object_id": a.object_id,
+                "binary_array_type_enum": a.binary_array_type_enum,
+                "rank": a.rank,
+                "lengths": a.lengths,
+                "lower_bounds": a.lower_bounds,
+                "type_enum": a.type_enum,
+                "additional_type_info": a.additional_type_info,
+                "$values": a.element_values.iter().map(|v| {
+                    let slot = match (&a.type_enum, &a.additional_type_info) {
+                        (BinaryType::Primitive, AdditionalTypeInfo::Primitive(p)) => Some(*p),
+                        _ => None,
+                    };
+                    self.slot_value_to_json(v, slot)
+                }).collect::<Vec<_>>(),
+            })),
+            Record::ArraySingleObject(a) => Some(json!({
+                "$record": "ArraySingleObject",
+                "object_id": a.object_id,
+                "length": a.length,
+                "$values": a.element_values.iter().map(|v| self.object_value_to_json(v)).collect::<Vec<_>>(),
+            })),
+            Record::ArraySinglePrimitive(a) => Some(json!({
+                "$record": "ArraySinglePrimitive",
+                "object_id": a.object_id,
+                "length": a.length,
+                "primitive_type_enum": a.primitive_type_enum,
+                "$values": a.element_values.iter().map(|p| self.typed_primitive_to_json(p)).collect::<Vec<_>>(),
+            })),
+            Record::ArraySingleString(a) => Some(json!({
+                "$record": "ArraySingleString",
+                "object_id": a.object_id,
+                "length": a.length,
+                "$values": a.element_values.iter().map(|v| self.object_value_to_json(v)).collect::<Vec<_>>(),
+            })),
+            Record::MemberPrimitiveTyped {
+                primitive_type_enum,
+                value,
+            } => Some(json!({
+                "$record": "MemberPrimitiveTyped",
+                "primitive_type_enum": primitive_type_enum,
+                "value": self.typed_primitive_to_json(value),
+            })),
+            Record::MemberReference { id_ref } => Some(json!({
+                "$record": "MemberReference",
+                "id_ref": *id_ref,
+            })),
+            Record::ObjectNull => Some(json!({ "$record": "ObjectNull" })),
+            Record::ObjectNullMultiple(n) => Some(json!({
+                "$record": "ObjectNullMultiple",
+                "null_count": n.null_count,
+            })),
+            Record::ObjectNullMultiple256(n) => Some(json!({
+                "$record": "ObjectNullMultiple256",
+                "null_count": n.null_count,
+            })),
+            Record::MessageEnd => Some(json!({ "$record": "MessageEnd" })),
+        }
+    }
+
+    fn class_to_value(
+        &mut self,
+        name: &str,
+        object_id: i32,
+        member_names: &[String],
+        member_values: &[ObjectValue],
+        library_id: Option<i32>,
+        member_type_info: Option<&MemberTypeInfo>,
+    ) -> Value {
+        let mut map = Map::new();
+        map.insert("$type".to_string(), Value::String(name.to_string()));
+        map.insert("$id".to_string(), json!(object_id));
+        if let Some(lib_id) = library_id {
+            map.insert("library_id".to_string(), json!(lib_id));
         }
-        Record::SystemClassWithMembers(c) => {
-            let mut val = class_to_value(
-                &c.class_info.name,
-                c.class_info.object_id,
-                &c.class_info.member_names,
-                &c.member_values,
-                None,
-            );
-            if let Value::Object(ref mut map) = val {
-                map.insert("$record".to_string(), json!("SystemClassWithMembers"));
-            }
-            Some(val)
+
+        for (i, (name, val)) in member_names.iter().zip(member_values.iter()).enumerate() {
+            let slot = member_type_info.and_then(|info| info.primitive_slot(i));
+            map.insert(name.clone(), self.slot_value_to_json(val, slot));
         }
-        Record::ClassWithMembers(c) => {
-            let mut val = class_to_value(
-                &c.class_info.name,
-                c.class_info.object_id,
-                &c.class_info.member_names,
-                &c.member_values,
-                Some(c.library_id),
-            );
-            if let Value::Object(ref mut map) = val {
-                map.insert("$record".to_string(), json!("ClassWithMembers"));
-            }
-            Some(val)
+
+        Value::Object(map)
+    }
+
+    /// Converts the value of a member or element, typed by its slot if known.
+    fn slot_value_to_json(&mut self, val: &ObjectValue, slot: Option<PrimitiveType>) -> Value {
+        match (val, slot) {
+            (ObjectValue::Primitive(p), Some(_)) => self.typed_primitive_to_json(p),
+            _ => self.object_value_to_json(val),
         }
-        Record::ClassWithId(c) => Some(json!({
-            "$record": "ClassWithId",
-            "object_id": c.object_id,
-            "metadata_id": c.metadata_id,
-            "$values": c.member_values.iter().map(object_value_to_json).collect::<Vec<_>>(),
-        })),
-        Record::BinaryObjectString { object_id, value } => Some(json!({
-            "$record": "BinaryObjectString",
-            "object_id": *object_id,
-            "value": value,
-        })),
-        Record::BinaryArray(a) => Some(json!({
-            "$record": "BinaryArray",
-            "object_id": a.object_id,
-            "binary_array_type_enum": a.binary_array_type_enum,
-            "rank": a.rank,
-            "lengths": a.lengths,
-            "lower_bounds": a.lower_bounds,
-            "type_enum": a.type_enum,
-            "additional_type_info": a.additional_type_info,
-            "$values": a.element_values.iter().map(object_value_to_json).collect::<Vec<_>>(),
-        })),
-        Record::ArraySingleObject(a) => Some(json!({
-            "$record": "ArraySingleObject",
-            "object_id": a.object_id,
-            "length": a.length,
-            "$values": a.element_values.iter().map(object_value_to_json).collect::<Vec<_>>(),
-        })),
-        Record::ArraySinglePrimitive(a) => Some(json!({
-            "$record": "ArraySinglePrimitive",
-            "object_id": a.object_id,
-            "length": a.length,
-            "primitive_type_enum": a.primitive_type_enum,
-            "$values": a.element_values.iter().map(primitive_value_to_json).collect::<Vec<_>>(),
-        })),
-        Record::ArraySingleString(a) => Some(json!({
-            "$record": "ArraySingleString",
-            "object_id": a.object_id,
-            "length": a.length,
-            "$values": a.element_values.iter().map(object_value_to_json).collect::<Vec<_>>(),
-        })),
-        Record::MemberPrimitiveTyped {
-            primitive_type_enum,
-            value,
-        } => Some(json!({
-            "$record": "MemberPrimitiveTyped",
-            "primitive_type_enum": primitive_type_enum,
-            "value": primitive_value_to_json(value),
-        })),
-        Record::MemberReference { id_ref } => Some(json!({
-            "$record": "MemberReference",
-            "id_ref": *id_ref,
-        })),
-        Record::ObjectNull => Some(json!({ "$record": "ObjectNull" })),
-        Record::ObjectNullMultiple(n) => Some(json!({
-            "$record": "ObjectNullMultiple",
-            "null_count": n.null_count,
-        })),
-        Record::ObjectNullMultiple256(n) => Some(json!({
-            "$record": "ObjectNullMultiple256",
-            "null_count": n.null_count,
-        })),
-        Record::MessageEnd => Some(json!({ "$record": "MessageEnd" })),
     }
-}
 
-fn class_to_value(
-    name: &str,
-    object_id: i32,
-    member_names: &[String],
-    member_values: &[ObjectValue],
-    library_id: Option<i32>,
-) -> Value {
-    let mut map = Map::new();
-    map.insert("$type".to_string(), Value::String(name.to_string()));
-    map.insert("$id".to_string(), json!(object_id));
-    if let Some(lib_id) = library_id {
-        map.insert("library_id".to_string(), json!(lib_id));
+    /// Converts a primitive whose type the metadata records.
+    fn typed_primitive_to_json(&self, val: &PrimitiveValue) -> Value {
+        if self.lossless && val.needs_text() {
+            tagged_primitive_to_json(val)
+        } else {
+            primitive_value_to_json(val)
+        }
     }
 
-    for (name, val) in member_names.iter().zip(member_values.iter()) {
-        map.insert(name.clone(), object_value_to_json(val));
+    /// Converts a value whose type the metadata does not record.
+    ///
+    /// Primitives that would not read back as the same type and value are
+    /// written with a type tag, e.g. `{"$int64": "42"}`.
+    fn object_value_to_json(&mut self, val: &ObjectValue) -> Value {
+        match val {
+            ObjectValue::Primitive(
+                p @ (PrimitiveValue::Boolean(_)
+                | PrimitiveValue::Int32(_)
+                | PrimitiveValue::String(_)
+                | PrimitiveValue::Null),
+            ) => primitive_value_to_json(p),
+            ObjectValue::Primitive(p @ PrimitiveValue::Double(f)) if f.is_finite() => {
+                primitive_value_to_json(p)
+            }
+            ObjectValue::Primitive(p) => tagged_primitive_to_json(p),
+            ObjectValue::Record(r) => self.record_to_value(r).unwrap_or(Value::Null),
+        }
     }
+}
+
+/// Tag keys of the primitive types.
+const TYPE_TAGS: [(PrimitiveType, &str); 17] = [
+    (PrimitiveType::Boolean, "$boolean"),
+    (PrimitiveType::Byte, "$byte"),
+    (PrimitiveType::Char, "$char"),
+    (PrimitiveType::Decimal, "$decimal"),
+    (PrimitiveType::Double, "$double"),
+    (PrimitiveType::Int16, "$int16"),
+    (PrimitiveType::Int32, "$int32"),
+    (PrimitiveType::Int64, "$int64"),
+    (PrimitiveType::SByte, "$sbyte"),
+    (PrimitiveType::Single, "$single"),
+    (PrimitiveType::TimeSpan, "$timespan"),
+    (PrimitiveType::DateTime, "$datetime"),
+    (PrimitiveType::UInt16, "$uint16"),
+    (PrimitiveType::UInt32, "$uint32"),
+    (PrimitiveType::UInt64, "$uint64"),
+    (PrimitiveType::Null, "$null"),
+    (PrimitiveType::String, "$string"),
+];
 
-    Value::Object(map)
+/// Returns the tag key of a primitive type, e.g. `$int64`.
+pub(crate) fn type_tag(t: PrimitiveType) -> &'static str {
+    TYPE_TAGS
+        .iter()
+        .find(|(tag_type, _)| *tag_type == t)
+        .map_or("$null", |(_, tag)| tag)
 }
 
-fn object_value_to_json(val: &ObjectValue) -> Value {
-    match val {
-        ObjectValue::Primitive(p) => primitive_value_to_json(p),
-        ObjectValue::Record(r) => record_to_value(r).unwrap_or(Value::Null),
-    }
+/// Writes a primitive as `{"$<type>": "<value>"}`.
+///
+/// Numbers are written as strings so that 64-bit integers survive JSON
+/// parsers that use doubles, and NaNs are written as their bit pattern.
+fn tagged_primitive_to_json(val: &PrimitiveValue) -> Value {
+    let tag = type_tag(val.primitive_type());
+    let payload = match val {
+        PrimitiveValue::Boolean(b) => Value::Bool(*b),
+        PrimitiveValue::Null => Value::Null,
+        _ => Value::String(val.to_text()),
+    };
+    json!({ tag: payload })
 }
 
 fn primitive_value_to_json(val: &PrimitiveValue) -> Value {
@@ -202,370 +362,662 @@ fn primitive_value_to_json(val: &PrimitiveValue) -> Value {
     }
 }
 
-use std::collections::HashMap;
+/// Converts interleaved JSON back into records.
+pub fn from_interleaved(value: Value) -> Result<Vec<Record>> {
+    InterleavedDeserializer::new().deserialize(&value)
+}
 
-pub fn from_interleaved(value: Value) -> Vec<Record> {
-    let mut deserializer = InterleavedDeserializer::new();
-    deserializer.deserialize(value)
+/// A reader for the interleaved JSON produced by [`to_interleaved`].
+///
+/// Malformed input is reported as [`Error::Interleaved`] with the JSON
+/// pointer of the offending node.
+pub struct InterleavedDeserializer {
+    strict: bool,
+    metadata_registry: HashMap<i32, ClassInfoWithTypes>,
 }
 
-struct InterleavedDeserializer {
-    metadata_registry: HashMap<i32, MemberTypeInfo>,
+impl Default for InterleavedDeserializer {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl InterleavedDeserializer {
-    fn new() -> Self {
+    /// Creates a new deserializer.
+    pub fn new() -> Self {
         Self {
+            strict: false,
             metadata_registry: HashMap::new(),
         }
     }
 
-    fn deserialize(&mut self, value: Value) -> Vec<Record> {
-        let mut records = Vec::new();
-        if let Value::Array(arr) = value {
-            for v in arr {
-                if let Some(record) = self.value_to_record(&v) {
-                    records.push(record);
-                }
-            }
-        }
+    /// Enables or disables strict mode.
+    ///
+    /// In strict mode unknown keys, `ClassWithId` records with unknown
+    /// metadata and `ClassWithId` records whose values do not match their
+    /// class's members are rejected instead of ignored. Otherwise a missing
+    /// member of a `ClassWithId` that is not a primitive is read as null.
+    pub fn with_strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Reads `ClassWithId` records against classes defined elsewhere.
+    pub(crate) fn with_metadata(mut self, metadata: HashMap<i32, ClassInfoWithTypes>) -> Self {
+        self.metadata_registry = metadata;
+        self
+    }
+
+    /// Converts an array of interleaved records back into records.
+    pub fn deserialize(&mut self, value: &Value) -> Result<Vec<Record>> {
+        let records = value
+            .as_array()
+            .ok_or_else(|| invalid("", "expected an array of records"))?;
         records
+            .iter()
+            .enumerate()
+            .map(|(i, v)| self.value_to_record(v, &child("", i)))
+            .collect()
     }
 
-    fn value_to_record(&mut self, v: &Value) -> Option<Record> {
-        let obj = v.as_object()?;
-        let record_type = obj.get("$record")?.as_str()?;
+    fn value_to_record(&mut self, v: &Value, ptr: &str) -> Result<Record> {
+        let obj = v
+            .as_object()
+            .ok_or_else(|| invalid(ptr, "expected a record object"))?;
+        let fields = Fields { obj, ptr };
+        let record_type = fields.str("$record")?;
 
-        match record_type {
-            "SerializationHeader" => Some(Record::SerializationHeader(
-                serde_json::from_value(v.clone()).ok()?,
-            )),
-            "BinaryLibrary" => Some(Record::BinaryLibrary(
-                serde_json::from_value(v.clone()).ok()?,
-            )),
+        let record = match record_type {
+            "SerializationHeader" => {
+                self.check_keys(
+                    &fields,
+                    &["root_id", "header_id", "major_version", "minor_version"],
+                )?;
+                Record::SerializationHeader(SerializationHeader {
+                    root_id: fields.int("root_id")?,
+                    header_id: fields.int("header_id")?,
+                    major_version: fields.int("major_version")?,
+                    minor_version: fields.int("minor_version")?,
+                })
+            }
+            "BinaryLibrary" => {
+                self.check_keys(&fields, &["library_id", "library_name"])?;
+                Record::BinaryLibrary(BinaryLibrary {
+                    library_id: fields.int("library_id")?,
+                    library_name: fields.str("library_name")?.to_string(),
+                })
+            }
             "ClassWithMembersAndTypes" => {
-                let class_info = self.value_to_class_info(v);
-                let member_type_info: MemberTypeInfo =
-                    serde_json::from_value(obj.get("$member_type_info")?.clone()).ok()?;
-                let library_id = obj.get("library_id")?.as_i64()? as i32;
-
-                self.metadata_registry
-                    .insert(class_info.object_id, member_type_info.clone());
-
-                let member_values = self.value_to_member_values_typed(
-                    v,
-                    &class_info.member_names,
-                    &member_type_info,
-                );
-                Some(Record::ClassWithMembersAndTypes(ClassWithMembersAndTypes {
+                self.check_keys(&fields, &["$type", "$id", "$member_type_info"])?;
+                let class_info = self.value_to_class_info(&fields, true)?;
+                let member_type_info: MemberTypeInfo = fields.parse("$member_type_info")?;
+                let library_id = fields.int("library_id")?;
+                self.register(&class_info, Some(&member_type_info), Some(library_id));
+                let member_values =
+                    self.value_to_member_values(&fields, &class_info, Some(&member_type_info))?;
+                Record::ClassWithMembersAndTypes(ClassWithMembersAndTypes {
                     class_info,
                     member_type_info,
                     library_id,
                     member_values,
-                }))
+                })
             }
             "SystemClassWithMembersAndTypes" => {
-                let class_info = self.value_to_class_info(v);
-                let member_type_info: MemberTypeInfo =
-                    serde_json::from_value(obj.get("$member_type_info")?.clone()).ok()?;
-
-                self.metadata_registry
-                    .insert(class_info.object_id, member_type_info.clone());
-
-                let member_values = self.value_to_member_values_typed(
-                    v,
-                    &class_info.member_names,
-                    &member_type_info,
-                );
-                Some(Record::SystemClassWithMembersAndTypes(
-                    SystemClassWithMembersAndTypes {
-                        class_info,
-                        member_type_info,
-                        member_values,
-                    },
-                ))
+                self.check_keys(&fields, &["$type", "$id", "$member_type_info"])?;
+                let class_info = self.value_to_class_info(&fields, false)?;
+                let member_type_info: MemberTypeInfo = fields.parse("$member_type_info")?;
+                self.register(&class_info, Some(&member_type_info), None);
+                let member_values =
+                    self.value_to_member_values(&fields, &class_info, Some(&member_type_info))?;
+                Record::SystemClassWithMembersAndTypes(SystemClassWithMembersAndTypes {
+                    class_info,
+                    member_type_info,
+                    member_values,
+                })
             }
             "SystemClassWithMembers" => {
-                let class_info = self.value_to_class_info(v);
-                let member_values = self.value_to_member_values(v, &class_info.member_names);
-                Some(Record::SystemClassWithMembers(SystemClassWithMembers {
+                self.check_keys(&fields, &["$type", "$id"])?;
+                let class_info = self.value_to_class_info(&fields, false)?;
+                self.register(&class_info, None, None);
+                let member_values = self.value_to_member_values(&fields, &class_info, None)?;
+                Record::SystemClassWithMembers(SystemClassWithMembers {
                     class_info,
                     member_values,
-                }))
+                })
             }
             "ClassWithMembers" => {
-                let class_info = self.value_to_class_info(v);
-                let library_id = obj.get("library_id")?.as_i64()? as i32;
-                let member_values = self.value_to_member_values(v, &class_info.member_names);
-                Some(Record::ClassWithMembers(ClassWithMembers {
+                self.check_keys(&fields, &["$type", "$id"])?;
+                let class_info = self.value_to_class_info(&fields, true)?;
+                let library_id = fields.int("library_id")?;
+                self.register(&class_info, None, Some(library_id));
+                let member_values = self.value_to_member_values(&fields, &class_info, None)?;
+                Record::ClassWithMembers(ClassWithMembers {
                     class_info,
                     library_id,
                     member_values,
-                }))
+                })
             }
-            "ClassWithId" => {
-                let object_id = obj.get("object_id")?.as_i64()? as i32;
-                let metadata_id = obj.get("metadata_id")?.as_i64()? as i32;
-                let member_values =
-                    if let Some(mti) = self.metadata_registry.get(&metadata_id).cloned() {
-                        let vals = obj.get("$values")?.as_array()?;
-                        let mut result = Vec::new();
-                        for (i, v) in vals.iter().enumerate() {
-                            let bt = &mti.binary_type_enums[i];
-                            let add_info = &mti.additional_infos[i];
-                            match bt {
-                                BinaryType::Primitive => {
-                                    if let AdditionalTypeInfo::Primitive(p_type) = add_info {
-                                        result.push(ObjectValue::Primitive(
-                                            self.json_to_primitive_value(v, p_type),
-                                        ));
-                                    } else {
-                                        result.push(self.json_to_object_value(v));
-                                    }
-                                }
-                                _ => result.push(self.json_to_object_value(v)),
-                            }
+            "ClassWithId" if !obj.contains_key("$values") => {
+                self.check_keys(&fields, &["$type", "$id", "$metadata_id"])?;
+                let object_id = fields.int("$id")?;
+                let metadata_id = fields.int("$metadata_id")?;
+                let metadata = self
+                    .metadata_registry
+                    .get(&metadata_id)
+                    .cloned()
+                    .ok_or_else(|| {
+                        invalid(
+                            &fields.pointer("$metadata_id"),
+                            format!("unknown metadata id {}", metadata_id),
+                        )
+                    })?;
+                let names = &metadata.class_info.member_names;
+                if self.strict {
+                    if fields.str("$type")? != metadata.class_info.name {
+                        return Err(invalid(
+                            &fields.pointer("$type"),
+                            format!("expected {}", metadata.class_info.name),
+                        ));
+                    }
+                    if let Some(key) = obj
+                        .keys()
+                        .find(|key| !key.starts_with('$') && !names.contains(key))
+                    {
+                        return Err(invalid(&fields.pointer(key), "unknown member"));
+                    }
+                }
+                let mut member_values = Vec::new();
+                for (i, name) in names.iter().enumerate() {
+                    let slot = metadata
+                        .member_type_info
+                        .as_ref()
+                        .and_then(|info| info.primitive_slot(i));
+                    let Some(v) = obj.get(name) else {
+                        // Only a member that is not a primitive can be null.
+                        if self.strict || slot.is_some() {
+                            return Err(invalid(ptr, format!("missing member '{}'", name)));
                         }
-                        result
-                    } else {
-                        obj.get("$values")?
-                            .as_array()?
-                            .iter()
-                            .map(|v| self.json_to_object_value(v))
-                            .collect()
+                        member_values.push(ObjectValue::Record(Box::new(Record::ObjectNull)));
+                        continue;
                     };
-                Some(Record::ClassWithId(ClassWithId {
+                    member_values.push(self.slot_value(v, &fields.pointer(name), slot)?);
+                }
+                Record::ClassWithId(ClassWithId {
+                    object_id,
+                    metadata_id,
+                    member_values,
+                })
+            }
+            "ClassWithId" => {
+                self.check_keys(&fields, &["object_id", "metadata_id", "$values"])?;
+                let object_id = fields.int("object_id")?;
+                let metadata_id = fields.int("metadata_id")?;
+                let values = fields.array("$values")?;
+                let values_ptr = child(ptr, "$values");
+                let metadata = self.metadata_registry.get(&metadata_id).cloned();
+                match &metadata {
+                    Some(m) if self.strict && values.len() != m.class_info.member_names.len() => {
+                        return Err(invalid(
+                            &values_ptr,
+                            format!(
+                                "expected {} values for {}, found {}",
+                                m.class_info.member_names.len(),
+                                m.class_info.name,
+                                values.len()
+                            ),
+                        ));
+                    }
+                    None if self.strict => {
+                        return Err(invalid(
+                            &fields.pointer("metadata_id"),
+                            format!("unknown metadata id {}", metadata_id),
+                        ));
+                    }
+                    _ => {}
+                }
+                let member_type_info = metadata.and_then(|m| m.member_type_info);
+                let member_values = values
+                    .iter()
+                    .enumerate()
+                    .map(|(i, v)| {
+                        let slot = member_type_info
+                            .as_ref()
+                            .and_then(|info| info.primitive_slot(i));
+                        self.slot_value(v, &child(&values_ptr, i), slot)
+                    })
+                    .collect::<Result<_>>()?;
+                Record::ClassWithId(ClassWithId {
                     object_id,
                     metadata_id,
                     member_values,
-                }))
+                })
+            }
+            "BinaryObjectString" => {
+                self.check_keys(&fields, &["object_id", "value"])?;
+                Record::BinaryObjectString {
+                    object_id: fields.int("object_id")?,
+                    value: fields.str("value")?.to_string(),
+                }
             }
-            "BinaryObjectString" => Some(Record::BinaryObjectString {
-                object_id: obj.get("object_id")?.as_i64()? as i32,
-                value: obj.get("value")?.as_str()?.to_string(),
-            }),
             "BinaryArray" => {
-                let type_enum: BinaryType =
-                    serde_json::from_value(obj.get("type_enum")?.clone()).ok()?;
+                self.check_keys(
+                    &fields,
+                    &[
+                        "object_id",
+                        "binary_array_type_enum",
+                        "rank",
+                        "lengths",
+                        "lower_bounds",
+                        "type_enum",
+                        "additional_type_info",
+                        "$values",
+                    ],
+                )?;
+                let type_enum: BinaryType = fields.parse("type_enum")?;
                 let additional_type_info: AdditionalTypeInfo =
-                    serde_json::from_value(obj.get("additional_type_info")?.clone()).ok()?;
-                let element_values = obj
-                    .get("$values")?
-                    .as_array()?
+                    fields.parse("additional_type_info")?;
+                let slot = match (&type_enum, &additional_type_info) {
+                    (BinaryType::Primitive, AdditionalTypeInfo::Primitive(p)) => Some(*p),
+                    _ => None,
+                };
+                let values_ptr = fields.pointer("$values");
+                let element_values = fields
+                    .array("$values")?
                     .iter()
-                    .map(|v| match type_enum {
-                        BinaryType::Primitive => {
-                            if let AdditionalTypeInfo::Primitive(p_type) = &additional_type_info {
-                                ObjectValue::Primitive(self.json_to_primitive_value(v, p_type))
-                            } else {
-                                self.json_to_object_value(v)
-                            }
-                        }
-                        _ => self.json_to_object_value(v),
-                    })
-                    .collect();
-                Some(Record::BinaryArray(BinaryArray {
-                    object_id: obj.get("object_id")?.as_i64()? as i32,
-                    binary_array_type_enum: obj.get("binary_array_type_enum")?.as_i64()? as u8,
-                    rank: obj.get("rank")?.as_i64()? as i32,
-                    lengths: serde_json::from_value(obj.get("lengths")?.clone()).ok()?,
-                    lower_bounds: serde_json::from_value(obj.get("lower_bounds")?.clone()).ok()?,
+                    .enumerate()
+                    .map(|(i, v)| self.slot_value(v, &child(&values_ptr, i), slot))
+                    .collect::<Result<_>>()?;
+                Record::BinaryArray(BinaryArray {
+                    object_id: fields.int("object_id")?,
+                    binary_array_type_enum: fields.int("binary_array_type_enum")?,
+                    rank: fields.int("rank")?,
+                    lengths: fields.parse("lengths")?,
+                    lower_bounds: fields.parse("lower_bounds")?,
                     type_enum,
                     additional_type_info,
                     element_values,
-                }))
-            }
-            "ArraySingleObject" => Some(Record::ArraySingleObject(
-                crate::records::ArraySingleObject {
-                    object_id: obj.get("object_id")?.as_i64()? as i32,
-                    length: obj.get("length")?.as_i64()? as i32,
-                    element_values: obj
-                        .get("$values")?
-                        .as_array()?
-                        .iter()
-                        .map(|v| self.json_to_object_value(v))
-                        .collect(),
-                },
-            )),
+                })
+            }
+            "ArraySingleObject" => {
+                self.check_keys(&fields, &["object_id", "length", "$values"])?;
+                Record::ArraySingleObject(ArraySingleObject {
+                    object_id: fields.int("object_id")?,
+                    length: fields.int("length")?,
+                    element_values: self.value_to_element_values(&fields)?,
+                })
+            }
             "ArraySinglePrimitive" => {
-                let primitive_type_enum: PrimitiveType =
-                    serde_json::from_value(obj.get("primitive_type_enum")?.clone()).ok()?;
-                let element_values = obj
-                    .get("$values")?
-                    .as_array()?
+                self.check_keys(
+                    &fields,
+                    &["object_id", "length", "primitive_type_enum", "$values"],
+                )?;
+                let primitive_type_enum: PrimitiveType = fields.parse("primitive_type_enum")?;
+                let values_ptr = fields.pointer("$values");
+                let element_values = fields
+                    .array("$values")?
                     .iter()
-                    .map(
-                        |v| match self.json_to_primitive_value(v, &primitive_type_enum) {
-                            PrimitiveValue::Null => PrimitiveValue::Null,
-                            p => p,
-                        },
-                    )
-                    .collect();
-                Some(Record::ArraySinglePrimitive(
-                    crate::records::ArraySinglePrimitive {
-                        object_id: obj.get("object_id")?.as_i64()? as i32,
-                        length: obj.get("length")?.as_i64()? as i32,
-                        primitive_type_enum,
-                        element_values,
-                    },
-                ))
-            }
-            "ArraySingleString" => Some(Record::ArraySingleString(
-                crate::records::ArraySingleString {
-                    object_id: obj.get("object_id")?.as_i64()? as i32,
-                    length: obj.get("length")?.as_i64()? as i32,
-                    element_values: obj
-                        .get("$values")?
-                        .as_array()?
-                        .iter()
-                        .map(|v| self.json_to_object_value(v))
-                        .collect(),
-                },
-            )),
+                    .enumerate()
+                    .map(|(i, v)| {
+                        json_to_primitive_value(v, &primitive_type_enum, &child(&values_ptr, i))
+                    })
+                    .collect::<Result<_>>()?;
+                Record::ArraySinglePrimitive(ArraySinglePrimitive {
+                    object_id: fields.int("object_id")?,
+                    length: fields.int("length")?,
+                    primitive_type_enum,
+                    element_values,
+                })
+            }
+            "ArraySingleString" => {
+                self.check_keys(&fields, &["object_id", "length", "$values"])?;
+                Record::ArraySingleString(ArraySingleString {
+                    object_id: fields.int("object_id")?,
+                    length: fields.int("length")?,
+                    element_values: self.value_to_element_values(&fields)?,
+                })
+            }
             "MemberPrimitiveTyped" => {
-                let primitive_type_enum: PrimitiveType =
-                    serde_json::from_value(obj.get("primitive_type_enum")?.clone()).ok()?;
-                let value = self.json_to_primitive_value(obj.get("value")?, &primitive_type_enum);
-                Some(Record::MemberPrimitiveTyped {
+                self.check_keys(&fields, &["primitive_type_enum", "value"])?;
+                let primitive_type_enum: PrimitiveType = fields.parse("primitive_type_enum")?;
+                let value = json_to_primitive_value(
+                    fields.get("value")?,
+                    &primitive_type_enum,
+                    &fields.pointer("value"),
+                )?;
+                Record::MemberPrimitiveTyped {
                     primitive_type_enum,
                     value,
+                }
+            }
+            "MemberReference" => {
+                self.check_keys(&fields, &["id_ref"])?;
+                Record::MemberReference {
+                    id_ref: fields.int("id_ref")?,
+                }
+            }
+            "ObjectNull" => {
+                self.check_keys(&fields, &[])?;
+                Record::ObjectNull
+            }
+            "ObjectNullMultiple" => {
+                self.check_keys(&fields, &["null_count"])?;
+                Record::ObjectNullMultiple(ObjectNullMultiple {
+                    null_count: fields.int("null_count")?,
                 })
             }
-            "MemberReference" => Some(Record::MemberReference {
-                id_ref: obj.get("id_ref")?.as_i64()? as i32,
-            }),
-            "ObjectNull" => Some(Record::ObjectNull),
-            "ObjectNullMultiple" => Some(Record::ObjectNullMultiple(
-                crate::records::ObjectNullMultiple {
-                    null_count: obj.get("null_count")?.as_i64()? as i32,
-                },
-            )),
-            "ObjectNullMultiple256" => Some(Record::ObjectNullMultiple256(
-                crate::records::ObjectNullMultiple256 {
-                    null_count: obj.get("null_count")?.as_i64()? as u8,
-                },
-            )),
-            "MessageEnd" => Some(Record::MessageEnd),
-            _ => None,
-        }
+            "ObjectNullMultiple256" => {
+                self.check_keys(&fields, &["null_count"])?;
+                Record::ObjectNullMultiple256(ObjectNullMultiple256 {
+                    null_count: fields.int("null_count")?,
+                })
+            }
+            "MessageEnd" => {
+                self.check_keys(&fields, &[])?;
+                Record::MessageEnd
+            }
+            other => {
+                return Err(invalid(
+                    &fields.pointer("$record"),
+                    format!("unknown record type '{}'", other),
+                ));
+            }
+        };
+
+        Ok(record)
+    }
+
+    /// Registers class metadata before its members are read, since they may
+    /// contain `ClassWithId` records that reuse it.
+    fn register(
+        &mut self,
+        class_info: &ClassInfo,
+        member_type_info: Option<&MemberTypeInfo>,
+        library_id: Option<i32>,
+    ) {
+        self.metadata_registry.insert(
+            class_info.object_id,
+            ClassInfoWithTypes {
+                class_info: class_info.clone(),
+                member_type_info: member_type_info.cloned(),
+                library_id,
+            },
+        );
     }
 
-    fn value_to_class_info(&self, v: &Value) -> ClassInfo {
-        let obj = v.as_object().unwrap();
-        let name = obj.get("$type").unwrap().as_str().unwrap().to_string();
-        let object_id = obj.get("$id").unwrap().as_i64().unwrap() as i32;
-        let mut member_names = Vec::new();
-        for key in obj.keys() {
-            if !key.starts_with('$') && key != "library_id" {
-                member_names.push(key.clone());
+    /// In strict mode, rejects keys other than `$record` and `allowed`.
+    ///
+    /// Class records also allow their members, which are the keys without a
+    /// `$` prefix (and `library_id` where the record has one).
+    fn check_keys(&self, fields: &Fields, allowed: &[&str]) -> Result<()> {
+        if !self.strict {
+            return Ok(());
+        }
+        let is_class = allowed.contains(&"$type");
+        for key in fields.obj.keys() {
+            let known = key == "$record"
+                || allowed.contains(&key.as_str())
+                || (is_class && !key.starts_with('$'));
+            if !known {
+                return Err(invalid(&fields.pointer(key), "unknown key"));
             }
         }
+        Ok(())
+    }
 
-        ClassInfo {
+    fn value_to_class_info(&self, fields: &Fields, has_library: bool) -> Result<ClassInfo> {
+        let name = fields.str("$type")?.to_string();
+        let object_id = fields.int("$id")?;
+        let member_names: Vec<String> = fields
+            .obj
+            .keys()
+            .filter(|key| !(key.starts_with('$') || (has_library && *key == "library_id")))
+            .cloned()
+            .collect();
+
+        Ok(ClassInfo {
             object_id,
             name,
             member_count: member_names.len() as i32,
             member_names,
-        }
+        })
     }
 
-    fn value_to_member_values(&mut self, v: &Value, member_names: &[String]) -> Vec<ObjectValue> {
-        let obj = v.as_object().unwrap();
-        let mut values = Vec::new();
-        for name in member_names {
-            if let Some(val) = obj.get(name) {
-                values.push(self.json_to_object_value(val));
+    fn value_to_member_values(
+        &mut self,
+        fields: &Fields,
+        class_info: &ClassInfo,
+        member_type_info: Option<&MemberTypeInfo>,
+    ) -> Result<Vec<ObjectValue>> {
+        if let Some(info) = member_type_info {
+            let count = class_info.member_names.len();
+            if info.binary_type_enums.len() != count || info.additional_infos.len() != count {
+                return Err(invalid(
+                    &fields.pointer("$member_type_info"),
+                    format!(
+                        "type info has {} binary types and {} additional infos for {} members",
+                        info.binary_type_enums.len(),
+                        info.additional_infos.len(),
+                        count
+                    ),
+                ));
             }
         }
-        values
+        class_info
+            .member_names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| {
+                let slot = member_type_info.and_then(|info| info.primitive_slot(i));
+                self.slot_value(fields.get(name)?, &fields.pointer(name), slot)
+            })
+            .collect()
+    }
+
+    fn value_to_element_values(&mut self, fields: &Fields) -> Result<Vec<ObjectValue>> {
+        let values_ptr = fields.pointer("$values");
+        fields
+            .array("$values")?
+            .iter()
+            .enumerate()
+            .map(|(i, v)| self.json_to_object_value(v, &child(&values_ptr, i)))
+            .collect()
     }
 
-    fn value_to_member_values_typed(
+    /// Converts the value of a member or element, typed by its slot if known.
+    fn slot_value(
         &mut self,
         v: &Value,
-        member_names: &[String],
-        member_type_info: &MemberTypeInfo,
-    ) -> Vec<ObjectValue> {
-        let obj = v.as_object().unwrap();
-        let mut values = Vec::new();
-        for (i, name) in member_names.iter().enumerate() {
-            if let Some(val) = obj.get(name) {
-                let binary_type = &member_type_info.binary_type_enums[i];
-                let additional_info = &member_type_info.additional_infos[i];
-
-                match binary_type {
-                    BinaryType::Primitive => {
-                        if let AdditionalTypeInfo::Primitive(p_type) = additional_info {
-                            values.push(ObjectValue::Primitive(
-                                self.json_to_primitive_value(val, p_type),
-                            ));
-                        } else {
-                            values.push(self.json_to_object_value(val));
-                        }
-                    }
-                    _ => {
-                        values.push(self.json_to_object_value(val));
-                    }
+        ptr: &str,
+        slot: Option<PrimitiveType>,
+    ) -> Result<ObjectValue> {
+        match slot {
+            Some(p_type) => Ok(ObjectValue::Primitive(json_to_primitive_value(
+                v, &p_type, ptr,
+            )?)),
+            None => self.json_to_object_value(v, ptr),
+        }
+    }
+
+    pub(crate) fn json_to_object_value(&mut self, v: &Value, ptr: &str) -> Result<ObjectValue> {
+        let primitive = match v {
+            Value::Object(obj) if obj.contains_key("$record") => {
+                return Ok(ObjectValue::Record(Box::new(self.value_to_record(v, ptr)?)));
+            }
+            Value::Object(_) => match tagged_primitive(v, ptr)? {
+                Some(value) => value,
+                None => return Err(invalid(ptr, "expected a record or a type-tagged value")),
+            },
+            Value::Bool(b) => PrimitiveValue::Boolean(*b),
+            Value::Number(n) => {
+                if let Some(i) = n.as_i64().and_then(|i| i32::try_from(i).ok()) {
+                    PrimitiveValue::Int32(i)
+                } else if let Some(u) = n.as_u64().and_then(|u| u32::try_from(u).ok()) {
+                    PrimitiveValue::UInt32(u)
+                } else if let Some(i) = n.as_i64() {
+                    PrimitiveValue::Int64(i)
+                } else if let Some(u) = n.as_u64() {
+                    PrimitiveValue::UInt64(u)
+                } else {
+                    PrimitiveValue::Double(n.as_f64().unwrap_or(f64::NAN))
                 }
             }
-        }
-        values
-    }
-
-    fn json_to_primitive_value(&self, v: &Value, t: &PrimitiveType) -> PrimitiveValue {
-        match t {
-            PrimitiveType::Boolean => PrimitiveValue::Boolean(v.as_bool().unwrap_or(false)),
-            PrimitiveType::Byte => PrimitiveValue::Byte(v.as_i64().unwrap_or(0) as u8),
-            PrimitiveType::UInt16 => PrimitiveValue::UInt16(v.as_u64().unwrap_or(0) as u16),
-            PrimitiveType::UInt32 => PrimitiveValue::UInt32(v.as_u64().unwrap_or(0) as u32),
-            PrimitiveType::Char => {
-                PrimitiveValue::Char(v.as_str().and_then(|s| s.chars().next()).unwrap_or('\0'))
-            }
-            PrimitiveType::Decimal => {
-                PrimitiveValue::Decimal(v.as_str().unwrap_or("0").to_string())
-            }
-            PrimitiveType::Double => PrimitiveValue::Double(v.as_f64().unwrap_or(0.0)),
-            PrimitiveType::Int16 => PrimitiveValue::Int16(v.as_i64().unwrap_or(0) as i16),
-            PrimitiveType::Int32 => PrimitiveValue::Int32(v.as_i64().unwrap_or(0) as i32),
-            PrimitiveType::Int64 => PrimitiveValue::Int64(v.as_i64().unwrap_or(0)),
-            PrimitiveType::SByte => PrimitiveValue::SByte(v.as_i64().unwrap_or(0) as i8),
-            PrimitiveType::Single => PrimitiveValue::Single(v.as_f64().unwrap_or(0.0) as f32),
-            PrimitiveType::TimeSpan => PrimitiveValue::TimeSpan(v.as_i64().unwrap_or(0)),
-            PrimitiveType::DateTime => PrimitiveValue::DateTime(
-                v.as_u64()
-                    .or_else(|| v.as_i64().map(|i| i as u64))
-                    .unwrap_or(0),
-            ),
-            PrimitiveType::UInt64 => PrimitiveValue::UInt64(
-                v.as_u64()
-                    .or_else(|| v.as_i64().map(|i| i as u64))
-                    .unwrap_or(0),
-            ),
-            PrimitiveType::String => PrimitiveValue::String(v.as_str().unwrap_or("").to_string()),
-            PrimitiveType::Null => PrimitiveValue::Null,
-        }
+            Value::String(s) => PrimitiveValue::String(s.clone()),
+            Value::Null => PrimitiveValue::Null,
+            Value::Array(_) => {
+                return Err(invalid(ptr, "expected a record or a primitive value"));
+            }
+        };
+        Ok(ObjectValue::Primitive(primitive))
     }
+}
 
-    fn json_to_object_value(&mut self, v: &Value) -> ObjectValue {
-        if let Some(record) = self.value_to_record(v) {
-            return ObjectValue::Record(Box::new(record));
+pub(crate) fn json_to_primitive_value(
+    v: &Value,
+    t: &PrimitiveType,
+    ptr: &str,
+) -> Result<PrimitiveValue> {
+    fn int<T: TryFrom<i64>>(v: &Value) -> Option<T> {
+        v.as_i64().and_then(|i| T::try_from(i).ok())
+    }
+    fn uint<T: TryFrom<u64>>(v: &Value) -> Option<T> {
+        v.as_u64().and_then(|u| T::try_from(u).ok())
+    }
+    fn float(v: &Value) -> Option<f64> {
+        if v.is_null() {
+            Some(f64::NAN)
+        } else {
+            v.as_f64()
         }
+    }
 
-        // Fallback for primitive/basic types
-        if v.is_boolean() {
-            ObjectValue::Primitive(PrimitiveValue::Boolean(v.as_bool().unwrap()))
-        } else if v.is_number() {
-            if v.is_i64() {
-                ObjectValue::Primitive(PrimitiveValue::Int32(v.as_i64().unwrap() as i32))
-            } else if v.is_u64() {
-                ObjectValue::Primitive(PrimitiveValue::UInt32(v.as_u64().unwrap() as u32))
-            } else {
-                ObjectValue::Primitive(PrimitiveValue::Double(v.as_f64().unwrap()))
-            }
-        } else if v.is_string() {
-            ObjectValue::Primitive(PrimitiveValue::String(v.as_str().unwrap().to_string()))
-        } else {
-            ObjectValue::Primitive(PrimitiveValue::Null)
+    match tagged_primitive(v, ptr)? {
+        Some(value) if value.primitive_type() == *t => return Ok(value),
+        Some(value) => {
+            return Err(invalid(
+                ptr,
+                format!(
+                    "expected a {:?} value, found a {:?} tag",
+                    t,
+                    value.primitive_type()
+                ),
+            ));
         }
+        None => {}
+    }
+
+    // Numbers may be written as text to keep them exact.
+    if let Some(text) = v.as_str()
+        && !matches!(
+            t,
+            PrimitiveType::Char | PrimitiveType::Decimal | PrimitiveType::String
+        )
+    {
+        return PrimitiveValue::from_text(*t, text)
+            .ok_or_else(|| invalid(ptr, format!("expected a {:?} value, found {}", t, v)));
+    }
+
+    let value = match t {
+        PrimitiveType::Boolean => v.as_bool().map(PrimitiveValue::Boolean),
+        PrimitiveType::Byte => uint(v).map(PrimitiveValue::Byte),
+        PrimitiveType::UInt16 => uint(v).map(PrimitiveValue::UInt16),
+        PrimitiveType::UInt32 => uint(v).map(PrimitiveValue::UInt32),
+        PrimitiveType::Char => v
+            .as_str()
+            .and_then(|s| PrimitiveValue::from_text(PrimitiveType::Char, s)),
+        PrimitiveType::Decimal => v.as_str().map(|s| PrimitiveValue::Decimal(s.to_string())),
+        // serde_json writes non-finite floats as null.
+        PrimitiveType::Double => float(v).map(PrimitiveValue::Double),
+        PrimitiveType::Int16 => int(v).map(PrimitiveValue::Int16),
+        PrimitiveType::Int32 => int(v).map(PrimitiveValue::Int32),
+        PrimitiveType::Int64 => v.as_i64().map(PrimitiveValue::Int64),
+        PrimitiveType::SByte => int(v).map(PrimitiveValue::SByte),
+        PrimitiveType::Single => float(v).map(|f| PrimitiveValue::Single(f as f32)),
+        PrimitiveType::TimeSpan => v.as_i64().map(PrimitiveValue::TimeSpan),
+        PrimitiveType::DateTime => v
+            .as_u64()
+            .or_else(|| v.as_i64().map(|i| i as u64))
+            .map(PrimitiveValue::DateTime),
+        PrimitiveType::UInt64 => v
+            .as_u64()
+            .or_else(|| v.as_i64().map(|i| i as u64))
+            .map(PrimitiveValue::UInt64),
+        PrimitiveType::String => v.as_str().map(|s| PrimitiveValue::String(s.to_string())),
+        PrimitiveType::Null => Some(PrimitiveValue::Null),
+    };
+    value.ok_or_else(|| invalid(ptr, format!("expected a {:?} value, found {}", t, v)))
+}
+
+/// Reads a type-tagged primitive such as `{"$int64": "42"}`.
+///
+/// Returns `Ok(None)` if `v` is not a type tag.
+fn tagged_primitive(v: &Value, ptr: &str) -> Result<Option<PrimitiveValue>> {
+    let Some(obj) = v.as_object().filter(|obj| obj.len() == 1) else {
+        return Ok(None);
+    };
+    let Some((key, payload)) = obj.iter().next() else {
+        return Ok(None);
+    };
+    let Some((t, _)) = TYPE_TAGS.iter().find(|(_, tag)| tag == key) else {
+        return Ok(None);
+    };
+    let ptr = child(ptr, key);
+    json_to_primitive_value(payload, t, &ptr).map(Some)
+}
+
+/// The fields of a JSON object, reporting errors against its pointer.
+struct Fields<'a> {
+    obj: &'a Map<String, Value>,
+    ptr: &'a str,
+}
+
+impl<'a> Fields<'a> {
+    fn pointer(&self, key: &str) -> String {
+        child(self.ptr, key)
+    }
+
+    fn get(&self, key: &str) -> Result<&'a Value> {
+        self.obj
+            .get(key)
+            .ok_or_else(|| invalid(self.ptr, format!("missing field '{}'", key)))
+    }
+
+    fn int<T: TryFrom<i64>>(&self, key: &str) -> Result<T> {
+        self.get(key)?
+            .as_i64()
+            .and_then(|i| T::try_from(i).ok())
+            .ok_or_else(|| invalid(&self.pointer(key), "expected an integer in range"))
+    }
+
+    fn str(&self, key: &str) -> Result<&'a str> {
+        self.get(key)?
+            .as_str()
+            .ok_or_else(|| invalid(&self.pointer(key), "expected a string"))
+    }
+
+    fn array(&self, key: &str) -> Result<&'a Vec<Value>> {
+        self.get(key)?
+            .as_array()
+            .ok_or_else(|| invalid(&self.pointer(key), "expected an array"))
+    }
+
+    fn parse<T: DeserializeOwned>(&self, key: &str) -> Result<T> {
+        T::deserialize(self.get(key)?).map_err(|e| invalid(&self.pointer(key), e.to_string()))
+    }
+}
+
+/// Appends a reference token to a JSON pointer, escaping it per RFC 6901.
+pub(crate) fn child(ptr: &str, token: impl ToString) -> String {
+    format!("{}/{}", ptr, escape(&token.to_string()))
+}
+
+/// Escapes a reference token of a JSON pointer.
+pub(crate) fn escape(token: &str) -> String {
+    token.replace('~', "~0").replace('/', "~1")
+}
+
+fn invalid(pointer: &str, message: impl Into<String>) -> Error {
+    Error::Interleaved {
+        pointer: pointer.to_string(),
+        message: message.into(),
     }
 }
@@ -0,0 +1,697 @@
+// nrbf-parser - A high-performance MS-NRBF binary parser and encoder.
+// Copyright (C) 2026  driedpampas@proton.me
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Applying RFC 6902 JSON Patches to decoded records.
+//!
+//! Patches are written against the [interleaved JSON](crate::interleaved)
+//! of the records, as a JSON editor would produce them, but are applied to
+//! the records directly:
+//!
+//! - `/3/level` is the member `level` of top-level record 3, and pointers
+//!   continue into objects written inline, e.g. `/3/inventory/$values/0/name`.
+//! - `$values/<n>` is entry `n` of an array as the JSON shows it, so a null
+//!   run is one entry. `$values/-` appends.
+//! - `value` of a `BinaryObjectString` edits the string itself, and
+//!   `id_ref` of a `MemberReference` points the reference at another object.
+//!
+//! Ids, metadata and lengths cannot be patched; they are kept consistent the
+//! same way as for a [`Patch`]. New values are checked against the type of
+//! the member or element they go into, and objects in them whose ids are
+//! already used get fresh ids, so `copy` duplicates objects.
+//!
+//! Every operation is applied in order and the patch fails as a whole.
+
+use crate::error::{Error, Result};
+use crate::interleaved::{
+    InterleavedDeserializer, InterleavedSerializer, json_to_primitive_value, to_interleaved,
+};
+use crate::patch::{
+    Kind, Patch, Shape, Stored, accepts, compress, expand, expected, find_object, found,
+    index_objects,
+};
+use crate::plain::{Graph, null_count};
+use crate::records::*;
+use crate::renumber::{IdMap, renumber};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+
+/// Applies a JSON Patch to `records` and returns the patched records.
+pub fn apply_json_patch(records: &[Record], patch: &Value) -> Result<Vec<Record>> {
+    let operations = patch
+        .as_array()
+        .ok_or_else(|| invalid("", "expected an array of operations"))?;
+    let mut editor = Editor {
+        patch: Patch::new(records.to_vec()),
+        dropped: Vec::new(),
+        touched: HashSet::new(),
+    };
+    for (i, operation) in operations.iter().enumerate() {
+        editor.operation(i, operation)?;
+    }
+    editor.finish()
+}
+
+struct Editor {
+    patch: Patch,
+    /// Records taken out by operations so far.
+    dropped: Vec<Record>,
+    /// Arrays whose entries changed.
+    touched: HashSet<i32>,
+}
+
+/// What a JSON pointer addresses.
+enum Target {
+    /// A class member or an array entry: value `index` of `container`.
+    Slot {
+        container: i32,
+        index: usize,
+        element: bool,
+    },
+    /// The end of an array, `$values/-`.
+    End(i32),
+    /// The text of a string object.
+    Text(i32),
+    /// The target of the reference in value `index` of `container`.
+    Reference { container: i32, index: usize },
+}
+
+impl Editor {
+    fn operation(&mut self, i: usize, operation: &Value) -> Result<()> {
+        let field = |key: &str| {
+            operation.get(key).ok_or_else(|| {
+                invalid(
+                    &format!("/{}", i),
+                    format!("operation {} has no '{}'", i, key),
+                )
+            })
+        };
+        let text = |key: &str| {
+            field(key)?.as_str().ok_or_else(|| {
+                invalid(
+                    &format!("/{}", i),
+                    format!("'{}' of operation {} is not a string", key, i),
+                )
+            })
+        };
+        let path = text("path")?;
+        match text("op")? {
+            "add" => self.add(path, field("value")?),
+            "remove" => self.remove(path),
+            "replace" => self.replace(path, field("value")?),
+            "move" => {
+                let from = text("from")?;
+                if path.starts_with(from) && path[from.len()..].starts_with('/') {
+                    return Err(invalid(path, format!("cannot be moved into '{}'", from)));
+                }
+                let value = self.current(from, true)?;
+                self.remove(from)?;
+                self.add(path, &value)
+            }
+            "copy" => {
+                let value = self.current(text("from")?, true)?;
+                self.add(path, &value)
+            }
+            "test" => {
+                let value = field("value")?;
+                let plain = self.current(path, false)?;
+                let lossless = self.current(path, true)?;
+                if equal(&plain, value) || equal(&lossless, value) {
+                    Ok(())
+                } else {
+                    Err(invalid(path, format!("test failed, found {}", plain)))
+                }
+            }
+            op => Err(invalid(path, format!("unknown operation '{}'", op))),
+        }
+    }
+
+    fn add(&mut self, path: &str, value: &Value) -> Result<()> {
+        match self.resolve(path, true)? {
+            Target::Slot {
+                container,
+                index,
+                element: true,
+            } => {
+                let stored = self.read(path, container, index, value)?;
+                self.splice(path, container, index, false, Some(stored))
+            }
+            Target::End(container) => {
+                let len = self.entries(container);
+                let stored = self.read(path, container, len, value)?;
+                self.splice(path, container, len, false, Some(stored))
+            }
+            target => self.set(path, target, value),
+        }
+    }
+
+    fn replace(&mut self, path: &str, value: &Value) -> Result<()> {
+        match self.resolve(path, false)? {
+            Target::Slot {
+                container,
+                index,
+                element: true,
+            } => {
+                let stored = self.read(path, container, index, value)?;
+                self.splice(path, container, index, true, Some(stored))
+            }
+            Target::End(_) => Err(invalid(path, "does not exist")),
+            target => self.set(path, target, value),
+        }
+    }
+
+    fn remove(&mut self, path: &str) -> Result<()> {
+        match self.resolve(path, false)? {
+            Target::Slot {
+                container,
+                index,
+                element: true,
+            } => self.splice(path, container, index, true, None),
+            Target::Slot { .. } => Err(invalid(
+                path,
+                "class members cannot be removed, replace them with null",
+            )),
+            _ => Err(invalid(path, "cannot be removed")),
+        }
+    }
+
+    /// Replaces a member, a string's text or a reference's target.
+    fn set(&mut self, path: &str, target: Target, value: &Value) -> Result<()> {
+        match target {
+            Target::Slot {
+                container, index, ..
+            } => {
+                let Stored::Value(value) = self.read(path, container, index, value)? else {
+                    return Err(invalid(path, "does not select a member or element"));
+                };
+                let record = self.object(path, container)?;
+                let slot = record
+                    .child_values_mut()
+                    .get_mut(index)
+                    .ok_or_else(|| invalid(path, "has no value to replace"))?;
+                let old = std::mem::replace(slot, value);
+                if let ObjectValue::Record(old) = old {
+                    self.dropped.push(*old);
+                }
+            }
+            Target::Text(id) => {
+                let text = value
+                    .as_str()
+                    .ok_or_else(|| invalid(path, format!("expected a string, found {}", value)))?
+                    .to_string();
+                if let Record::BinaryObjectString { value, .. } = self.object(path, id)? {
+                    *value = text;
+                }
+            }
+            Target::Reference { container, index } => {
+                let id = value
+                    .as_i64()
+                    .and_then(|id| i32::try_from(id).ok())
+                    .ok_or_else(|| {
+                        invalid(path, format!("expected an object id, found {}", value))
+                    })?;
+                let kind = self.kind(path, container, index)?;
+                let shape = self
+                    .patch
+                    .shape(&crate::builder::Value::Reference(id))
+                    .map_err(|message| invalid(path, message))?;
+                check(path, &kind, &shape)?;
+                let record = self.object(path, container)?;
+                if let Some(ObjectValue::Record(r)) = record.child_values_mut().get_mut(index)
+                    && let Record::MemberReference { id_ref } = r.as_mut()
+                {
+                    *id_ref = id;
+                }
+            }
+            Target::End(_) => return Err(invalid(path, "does not exist")),
+        }
+        Ok(())
+    }
+
+    /// Inserts `stored` before entry `index` of an array, replacing the
+    /// entry if `remove` is set, or removes the entry.
+    fn splice(
+        &mut self,
+        path: &str,
+        container: i32,
+        index: usize,
+        remove: bool,
+        stored: Option<Stored>,
+    ) -> Result<()> {
+        let record = self.object(path, container)?;
+        let mut removed = None;
+        match (record, stored) {
+            (Record::ArraySinglePrimitive(a), stored) => {
+                if remove {
+                    a.element_values.remove(index);
+                }
+                if let Some(Stored::Element(value)) = stored {
+                    a.element_values.insert(index, value);
+                }
+                a.length = a.element_values.len() as i32;
+            }
+            (record, stored) => {
+                let old_len = array_len(record);
+                let Some(values) = entries_mut(record) else {
+                    return Err(invalid(path, "does not select an array element"));
+                };
+                if remove {
+                    removed = Some(values.remove(index));
+                }
+                if let Some(Stored::Value(value)) = stored {
+                    values.insert(index, value);
+                }
+                let len = array_len(record);
+                match record {
+                    Record::BinaryArray(a) if a.rank != 1 && len != old_len => {
+                        return Err(invalid(path, "multi-dimensional arrays cannot be resized"));
+                    }
+                    Record::BinaryArray(a) if a.rank == 1 => a.lengths[0] = len as i32,
+                    Record::ArraySingleObject(a) => a.length = len as i32,
+                    Record::ArraySingleString(a) => a.length = len as i32,
+                    _ => {}
+                }
+            }
+        }
+        if let Some(ObjectValue::Record(old)) = removed {
+            self.dropped.push(*old);
+        }
+        self.touched.insert(container);
+        Ok(())
+    }
+
+    /// Builds the value for a member or element of `container` from JSON.
+    fn read(&mut self, path: &str, container: i32, index: usize, value: &Value) -> Result<Stored> {
+        let kind = self.kind(path, container, index)?;
+        let primitive = match &kind {
+            Kind::Element(t) => {
+                return Ok(Stored::Element(
+                    json_to_primitive_value(value, t, path).map_err(reading)?,
+                ));
+            }
+            Kind::Typed(BinaryType::Primitive, AdditionalTypeInfo::Primitive(t)) => {
+                return Ok(Stored::Value(ObjectValue::Primitive(
+                    json_to_primitive_value(value, t, path).map_err(reading)?,
+                )));
+            }
+            _ => InterleavedDeserializer::new()
+                .with_metadata(self.patch.metadata.clone())
+                .json_to_object_value(value, path)
+                .map_err(reading)?,
+        };
+        let record = match primitive {
+            ObjectValue::Record(record) => *record,
+            ObjectValue::Primitive(PrimitiveValue::Null) => Record::ObjectNull,
+            ObjectValue::Primitive(PrimitiveValue::String(value)) => Record::BinaryObjectString {
+                object_id: self.patch.builder.reserve_id(),
+                value,
+            },
+            ObjectValue::Primitive(value) => Record::MemberPrimitiveTyped {
+                primitive_type_enum: value.primitive_type(),
+                value,
+            },
+        };
+        let shape = self
+            .record_shape(&record)
+            .map_err(|message| invalid(path, message))?;
+        check(path, &kind, &shape)?;
+        let record = self.adopt(path, record)?;
+        Ok(Stored::Value(ObjectValue::Record(Box::new(record))))
+    }
+
+    /// Returns what a new record is, looking up classes and references in
+    /// the record itself before the message.
+    fn record_shape(&self, record: &Record) -> std::result::Result<Shape, String> {
+        let mut inside = HashMap::new();
+        index_objects(record, &mut inside);
+        let mut record = record;
+        // Follow references, but not around a cycle.
+        for _ in 0..=inside.len() {
+            return Ok(match record {
+                Record::ObjectNull
+                | Record::ObjectNullMultiple(_)
+                | Record::ObjectNullMultiple256(_) => Shape::Null,
+                Record::BinaryObjectString { .. } => Shape::String,
+                Record::MemberPrimitiveTyped { value, .. } => {
+                    Shape::Primitive(value.primitive_type())
+                }
+                Record::ArraySinglePrimitive(a) => Shape::PrimitiveArray(a.primitive_type_enum),
+                Record::ArraySingleObject(_) => Shape::ObjectArray,
+                Record::ArraySingleString(_) => Shape::StringArray,
+                Record::BinaryArray(_) => Shape::Array,
+                Record::MemberReference { id_ref } => match inside.get(id_ref) {
+                    Some(target) => {
+                        record = target;
+                        continue;
+                    }
+                    None => self
+                        .patch
+                        .shape(&crate::builder::Value::Reference(*id_ref))?,
+                },
+                Record::ClassWithId(c) => {
+                    let name = match inside.get(&c.metadata_id).and_then(|r| r.class_info()) {
+                        Some(info) => &info.name,
+                        None => {
+                            &self
+                                .patch
+                                .metadata
+                                .get(&c.metadata_id)
+                                .ok_or_else(|| format!("unknown metadata id {}", c.metadata_id))?
+                                .class_info
+                                .name
+                        }
+                    };
+                    Shape::Class(name.clone())
+                }
+                _ => match record.class_info() {
+                    Some(info) => Shape::Class(info.name.clone()),
+                    None => return Err(format!("{:?} is not a value", RecordType::from(record))),
+                },
+            });
+        }
+        Err("references itself".to_string())
+    }
+
+    /// Prepares a new record for the message: objects whose ids are taken
+    /// get fresh ids, and references and libraries must exist.
+    fn adopt(&mut self, path: &str, mut record: Record) -> Result<Record> {
+        let graph = Graph::new(&self.patch.records);
+        let mut libraries = HashSet::new();
+        for r in &self.patch.records {
+            if let Record::BinaryLibrary(l) = r {
+                libraries.insert(l.library_id);
+            }
+        }
+        let mut ids = Vec::new();
+        collect_ids(&record, &mut ids);
+        let mut seen = HashSet::new();
+        let mut renamed = IdMap::default();
+        for id in ids {
+            if !seen.insert(id) {
+                return Err(invalid(path, format!("object id {} is used twice", id)));
+            }
+            if graph.objects.contains_key(&id) {
+                renamed.objects.insert(id, self.patch.builder.reserve_id());
+            }
+        }
+        renumber(std::slice::from_mut(&mut record), &renamed);
+
+        let mut problem = None;
+        check_links(&record, &mut |r| {
+            let message = match r {
+                Record::MemberReference { id_ref }
+                    if !seen.contains(id_ref) && !graph.objects.contains_key(id_ref) =>
+                {
+                    format!("no object has id {}", id_ref)
+                }
+                Record::ClassWithMembersAndTypes(c) if !libraries.contains(&c.library_id) => {
+                    format!("no library has id {}", c.library_id)
+                }
+                Record::ClassWithMembers(c) if !libraries.contains(&c.library_id) => {
+                    format!("no library has id {}", c.library_id)
+                }
+                Record::SerializationHeader(_) | Record::BinaryLibrary(_) | Record::MessageEnd => {
+                    format!("{:?} is not a value", RecordType::from(r))
+                }
+                _ => return,
+            };
+            problem.get_or_insert(message);
+        });
+        match problem {
+            Some(message) => Err(invalid(path, message)),
+            None => Ok(record),
+        }
+    }
+
+    /// Returns the interleaved JSON at `path`.
+    fn current(&self, path: &str, lossless: bool) -> Result<Value> {
+        let json = if lossless {
+            InterleavedSerializer::new()
+                .with_lossless(true)
+                .serialize(&self.patch.records)
+        } else {
+            to_interleaved(&self.patch.records)
+        };
+        json.pointer(path)
+            .cloned()
+            .ok_or_else(|| invalid(path, "does not exist"))
+    }
+
+    /// Finds what `path` addresses, following members and array entries
+    /// through objects written inline.
+    ///
+    /// With `add`, the last token may also be the length of an array, which
+    /// addresses the position after its last entry.
+    fn resolve(&self, path: &str, add: bool) -> Result<Target> {
+        let tokens = tokens(path)?;
+        let Some((first, mut rest)) = tokens.split_first() else {
+            return Err(invalid(path, "the whole message cannot be patched"));
+        };
+        let mut record = index(first, self.patch.records.len())
+            .map(|i| &self.patch.records[i])
+            .ok_or_else(|| invalid(path, format!("there is no record {}", first)))?;
+        if rest.is_empty() {
+            return Err(invalid(path, "top-level records cannot be patched"));
+        }
+        let graph = Graph::new(&self.patch.records);
+        loop {
+            let Some((token, after)) = rest.split_first() else {
+                return Err(invalid(path, "does not select a value"));
+            };
+            let container = record.object_id().unwrap_or_default();
+            let (index, element) = match record {
+                Record::BinaryObjectString { object_id, .. }
+                    if token == "value" && after.is_empty() =>
+                {
+                    return Ok(Target::Text(*object_id));
+                }
+                Record::ArraySinglePrimitive(a) if token == "$values" => {
+                    return match after {
+                        [entry] if entry == "-" => Ok(Target::End(container)),
+                        [entry] => index(entry, a.element_values.len() + add as usize)
+                            .map(|index| Target::Slot {
+                                container,
+                                index,
+                                element: true,
+                            })
+                            .ok_or_else(|| invalid(path, format!("there is no entry {}", entry))),
+                        _ => Err(invalid(path, "does not select a value")),
+                    };
+                }
+                Record::BinaryArray(_)
+                | Record::ArraySingleObject(_)
+                | Record::ArraySingleString(_)
+                    if token == "$values" =>
+                {
+                    let Some((entry, after)) = after.split_first() else {
+                        return Err(invalid(path, "'$values' cannot be patched"));
+                    };
+                    if entry == "-" && after.is_empty() {
+                        return Ok(Target::End(container));
+                    }
+                    let end = record.child_values().len() + (add && after.is_empty()) as usize;
+                    let index = index(entry, end)
+                        .ok_or_else(|| invalid(path, format!("there is no entry {}", entry)))?;
+                    rest = after;
+                    (index, true)
+                }
+                _ => {
+                    let index = graph
+                        .class_of(record)
+                        .and_then(|class| {
+                            class
+                                .class_info
+                                .member_names
+                                .iter()
+                                .position(|name| name == token)
+                        })
+                        .ok_or_else(|| invalid(path, format!("'{}' cannot be patched", token)))?;
+                    rest = after;
+                    (index, false)
+                }
+            };
+            let Some((next, _)) = rest.split_first() else {
+                return Ok(Target::Slot {
+                    container,
+                    index,
+                    element,
+                });
+            };
+            let value = record
+                .child_values()
+                .get(index)
+                .ok_or_else(|| invalid(path, format!("'{}' has no value", token)))?;
+            match value {
+                ObjectValue::Record(r) if matches!(r.as_ref(), Record::MemberReference { .. }) => {
+                    return match rest {
+                        [token] if token == "id_ref" => Ok(Target::Reference { container, index }),
+                        _ => Err(invalid(path, format!("'{}' cannot be patched", next))),
+                    };
+                }
+                ObjectValue::Record(r) => record = r,
+                ObjectValue::Primitive(_) => {
+                    return Err(invalid(path, format!("'{}' cannot be patched", next)));
+                }
+            }
+        }
+    }
+
+    fn kind(&self, path: &str, container: i32, index: usize) -> Result<Kind> {
+        self.patch
+            .kind(container, index)
+            .ok_or_else(|| invalid(path, "does not select a member or element"))
+    }
+
+    fn object(&mut self, path: &str, id: i32) -> Result<&mut Record> {
+        find_object(&mut self.patch.records, id)
+            .ok_or_else(|| invalid(path, "does not select a member or element"))
+    }
+
+    /// Returns the number of entries of array `id`.
+    fn entries(&mut self, id: i32) -> usize {
+        match find_object(&mut self.patch.records, id) {
+            Some(Record::ArraySinglePrimitive(a)) => a.element_values.len(),
+            Some(record) => record.child_values().len(),
+            None => 0,
+        }
+    }
+
+    /// Keeps dropped objects that are still referenced, writes the changed
+    /// arrays' nulls as runs and restores the stream invariants.
+    fn finish(mut self) -> Result<Vec<Record>> {
+        self.patch.drop_records(std::mem::take(&mut self.dropped));
+        for id in self.touched {
+            if let Some(record) = find_object(&mut self.patch.records, id)
+                && let Some(values) = entries_mut(record)
+            {
+                *values = compress(expand(values));
+            }
+        }
+        self.patch.fix_up();
+        Ok(self.patch.into_records())
+    }
+}
+
+fn check(path: &str, kind: &Kind, shape: &Shape) -> Result<()> {
+    if accepts(kind, shape) {
+        Ok(())
+    } else {
+        Err(invalid(
+            path,
+            format!("expected {}, found {}", expected(kind), found(shape)),
+        ))
+    }
+}
+
+/// Splits a JSON pointer into unescaped reference tokens.
+fn tokens(path: &str) -> Result<Vec<String>> {
+    if path.is_empty() {
+        return Ok(Vec::new());
+    }
+    let rest = path
+        .strip_prefix('/')
+        .ok_or_else(|| invalid(path, "a JSON pointer starts with '/'"))?;
+    Ok(rest
+        .split('/')
+        .map(|token| token.replace("~1", "/").replace("~0", "~"))
+        .collect())
+}
+
+/// Parses an array index token, which must be below `end`.
+fn index(token: &str, end: usize) -> Option<usize> {
+    if token.len() > 1 && token.starts_with('0') {
+        return None;
+    }
+    token.parse().ok().filter(|&i| i < end)
+}
+
+/// Compares JSON values, treating numbers as equal by value.
+fn equal(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Number(x), Value::Number(y)) => match (x.as_i64(), y.as_i64()) {
+            (Some(x), Some(y)) => x == y,
+            _ => x
+                .as_u64()
+                .zip(y.as_u64())
+                .map_or_else(|| x.as_f64() == y.as_f64(), |(x, y)| x == y),
+        },
+        (Value::Array(x), Value::Array(y)) => {
+            x.len() == y.len() && x.iter().zip(y).all(|(x, y)| equal(x, y))
+        }
+        (Value::Object(x), Value::Object(y)) => {
+            x.len() == y.len() && x.iter().all(|(k, v)| y.get(k).is_some_and(|w| equal(v, w)))
+        }
+        _ => a == b,
+    }
+}
+
+fn entries_mut(record: &mut Record) -> Option<&mut Vec<ObjectValue>> {
+    match record {
+        Record::BinaryArray(a) => Some(&mut a.element_values),
+        Record::ArraySingleObject(a) => Some(&mut a.element_values),
+        Record::ArraySingleString(a) => Some(&mut a.element_values),
+        _ => None,
+    }
+}
+
+/// Returns the number of elements of an array, counting null runs.
+fn array_len(record: &Record) -> usize {
+    record
+        .child_values()
+        .iter()
+        .map(|value| match value {
+            ObjectValue::Record(r) => null_count(r).unwrap_or(1),
+            ObjectValue::Primitive(_) => 1,
+        })
+        .sum()
+}
+
+fn collect_ids(record: &Record, out: &mut Vec<i32>) {
+    if let Some(id) = record.object_id() {
+        out.push(id);
+    }
+    for value in record.child_values() {
+        if let ObjectValue::Record(r) = value {
+            collect_ids(r, out);
+        }
+    }
+}
+
+fn check_links(record: &Record, f: &mut impl FnMut(&Record)) {
+    f(record);
+    for value in record.child_values() {
+        if let ObjectValue::Record(r) = value {
+            check_links(r, f);
+        }
+    }
+}
+
+/// Reports errors reading new values against the patched path.
+fn reading(error: Error) -> Error {
+    match error {
+        Error::Interleaved { pointer, message } => Error::Patch {
+            path: pointer,
+            message,
+        },
+        error => error,
+    }
+}
+
+fn invalid(path: &str, message: impl Into<String>) -> Error {
+    Error::Patch {
+        path: path.to_string(),
+        message: message.into(),
+    }
+}
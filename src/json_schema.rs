@@ -0,0 +1,426 @@
+// nrbf-parser - A high-performance MS-NRBF binary parser and encoder.
+// Copyright (C) 2026  driedpampas@proton.me
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! JSON Schema (draft 2020-12) generation for interleaved and plain JSON.
+//!
+//! [`json_schema`] describes every class defined in a message under
+//! `$defs`, keyed by class name, with one property per member typed from
+//! its `MemberTypeInfo`. Members without type info accept any value.
+
+use crate::decoder::ClassInfoWithTypes;
+use crate::interleaved::type_tag;
+use crate::records::*;
+use serde_json::{Map, Value, json};
+
+/// The JSON layout a schema describes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JsonFormat {
+    /// The output of [`to_interleaved`](crate::interleaved::to_interleaved).
+    Interleaved,
+    /// The output of [`to_plain`](crate::plain::to_plain).
+    Plain,
+}
+
+const DIALECT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+/// `$defs` keys of the helper definitions. Class names are kept as they are,
+/// so these use a prefix no .NET type name has.
+const REFERENCE: &str = "nrbf.Reference";
+const MEMBER_REFERENCE: &str = "nrbf.MemberReference";
+const OBJECT_NULL: &str = "nrbf.ObjectNull";
+const OBJECT_STRING: &str = "nrbf.BinaryObjectString";
+const RECORD: &str = "nrbf.Record";
+
+const CLASS_RECORDS: [&str; 5] = [
+    "ClassWithMembersAndTypes",
+    "SystemClassWithMembersAndTypes",
+    "SystemClassWithMembers",
+    "ClassWithMembers",
+    "ClassWithId",
+];
+
+const OTHER_RECORDS: [&str; 13] = [
+    "SerializationHeader",
+    "BinaryLibrary",
+    "BinaryObjectString",
+    "BinaryArray",
+    "ArraySingleObject",
+    "ArraySinglePrimitive",
+    "ArraySingleString",
+    "MemberPrimitiveTyped",
+    "MemberReference",
+    "ObjectNull",
+    "ObjectNullMultiple",
+    "ObjectNullMultiple256",
+    "MessageEnd",
+];
+
+/// Generates a JSON Schema for the interleaved or plain JSON of `records`.
+///
+/// When several classes share a name, the first definition is used.
+pub fn json_schema(records: &[Record], format: JsonFormat) -> Value {
+    let mut classes: Vec<ClassInfoWithTypes> = Vec::new();
+    for record in records {
+        collect_classes(record, &mut classes);
+    }
+    let generator = Generator {
+        format,
+        classes: &classes,
+    };
+
+    let mut defs = Map::new();
+    for class in &classes {
+        defs.insert(class.class_info.name.clone(), generator.class_schema(class));
+    }
+    let root = match format {
+        JsonFormat::Interleaved => {
+            defs.insert(
+                MEMBER_REFERENCE.to_string(),
+                record_schema(
+                    "MemberReference",
+                    &[("id_ref", json!({ "type": "integer" }))],
+                ),
+            );
+            defs.insert(OBJECT_NULL.to_string(), record_schema("ObjectNull", &[]));
+            defs.insert(
+                OBJECT_STRING.to_string(),
+                record_schema(
+                    "BinaryObjectString",
+                    &[
+                        ("object_id", json!({ "type": "integer" })),
+                        ("value", json!({ "type": "string" })),
+                    ],
+                ),
+            );
+            defs.insert(
+                RECORD.to_string(),
+                json!({
+                    "anyOf": [
+                        {
+                            "type": "object",
+                            "properties": { "$record": { "enum": OTHER_RECORDS } },
+                            "required": ["$record"],
+                        },
+                        {
+                            "description": "A class record whose metadata is unknown, \
+                                with its members in order.",
+                            "type": "object",
+                            "properties": {
+                                "$record": { "enum": CLASS_RECORDS },
+                                "$values": { "type": "array" },
+                            },
+                            "required": ["$record", "$values"],
+                        },
+                    ],
+                }),
+            );
+            let mut items: Vec<Value> = classes
+                .iter()
+                .map(|class| def_ref(&class.class_info.name))
+                .collect();
+            items.push(def_ref(RECORD));
+            json!({ "type": "array", "items": { "anyOf": items } })
+        }
+        JsonFormat::Plain => {
+            defs.insert(
+                REFERENCE.to_string(),
+                json!({
+                    "description": "An object already being rendered, by object id.",
+                    "type": "object",
+                    "properties": { "$ref": { "type": "integer" } },
+                    "required": ["$ref"],
+                    "additionalProperties": false,
+                }),
+            );
+            generator.plain_root(records)
+        }
+    };
+
+    let mut schema = Map::new();
+    schema.insert("$schema".to_string(), json!(DIALECT));
+    if let Value::Object(root) = root {
+        schema.extend(root);
+    }
+    schema.insert("$defs".to_string(), Value::Object(defs));
+    Value::Object(schema)
+}
+
+fn collect_classes(record: &Record, classes: &mut Vec<ClassInfoWithTypes>) {
+    if let Some(class) = ClassInfoWithTypes::from_record(record)
+        && !classes
+            .iter()
+            .any(|c| c.class_info.name == class.class_info.name)
+    {
+        classes.push(class);
+    }
+    for value in record.child_values() {
+        if let ObjectValue::Record(r) = value {
+            collect_classes(r, classes);
+        }
+    }
+}
+
+struct Generator<'a> {
+    format: JsonFormat,
+    classes: &'a [ClassInfoWithTypes],
+}
+
+impl Generator<'_> {
+    fn class_schema(&self, class: &ClassInfoWithTypes) -> Value {
+        let info = &class.class_info;
+        let mut properties = Map::new();
+        let mut required = Vec::new();
+        if self.format == JsonFormat::Interleaved {
+            properties.insert("$type".to_string(), json!({ "const": info.name }));
+            properties.insert("$id".to_string(), json!({ "type": "integer" }));
+            properties.insert("$record".to_string(), json!({ "enum": CLASS_RECORDS }));
+            properties.insert("$metadata_id".to_string(), json!({ "type": "integer" }));
+            properties.insert("$member_type_info".to_string(), json!({ "type": "object" }));
+            properties.insert("library_id".to_string(), json!({ "type": "integer" }));
+            required.extend(["$type", "$id", "$record"].map(Value::from));
+        }
+        for (i, name) in info.member_names.iter().enumerate() {
+            let slot = class.member_type_info.as_ref().and_then(|mti| {
+                Some((mti.binary_type_enums.get(i)?, mti.additional_infos.get(i)?))
+            });
+            let schema = match slot {
+                Some((binary_type, additional)) => self.member_schema(binary_type, additional),
+                None => json!({}),
+            };
+            properties.insert(name.clone(), schema);
+            required.push(Value::from(name.as_str()));
+        }
+        json!({
+            "title": info.name,
+            "type": "object",
+            "properties": properties,
+            "required": required,
+            "additionalProperties": false,
+        })
+    }
+
+    fn member_schema(&self, binary_type: &BinaryType, additional: &AdditionalTypeInfo) -> Value {
+        let mut schema = match (binary_type, additional) {
+            (BinaryType::Primitive, AdditionalTypeInfo::Primitive(t)) => {
+                return self.primitive_schema(*t);
+            }
+            (BinaryType::String, _) => self.string_schema(),
+            (BinaryType::Class, AdditionalTypeInfo::Class(c)) => self.class_ref(&c.type_name),
+            (BinaryType::SystemClass, AdditionalTypeInfo::SystemClass(name)) => {
+                self.class_ref(name)
+            }
+            (BinaryType::PrimitiveArray, AdditionalTypeInfo::Primitive(t)) => {
+                self.array_schema(self.primitive_schema(*t))
+            }
+            (BinaryType::StringArray, _) => {
+                let mut item = self.string_schema();
+                nullable(&mut item);
+                self.array_schema(item)
+            }
+            (BinaryType::ObjectArray, _) => self.array_schema(json!({})),
+            _ => return json!({}),
+        };
+        nullable(&mut schema);
+        schema
+    }
+
+    fn primitive_schema(&self, t: PrimitiveType) -> Value {
+        let mut schema = match t {
+            PrimitiveType::Boolean => json!({ "type": "boolean" }),
+            PrimitiveType::Byte => integer(0, u8::MAX),
+            PrimitiveType::SByte => integer(i8::MIN, i8::MAX),
+            PrimitiveType::Int16 => integer(i16::MIN, i16::MAX),
+            PrimitiveType::UInt16 => integer(0, u16::MAX),
+            PrimitiveType::Int32 => integer(i32::MIN, i32::MAX),
+            PrimitiveType::UInt32 => integer(0, u32::MAX),
+            PrimitiveType::Int64 | PrimitiveType::TimeSpan => json!({
+                "anyOf": [integer(i64::MIN, i64::MAX), { "type": "string", "pattern": "^-?[0-9]+$" }],
+            }),
+            PrimitiveType::UInt64 | PrimitiveType::DateTime => json!({
+                "anyOf": [integer(0, u64::MAX), { "type": "string", "pattern": "^[0-9]+$" }],
+            }),
+            // Non-finite values are written as text, or as `null` by the
+            // interleaved writer without lossless numbers.
+            PrimitiveType::Single | PrimitiveType::Double => match self.format {
+                JsonFormat::Interleaved => json!({ "type": ["number", "string", "null"] }),
+                JsonFormat::Plain => json!({ "type": ["number", "string"] }),
+            },
+            PrimitiveType::Char => json!({ "type": "string", "minLength": 1, "maxLength": 1 }),
+            PrimitiveType::Decimal => json!({ "type": "string", "pattern": "^[0-9a-fA-F]{32}$" }),
+            PrimitiveType::String => json!({ "type": "string" }),
+            PrimitiveType::Null => json!({ "type": "null" }),
+        };
+        let tagged = matches!(
+            t,
+            PrimitiveType::Int64
+                | PrimitiveType::TimeSpan
+                | PrimitiveType::UInt64
+                | PrimitiveType::DateTime
+                | PrimitiveType::Single
+                | PrimitiveType::Double
+        );
+        if self.format == JsonFormat::Interleaved && tagged {
+            let tag = type_tag(t);
+            schema = json!({
+                "anyOf": [
+                    schema,
+                    {
+                        "type": "object",
+                        "properties": { tag: { "type": "string" } },
+                        "required": [tag],
+                        "additionalProperties": false,
+                    },
+                ],
+            });
+        }
+        if let Value::Object(map) = &mut schema {
+            map.insert("description".to_string(), json!(format!("{:?}", t)));
+        }
+        schema
+    }
+
+    fn string_schema(&self) -> Value {
+        match self.format {
+            JsonFormat::Interleaved => json!({
+                "anyOf": [def_ref(OBJECT_STRING), def_ref(MEMBER_REFERENCE)],
+            }),
+            JsonFormat::Plain => json!({ "type": "string" }),
+        }
+    }
+
+    fn class_ref(&self, name: &str) -> Value {
+        let known = self.classes.iter().any(|c| c.class_info.name == name);
+        let target = match (self.format, known) {
+            (_, true) => def_ref(name),
+            (JsonFormat::Interleaved, false) => def_ref(RECORD),
+            (JsonFormat::Plain, false) => json!({ "type": "object" }),
+        };
+        let mut schema = self.referenced(target);
+        if let Value::Object(map) = &mut schema {
+            map.insert("description".to_string(), json!(name));
+        }
+        schema
+    }
+
+    fn array_schema(&self, items: Value) -> Value {
+        let array = match self.format {
+            JsonFormat::Interleaved => json!({
+                "type": "object",
+                "properties": {
+                    "$record": {
+                        "enum": [
+                            "BinaryArray",
+                            "ArraySingleObject",
+                            "ArraySinglePrimitive",
+                            "ArraySingleString",
+                        ],
+                    },
+                    "$values": { "type": "array", "items": items },
+                },
+                "required": ["$record", "$values"],
+            }),
+            JsonFormat::Plain => json!({ "type": "array", "items": items }),
+        };
+        self.referenced(array)
+    }
+
+    /// Allows a reference to another object in place of `schema`.
+    fn referenced(&self, schema: Value) -> Value {
+        let reference = match self.format {
+            JsonFormat::Interleaved => def_ref(MEMBER_REFERENCE),
+            JsonFormat::Plain => def_ref(REFERENCE),
+        };
+        json!({ "anyOf": [schema, reference] })
+    }
+
+    /// Describes the root object of the plain JSON.
+    fn plain_root(&self, records: &[Record]) -> Value {
+        let root_id = records.iter().find_map(|record| match record {
+            Record::SerializationHeader(h) => Some(h.root_id),
+            _ => None,
+        });
+        let root = records
+            .iter()
+            .find(|record| root_id.is_some() && record.object_id() == root_id);
+        match root {
+            Some(Record::ClassWithId(c)) => self
+                .classes
+                .iter()
+                .find(|class| class.class_info.object_id == c.metadata_id)
+                .map_or_else(|| json!({}), |class| def_ref(&class.class_info.name)),
+            Some(record) if record.class_info().is_some() => record
+                .class_info()
+                .map_or_else(|| json!({}), |c| def_ref(&c.name)),
+            Some(Record::BinaryObjectString { .. }) => json!({ "type": "string" }),
+            Some(Record::ArraySinglePrimitive(a)) => {
+                json!({ "type": "array", "items": self.primitive_schema(a.primitive_type_enum) })
+            }
+            Some(
+                Record::BinaryArray(_)
+                | Record::ArraySingleObject(_)
+                | Record::ArraySingleString(_),
+            ) => json!({ "type": "array" }),
+            _ => json!({}),
+        }
+    }
+}
+
+fn record_schema(record: &str, fields: &[(&str, Value)]) -> Value {
+    let mut properties = Map::new();
+    properties.insert("$record".to_string(), json!({ "const": record }));
+    let mut required = vec![json!("$record")];
+    for (name, schema) in fields {
+        properties.insert(name.to_string(), schema.clone());
+        required.push(json!(name));
+    }
+    json!({
+        "type": "object",
+        "properties": properties,
+        "required": required,
+        "additionalProperties": false,
+    })
+}
+
+fn integer(min: impl Into<Value>, max: impl Into<Value>) -> Value {
+    json!({ "type": "integer", "minimum": min.into(), "maximum": max.into() })
+}
+
+/// Also accepts `null` (or, in interleaved JSON, an `ObjectNull` record).
+fn nullable(schema: &mut Value) {
+    if let Some(Value::Array(any_of)) = schema.get_mut("anyOf") {
+        any_of.push(json!({ "type": "null" }));
+        if any_of.contains(&def_ref(MEMBER_REFERENCE)) {
+            any_of.push(def_ref(OBJECT_NULL));
+        }
+    } else {
+        *schema = json!({ "anyOf": [schema.take(), { "type": "null" }] });
+    }
+}
+
+/// Returns `{"$ref": "#/$defs/<name>"}`, escaping the name for use in a JSON
+/// pointer and a URI fragment.
+fn def_ref(name: &str) -> Value {
+    let token = name.replace('~', "~0").replace('/', "~1");
+    let mut fragment = String::from("#/$defs/");
+    for byte in token.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~!$&'()*+,;=:@/".contains(&byte) {
+            fragment.push(byte as char);
+        } else {
+            fragment.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    json!({ "$ref": fragment })
+}
@@ -16,15 +16,38 @@
 
 //! A high-performance MS-NRBF binary parser and encoder.
 
+#[cfg(feature = "tokio")]
+pub mod async_io;
+pub mod builder;
+pub mod canonical;
+pub mod catalogue;
+pub mod codegen;
 pub mod decoder;
+pub mod dedup;
+pub mod diff;
 pub mod encoder;
 pub mod error;
+pub mod extract;
 pub mod interleaved;
+pub mod json_patch;
+pub mod json_schema;
+pub mod lossless;
+pub mod merge;
+pub mod patch;
+pub mod plain;
+pub mod push;
+pub mod query;
 pub mod records;
+pub mod renumber;
+pub mod validate;
 
+#[cfg(feature = "tokio")]
+pub use async_io::{AsyncDecoder, AsyncEncoder};
+pub use builder::StreamBuilder;
 pub use decoder::Decoder;
 pub use encoder::Encoder;
 pub use error::Error;
+pub use push::PushDecoder;
 pub use records::Record;
 
 /// Convenience function to parse an NRBF stream from a reader.
@@ -38,3 +61,13 @@ pub fn parse<R: std::io::Read>(reader: R) -> impl Iterator<Item = error::Result<
         Err(e) => Some(Err(e)),
     })
 }
+
+/// Convenience function to parse every message in an NRBF stream from a reader.
+///
+/// Returns an iterator of messages, each running from its header through `MessageEnd`.
+pub fn parse_messages<R: std::io::Read>(
+    reader: R,
+) -> impl Iterator<Item = error::Result<Vec<Record>>> {
+    let mut decoder = Decoder::new(reader);
+    std::iter::from_fn(move || decoder.decode_message().transpose())
+}
@@ -0,0 +1,380 @@
+// nrbf-parser - A high-performance MS-NRBF binary parser and encoder.
+// Copyright (C) 2026  driedpampas@proton.me
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Lossless serde encoding of records.
+//!
+//! JSON numbers cannot carry NaN, infinities or integers beyond 2^53 - 1
+//! exactly. Records wrapped in [`Lossless`] write such `PrimitiveValue`s as
+//! strings, e.g. `{"Double": "inf"}` or `{"Int64": "9007199254740993"}`,
+//! with NaNs as their bit pattern, and read both numbers and strings back.
+//! Everything else is encoded as by the records' own `Serialize` and
+//! `Deserialize`, which only use numbers.
+
+use crate::records::*;
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Records encoded with lossless numbers.
+///
+/// ```no_run
+/// use nrbf_parser::lossless::Lossless;
+/// # let records: Vec<nrbf_parser::Record> = Vec::new();
+/// let json = serde_json::to_string(&Lossless(&records)).unwrap();
+/// let Lossless(records) = serde_json::from_str::<Lossless<Vec<_>>>(&json).unwrap();
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lossless<T>(pub T);
+
+impl<T: AsRef<[Record]>> Serialize for Lossless<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.0.as_ref().iter().map(RecordRef))
+    }
+}
+
+impl<'de> Deserialize<'de> for Lossless<Vec<Record>> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let records = Vec::<RecordBuf>::deserialize(deserializer)?;
+        Ok(Lossless(records.into_iter().map(|r| r.0).collect()))
+    }
+}
+
+struct RecordRef<'a>(&'a Record);
+
+impl Serialize for RecordRef<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        RecordDef::serialize(self.0, serializer)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(transparent)]
+struct RecordBuf(#[serde(with = "RecordDef")] Record);
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "Record", rename = "Record")]
+enum RecordDef {
+    SerializationHeader(SerializationHeader),
+    BinaryLibrary(BinaryLibrary),
+    ClassWithMembersAndTypes(
+        #[serde(with = "ClassWithMembersAndTypesDef")] ClassWithMembersAndTypes,
+    ),
+    SystemClassWithMembersAndTypes(
+        #[serde(with = "SystemClassWithMembersAndTypesDef")] SystemClassWithMembersAndTypes,
+    ),
+    SystemClassWithMembers(#[serde(with = "SystemClassWithMembersDef")] SystemClassWithMembers),
+    ClassWithMembers(#[serde(with = "ClassWithMembersDef")] ClassWithMembers),
+    ClassWithId(#[serde(with = "ClassWithIdDef")] ClassWithId),
+    BinaryObjectString {
+        object_id: i32,
+        value: String,
+    },
+    BinaryArray(#[serde(with = "BinaryArrayDef")] BinaryArray),
+    ArraySingleObject(#[serde(with = "ArraySingleObjectDef")] ArraySingleObject),
+    ArraySinglePrimitive(#[serde(with = "ArraySinglePrimitiveDef")] ArraySinglePrimitive),
+    ArraySingleString(#[serde(with = "ArraySingleStringDef")] ArraySingleString),
+    MemberPrimitiveTyped {
+        primitive_type_enum: PrimitiveType,
+        #[serde(with = "primitive")]
+        value: PrimitiveValue,
+    },
+    MemberReference {
+        id_ref: i32,
+    },
+    ObjectNull,
+    ObjectNullMultiple(ObjectNullMultiple),
+    ObjectNullMultiple256(ObjectNullMultiple256),
+    MessageEnd,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(
+    remote = "ClassWithMembersAndTypes",
+    rename = "ClassWithMembersAndTypes"
+)]
+struct ClassWithMembersAndTypesDef {
+    class_info: ClassInfo,
+    member_type_info: MemberTypeInfo,
+    library_id: i32,
+    #[serde(with = "object_values")]
+    member_values: Vec<ObjectValue>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(
+    remote = "SystemClassWithMembersAndTypes",
+    rename = "SystemClassWithMembersAndTypes"
+)]
+struct SystemClassWithMembersAndTypesDef {
+    class_info: ClassInfo,
+    member_type_info: MemberTypeInfo,
+    #[serde(with = "object_values")]
+    member_values: Vec<ObjectValue>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "SystemClassWithMembers", rename = "SystemClassWithMembers")]
+struct SystemClassWithMembersDef {
+    class_info: ClassInfo,
+    #[serde(with = "object_values")]
+    member_values: Vec<ObjectValue>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "ClassWithMembers", rename = "ClassWithMembers")]
+struct ClassWithMembersDef {
+    class_info: ClassInfo,
+    library_id: i32,
+    #[serde(with = "object_values")]
+    member_values: Vec<ObjectValue>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "ClassWithId", rename = "ClassWithId")]
+struct ClassWithIdDef {
+    object_id: i32,
+    metadata_id: i32,
+    #[serde(with = "object_values")]
+    member_values: Vec<ObjectValue>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "BinaryArray", rename = "BinaryArray")]
+struct BinaryArrayDef {
+    object_id: i32,
+    binary_array_type_enum: u8,
+    rank: i32,
+    lengths: Vec<i32>,
+    lower_bounds: Option<Vec<i32>>,
+    type_enum: BinaryType,
+    additional_type_info: AdditionalTypeInfo,
+    #[serde(with = "object_values")]
+    element_values: Vec<ObjectValue>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "ArraySingleObject", rename = "ArraySingleObject")]
+struct ArraySingleObjectDef {
+    object_id: i32,
+    length: i32,
+    #[serde(with = "object_values")]
+    element_values: Vec<ObjectValue>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "ArraySinglePrimitive", rename = "ArraySinglePrimitive")]
+struct ArraySinglePrimitiveDef {
+    object_id: i32,
+    length: i32,
+    primitive_type_enum: PrimitiveType,
+    #[serde(with = "primitive_values")]
+    element_values: Vec<PrimitiveValue>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "ArraySingleString", rename = "ArraySingleString")]
+struct ArraySingleStringDef {
+    object_id: i32,
+    length: i32,
+    #[serde(with = "object_values")]
+    element_values: Vec<ObjectValue>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "ObjectValue", rename = "ObjectValue")]
+enum ObjectValueDef {
+    Primitive(#[serde(with = "primitive")] PrimitiveValue),
+    Record(#[serde(with = "boxed_record")] Box<Record>),
+}
+
+mod boxed_record {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(
+        record: &Record,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        RecordDef::serialize(record, serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Box<Record>, D::Error> {
+        RecordDef::deserialize(deserializer).map(Box::new)
+    }
+}
+
+mod object_values {
+    use super::*;
+
+    struct ValueRef<'a>(&'a ObjectValue);
+
+    impl Serialize for ValueRef<'_> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+            ObjectValueDef::serialize(self.0, serializer)
+        }
+    }
+
+    #[derive(Deserialize)]
+    #[serde(transparent)]
+    struct ValueBuf(#[serde(with = "ObjectValueDef")] ObjectValue);
+
+    pub fn serialize<S: Serializer>(
+        values: &[ObjectValue],
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_seq(values.iter().map(ValueRef))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Vec<ObjectValue>, D::Error> {
+        let values = Vec::<ValueBuf>::deserialize(deserializer)?;
+        Ok(values.into_iter().map(|v| v.0).collect())
+    }
+}
+
+mod primitive_values {
+    use super::*;
+
+    struct ValueRef<'a>(&'a PrimitiveValue);
+
+    impl Serialize for ValueRef<'_> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+            primitive::serialize(self.0, serializer)
+        }
+    }
+
+    #[derive(Deserialize)]
+    #[serde(transparent)]
+    struct ValueBuf(#[serde(with = "primitive")] PrimitiveValue);
+
+    pub fn serialize<S: Serializer>(
+        values: &[PrimitiveValue],
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_seq(values.iter().map(ValueRef))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Vec<PrimitiveValue>, D::Error> {
+        let values = Vec::<ValueBuf>::deserialize(deserializer)?;
+        Ok(values.into_iter().map(|v| v.0).collect())
+    }
+}
+
+mod primitive {
+    use super::*;
+
+    /// Writes values JSON numbers cannot carry as text, in the variant the
+    /// derived `Serialize` of [`PrimitiveValue`] would use.
+    pub fn serialize<S: Serializer>(
+        value: &PrimitiveValue,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        let (index, variant) = match value {
+            PrimitiveValue::Double(_) => (4, "Double"),
+            PrimitiveValue::Int64(_) => (7, "Int64"),
+            PrimitiveValue::Single(_) => (9, "Single"),
+            PrimitiveValue::TimeSpan(_) => (10, "TimeSpan"),
+            PrimitiveValue::DateTime(_) => (11, "DateTime"),
+            PrimitiveValue::UInt64(_) => (14, "UInt64"),
+            _ => return value.serialize(serializer),
+        };
+        if !value.needs_text() {
+            return value.serialize(serializer);
+        }
+        serializer.serialize_newtype_variant("PrimitiveValue", index, variant, &value.to_text())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<PrimitiveValue, D::Error> {
+        PrimitiveRepr::deserialize(deserializer)?
+            .into_value()
+            .map_err(D::Error::custom)
+    }
+}
+
+/// The lossless form of [`PrimitiveValue`], accepting text for the variants
+/// JSON numbers cannot always carry.
+#[derive(Deserialize)]
+#[serde(rename = "PrimitiveValue")]
+enum PrimitiveRepr {
+    Boolean(bool),
+    Byte(u8),
+    Char(char),
+    Decimal(String),
+    Double(NumberOrText<f64>),
+    Int16(i16),
+    Int32(i32),
+    Int64(NumberOrText<i64>),
+    SByte(i8),
+    Single(NumberOrText<f32>),
+    TimeSpan(NumberOrText<i64>),
+    DateTime(NumberOrText<u64>),
+    UInt16(u16),
+    UInt32(u32),
+    UInt64(NumberOrText<u64>),
+    String(String),
+    Null,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum NumberOrText<T> {
+    Number(T),
+    Text(String),
+}
+
+impl PrimitiveRepr {
+    fn into_value(self) -> std::result::Result<PrimitiveValue, String> {
+        fn number<T>(
+            value: NumberOrText<T>,
+            t: PrimitiveType,
+            wrap: fn(T) -> PrimitiveValue,
+        ) -> std::result::Result<PrimitiveValue, String> {
+            match value {
+                NumberOrText::Number(n) => Ok(wrap(n)),
+                NumberOrText::Text(text) => PrimitiveValue::from_text(t, &text)
+                    .ok_or_else(|| format!("Invalid {:?} value: {}", t, text)),
+            }
+        }
+        Ok(match self {
+            PrimitiveRepr::Boolean(v) => PrimitiveValue::Boolean(v),
+            PrimitiveRepr::Byte(v) => PrimitiveValue::Byte(v),
+            PrimitiveRepr::Char(v) => PrimitiveValue::Char(v),
+            PrimitiveRepr::Decimal(v) => PrimitiveValue::Decimal(v),
+            PrimitiveRepr::Double(v) => number(v, PrimitiveType::Double, PrimitiveValue::Double)?,
+            PrimitiveRepr::Int16(v) => PrimitiveValue::Int16(v),
+            PrimitiveRepr::Int32(v) => PrimitiveValue::Int32(v),
+            PrimitiveRepr::Int64(v) => number(v, PrimitiveType::Int64, PrimitiveValue::Int64)?,
+            PrimitiveRepr::SByte(v) => PrimitiveValue::SByte(v),
+            PrimitiveRepr::Single(v) => number(v, PrimitiveType::Single, PrimitiveValue::Single)?,
+            PrimitiveRepr::TimeSpan(v) => {
+                number(v, PrimitiveType::TimeSpan, PrimitiveValue::TimeSpan)?
+            }
+            PrimitiveRepr::DateTime(v) => {
+                number(v, PrimitiveType::DateTime, PrimitiveValue::DateTime)?
+            }
+            PrimitiveRepr::UInt16(v) => PrimitiveValue::UInt16(v),
+            PrimitiveRepr::UInt32(v) => PrimitiveValue::UInt32(v),
+            PrimitiveRepr::UInt64(v) => number(v, PrimitiveType::UInt64, PrimitiveValue::UInt64)?,
+            PrimitiveRepr::String(v) => PrimitiveValue::String(v),
+            PrimitiveRepr::Null => PrimitiveValue::Null,
+        })
+    }
+}
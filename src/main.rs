@@ -0,0 +1,144 @@
+// nrbf-parser - A high-performance MS-NRBF binary parser and encoder.
+// Copyright (C) 2026  driedpampas@proton.me
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Command-line tools for MS-NRBF files.
+
+use nrbf_parser::diff::{ChangeKind, diff};
+use nrbf_parser::extract::extract;
+use nrbf_parser::query::Query;
+use nrbf_parser::{Decoder, Encoder, Record};
+use serde_json::json;
+use std::env;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, IsTerminal, Write};
+use std::process;
+
+const USAGE: &str = "\
+Usage: nrbf-parser <command> [args]
+
+Commands:
+  query <file> <query>    Print the values a path query selects, one per line
+  diff <old> <new>        Print the differences between two files
+    --json                  one JSON object per change
+    --no-color              no ANSI colors, also set by NO_COLOR
+  extract <file> <query> <output>
+                          Write the first object a query selects, and the
+                          objects it refers to, as a file of its own";
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let result = match args.first().map(String::as_str) {
+        Some("query") => query(&args[1..]),
+        Some("diff") => compare(&args[1..]),
+        Some("extract") => subgraph(&args[1..]),
+        Some("-h" | "--help" | "help") => {
+            println!("{}", USAGE);
+            return;
+        }
+        _ => usage(),
+    };
+    if let Err(e) = result {
+        eprintln!("error: {}", e);
+        process::exit(1);
+    }
+}
+
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(2);
+}
+
+fn query(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let [path, query] = args else { usage() };
+    let query: Query = query.parse()?;
+    let records = read(path)?;
+    for m in query.evaluate(&records) {
+        println!("{} = {}", m.path(), m.node);
+    }
+    Ok(())
+}
+
+fn compare(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let (flags, files): (Vec<&String>, Vec<&String>) =
+        args.iter().partition(|arg| arg.starts_with("--"));
+    let mut json = false;
+    let mut color = std::io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none();
+    for flag in flags {
+        match flag.as_str() {
+            "--json" => json = true,
+            "--no-color" => color = false,
+            _ => usage(),
+        }
+    }
+    let [old, new] = files.as_slice() else {
+        usage()
+    };
+    let old = read(old)?;
+    let new = read(new)?;
+    for change in diff(&old, &new) {
+        if json {
+            let (kind, old, new) = match change.kind {
+                ChangeKind::Added(node) => ("added", None, Some(node)),
+                ChangeKind::Removed(node) => ("removed", Some(node), None),
+                ChangeKind::Changed(old, new) => ("changed", Some(old), Some(new)),
+                ChangeKind::TypeChanged(old, new) => ("type_changed", Some(old), Some(new)),
+            };
+            let mut line = json!({ "path": change.path, "change": kind });
+            if let Some(old) = old {
+                line["old"] = json!(old.to_string());
+            }
+            if let Some(new) = new {
+                line["new"] = json!(new.to_string());
+            }
+            println!("{}", line);
+        } else if color {
+            let code = match change.kind {
+                ChangeKind::Added(_) => 32,
+                ChangeKind::Removed(_) => 31,
+                ChangeKind::Changed(..) => 33,
+                ChangeKind::TypeChanged(..) => 35,
+            };
+            println!("\x1b[{}m{}\x1b[0m", code, change);
+        } else {
+            println!("{}", change);
+        }
+    }
+    Ok(())
+}
+
+fn subgraph(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let [path, query, output] = args else { usage() };
+    let query: Query = query.parse()?;
+    let records = read(path)?;
+    let id = query
+        .evaluate(&records)
+        .iter()
+        .find_map(|m| m.node.record()?.object_id())
+        .ok_or("the query selects no object")?;
+    let mut writer = BufWriter::new(File::create(output)?);
+    let mut encoder = Encoder::new(&mut writer);
+    for record in extract(&records, id)? {
+        encoder.encode(&record)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Reads the first message of a file.
+fn read(path: &str) -> Result<Vec<Record>, Box<dyn std::error::Error>> {
+    let mut decoder = Decoder::new(BufReader::new(File::open(path)?));
+    Ok(decoder.decode_message()?.unwrap_or_default())
+}
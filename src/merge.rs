@@ -0,0 +1,687 @@
+// nrbf-parser - A high-performance MS-NRBF binary parser and encoder.
+// Copyright (C) 2026  driedpampas@proton.me
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Three-way merges of messages.
+//!
+//! The base, ours and theirs are walked together from their roots, pairing
+//! values by graph position as the [diff](crate::diff) does. Each member
+//! and array element is merged on its own: a value only one side changed
+//! takes that side, and a value both sides changed differently is a
+//! [`Conflict`] for the [`Policy`] to settle. Arrays whose length only one
+//! side changed take that side's added or removed elements, unless the
+//! other side changed an element that was removed.
+//!
+//! An object is identified by the path it is first reached at, so a member
+//! that now refers to an object elsewhere in the graph is a change of that
+//! member, not of the object. Class definitions are taken from ours:
+//! members that only theirs has are not merged.
+//!
+//! The result starts from ours and is edited with a [`Patch`], so existing
+//! object ids are kept, new objects get fresh ones, and the records can be
+//! encoded directly. A value of theirs that cannot be copied into ours, such
+//! as an object holding a `BinaryArray` or referring back to itself, is left
+//! as an unresolved conflict.
+
+use crate::builder::Value;
+use crate::codegen::class_def;
+use crate::diff::type_name;
+use crate::error::{Error, Result};
+use crate::patch::Patch;
+use crate::plain::Graph;
+use crate::query::{Evaluator, Match, Node, Path, Segment, Slot};
+use crate::records::*;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+
+/// Merges the changes from `base` to `ours` and from `base` to `theirs`.
+pub fn merge<'a>(
+    base: &'a [Record],
+    ours: &'a [Record],
+    theirs: &'a [Record],
+    policy: &dyn Policy,
+) -> Result<Merge<'a>> {
+    let mut places = Places::default();
+    let mut merger = Merger {
+        base: Tree::new(base, &mut places),
+        ours: Tree::new(ours, &mut places),
+        theirs: Tree::new(theirs, &mut places),
+        policy,
+        objects: HashMap::new(),
+        steps: Vec::new(),
+        edits: Vec::new(),
+        conflicts: Vec::new(),
+    };
+    merger.objects = merger
+        .ours
+        .homes
+        .iter()
+        .map(|(id, home)| (home.place, *id))
+        .collect();
+    if let (Some(o), Some(t)) = (&merger.ours.root, &merger.theirs.root) {
+        let step = Step::Merge(merger.base.root.clone(), o.clone(), t.clone());
+        merger.run(step);
+    }
+
+    let mut patch = Patch::new(ours.to_vec());
+    for edit in &merger.edits {
+        match edit {
+            Edit::Set(path, value) => patch.replace(path, value)?,
+            Edit::Push(path, value) => patch.push(path, value)?,
+            Edit::Remove(path) => patch.remove(path)?,
+        }
+    }
+    Ok(Merge {
+        records: patch.into_records(),
+        conflicts: merger.conflicts,
+    })
+}
+
+/// The result of a merge.
+#[derive(Debug)]
+pub struct Merge<'a> {
+    /// The merged message, with unresolved conflicts left as in ours.
+    pub records: Vec<Record>,
+    /// Every value both sides changed, in graph order.
+    pub conflicts: Vec<Conflict<'a>>,
+}
+
+/// A value both sides changed differently.
+#[derive(Debug, Clone)]
+pub struct Conflict<'a> {
+    /// The path of the value from the root, e.g. `$.inventory[0].itemId`.
+    pub path: String,
+    /// The value in the base, unless the base does not have it.
+    pub base: Option<Node<'a>>,
+    pub ours: Node<'a>,
+    pub theirs: Node<'a>,
+    /// The side the policy picked, if any. The policy is not asked when
+    /// theirs cannot be copied into ours.
+    pub resolution: Option<Side>,
+}
+
+/// One side of a merge.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Ours,
+    Theirs,
+}
+
+/// Settles conflicts.
+///
+/// Closures taking a [`Conflict`] are policies too, e.g.
+/// `|c: &Conflict| PreferMax.resolve(c).or(Some(Side::Ours))`.
+pub trait Policy {
+    /// Picks a side, or `None` to leave the conflict unresolved.
+    fn resolve(&self, conflict: &Conflict) -> Option<Side>;
+}
+
+impl<F: Fn(&Conflict) -> Option<Side>> Policy for F {
+    fn resolve(&self, conflict: &Conflict) -> Option<Side> {
+        self(conflict)
+    }
+}
+
+/// Resolves every conflict with ours.
+pub struct PreferOurs;
+
+impl Policy for PreferOurs {
+    fn resolve(&self, _: &Conflict) -> Option<Side> {
+        Some(Side::Ours)
+    }
+}
+
+/// Resolves every conflict with theirs.
+pub struct PreferTheirs;
+
+impl Policy for PreferTheirs {
+    fn resolve(&self, _: &Conflict) -> Option<Side> {
+        Some(Side::Theirs)
+    }
+}
+
+/// Resolves conflicts between numbers of the same type with the larger one,
+/// ours on a tie, and leaves other conflicts unresolved.
+pub struct PreferMax;
+
+impl Policy for PreferMax {
+    fn resolve(&self, conflict: &Conflict) -> Option<Side> {
+        let (Node::Primitive(ours), Node::Primitive(theirs)) = (conflict.ours, conflict.theirs)
+        else {
+            return None;
+        };
+        if ours.primitive_type() != theirs.primitive_type() {
+            return None;
+        }
+        let ordering = match (integer(ours), integer(theirs)) {
+            (Some(o), Some(t)) => o.cmp(&t),
+            _ => float(ours)?.partial_cmp(&float(theirs)?)?,
+        };
+        Some(match ordering {
+            Ordering::Less => Side::Theirs,
+            _ => Side::Ours,
+        })
+    }
+}
+
+fn integer(value: &PrimitiveValue) -> Option<i128> {
+    Some(match value {
+        PrimitiveValue::Byte(v) => (*v).into(),
+        PrimitiveValue::SByte(v) => (*v).into(),
+        PrimitiveValue::Int16(v) => (*v).into(),
+        PrimitiveValue::UInt16(v) => (*v).into(),
+        PrimitiveValue::Int32(v) => (*v).into(),
+        PrimitiveValue::UInt32(v) => (*v).into(),
+        PrimitiveValue::Int64(v) => (*v).into(),
+        PrimitiveValue::UInt64(v) => (*v).into(),
+        _ => return None,
+    })
+}
+
+fn float(value: &PrimitiveValue) -> Option<f64> {
+    match value {
+        PrimitiveValue::Single(v) => Some((*v).into()),
+        PrimitiveValue::Double(v) => Some(*v),
+        PrimitiveValue::Decimal(v) => v.parse().ok(),
+        _ => None,
+    }
+}
+
+/// A change from theirs to make to ours.
+enum Edit {
+    /// Sets the value at the path.
+    Set(String, Value),
+    /// Appends the value to the array at the path.
+    Push(String, Value),
+    Remove(String),
+}
+
+/// Work left to do while walking the messages.
+enum Step<'a> {
+    /// Merges the values at base, ours and theirs.
+    Merge(Option<Match<'a>>, Match<'a>, Match<'a>),
+    /// Merges the length of an array whose lengths differ, once its
+    /// elements are merged.
+    Resize {
+        base: Option<Match<'a>>,
+        /// The lengths of the array in base, ours and theirs.
+        lengths: (Option<usize>, usize, usize),
+        ours: Match<'a>,
+        theirs: Match<'a>,
+        /// The elements only theirs has.
+        added: Vec<Match<'a>>,
+        /// The elements only ours has.
+        removed: Vec<Match<'a>>,
+    },
+}
+
+/// One of the messages being merged.
+struct Tree<'a> {
+    evaluator: Evaluator<'a>,
+    graph: Graph<'a>,
+    root: Option<Match<'a>>,
+    /// Where each object is first reached.
+    homes: HashMap<i32, Home>,
+    libraries: HashMap<i32, &'a str>,
+}
+
+/// Where an object is first reached.
+struct Home {
+    /// The place of the path, the same in every tree.
+    place: usize,
+    /// The member or element holding the object, `None` for the root.
+    slot: Option<Slot>,
+}
+
+/// Numbers the paths objects are first reached at, so paths in different
+/// trees can be compared without formatting them.
+#[derive(Default)]
+struct Places<'a> {
+    /// The place of each path but the root's, which is 0, keyed by the
+    /// place of its parent and its last segment.
+    places: HashMap<(usize, Segment<'a>), usize>,
+}
+
+impl<'a> Places<'a> {
+    fn child(&mut self, parent: usize, segment: Segment<'a>) -> usize {
+        let next = self.places.len() + 1;
+        *self.places.entry((parent, segment)).or_insert(next)
+    }
+}
+
+/// A value as far as merging is concerned.
+enum View<'a> {
+    Null,
+    /// A primitive or string, compared by value.
+    Leaf(Node<'a>),
+    /// An object first reached at this path.
+    Here(Node<'a>),
+    /// An object first reached at another path, by its place.
+    Elsewhere(usize),
+}
+
+impl<'a> Tree<'a> {
+    fn new(records: &'a [Record], places: &mut Places<'a>) -> Self {
+        let evaluator = Evaluator::new(records);
+        let root = evaluator.root().map(|root| Match {
+            path: Path::root(),
+            ..root
+        });
+        let mut homes = HashMap::new();
+        if let Some(root) = &root {
+            find_homes(&evaluator, root, places, &mut homes);
+        }
+        let libraries = records
+            .iter()
+            .filter_map(|record| match record {
+                Record::BinaryLibrary(l) => Some((l.library_id, l.library_name.as_str())),
+                _ => None,
+            })
+            .collect();
+        Self {
+            evaluator,
+            graph: Graph::new(records),
+            root,
+            homes,
+            libraries,
+        }
+    }
+
+    fn view(&'a self, m: &Match<'a>) -> View<'a> {
+        match m.node {
+            Node::Null | Node::Primitive(PrimitiveValue::Null) => View::Null,
+            Node::Primitive(_) | Node::Object(Record::BinaryObjectString { .. }) => {
+                View::Leaf(m.node)
+            }
+            node => match object_id(node).and_then(|id| self.homes.get(&id)) {
+                Some(home) if home.slot != m.slot => View::Elsewhere(home.place),
+                _ => View::Here(node),
+            },
+        }
+    }
+
+    /// Converts the value at `m` into a value for ours, referring to the
+    /// objects ours has at the same place where theirs refers to an object
+    /// elsewhere.
+    fn value(
+        &self,
+        m: &Match<'a>,
+        ours: &HashMap<usize, i32>,
+        copying: &mut HashSet<i32>,
+    ) -> Result<Value> {
+        let record = match m.node {
+            Node::Null | Node::Primitive(PrimitiveValue::Null) => return Ok(Value::Null),
+            Node::Primitive(p) => return Ok(Value::Primitive(p.clone())),
+            Node::Object(Record::BinaryObjectString { value, .. }) => {
+                return Ok(Value::String(value.clone()));
+            }
+            Node::Class(record, _) | Node::Object(record) => record,
+        };
+        let id = record.object_id().unwrap_or_default();
+        // Checked first, as ours has its own object where the one being
+        // copied is first reached.
+        if copying.contains(&id) {
+            return Err(invalid(&m.path(), "refers back to an object being copied"));
+        }
+        if let Some(home) = self.homes.get(&id)
+            && home.slot != m.slot
+            && let Some(target) = ours.get(&home.place)
+        {
+            return Ok(Value::Reference(*target));
+        }
+        copying.insert(id);
+        let value = match record {
+            Record::ArraySinglePrimitive(a) => {
+                Value::PrimitiveArray(a.primitive_type_enum, a.element_values.clone())
+            }
+            Record::ArraySingleObject(_) => Value::ObjectArray(
+                self.evaluator
+                    .elements(m)
+                    .unwrap_or_default()
+                    .iter()
+                    .map(|element| self.value(element, ours, copying))
+                    .collect::<Result<_>>()?,
+            ),
+            Record::ArraySingleString(_) => Value::StringArray(
+                self.evaluator
+                    .elements(m)
+                    .unwrap_or_default()
+                    .iter()
+                    .map(|element| element.node.as_str().map(str::to_string))
+                    .collect(),
+            ),
+            Record::BinaryArray(_) => {
+                return Err(invalid(&m.path(), "a BinaryArray cannot be copied"));
+            }
+            _ => {
+                let class = self
+                    .graph
+                    .class_of(record)
+                    .ok_or_else(|| invalid(&m.path(), "has no class metadata"))?;
+                let def = class_def(class, |id| {
+                    self.libraries.get(&id).map(|name| name.to_string())
+                });
+                let values = self
+                    .evaluator
+                    .children(m)
+                    .iter()
+                    .map(|member| self.value(member, ours, copying))
+                    .collect::<Result<_>>()?;
+                Value::Object(def, values)
+            }
+        };
+        copying.remove(&id);
+        Ok(value)
+    }
+}
+
+struct Merger<'a, 'p> {
+    base: Tree<'a>,
+    ours: Tree<'a>,
+    theirs: Tree<'a>,
+    policy: &'p dyn Policy,
+    /// The object ours has at each place.
+    objects: HashMap<usize, i32>,
+    /// Steps still to take, the next one last, so long reference chains do
+    /// not recurse.
+    steps: Vec<Step<'a>>,
+    edits: Vec<Edit>,
+    conflicts: Vec<Conflict<'a>>,
+}
+
+impl<'a> Merger<'a, '_> {
+    fn run(&mut self, step: Step<'a>) {
+        self.steps.push(step);
+        while let Some(step) = self.steps.pop() {
+            match step {
+                Step::Merge(base, ours, theirs) => self.merge_at(base.as_ref(), &ours, &theirs),
+                Step::Resize {
+                    base,
+                    lengths,
+                    ours,
+                    theirs,
+                    added,
+                    removed,
+                } => self.resize(base.as_ref(), lengths, &ours, &theirs, added, removed),
+            }
+        }
+    }
+
+    /// Schedules `steps` to run next, in order.
+    fn schedule(&mut self, steps: Vec<Step<'a>>) {
+        self.steps.extend(steps.into_iter().rev());
+    }
+
+    fn merge_at(&mut self, base: Option<&Match<'a>>, ours: &Match<'a>, theirs: &Match<'a>) {
+        if let (View::Here(o), View::Here(t)) = (self.ours.view(ours), self.theirs.view(theirs))
+            && type_name(&o) == type_name(&t)
+        {
+            let base = base.filter(
+                |b| matches!(self.base.view(b), View::Here(n) if type_name(&n) == type_name(&o)),
+            );
+            if matches!(o, Node::Class(..)) {
+                self.members(base, ours, theirs);
+            } else {
+                self.elements(base, ours, theirs);
+            }
+            return;
+        }
+        if self.equal((&self.ours, ours), (&self.theirs, theirs))
+            || base.is_some_and(|b| self.equal((&self.base, b), (&self.theirs, theirs)))
+        {
+            return;
+        }
+        let value = self.copy(theirs);
+        let take_theirs = match base {
+            Some(b) if value.is_some() && self.equal((&self.base, b), (&self.ours, ours)) => true,
+            _ => self.conflict(base, ours, theirs, value.is_some()) == Some(Side::Theirs),
+        };
+        if take_theirs && let Some(value) = value {
+            self.edits.push(Edit::Set(ours.path(), value));
+        }
+    }
+
+    /// Pairs members by name.
+    fn members(&mut self, base: Option<&Match<'a>>, ours: &Match<'a>, theirs: &Match<'a>) {
+        let names = self.ours.evaluator.member_names(ours);
+        let their_names = self.theirs.evaluator.member_names(theirs);
+        let ours = self.ours.evaluator.children(ours);
+        let theirs = self.theirs.evaluator.children(theirs);
+        let base = base.map(|b| {
+            (
+                self.base.evaluator.member_names(b),
+                self.base.evaluator.children(b),
+            )
+        });
+        let mut steps = Vec::new();
+        for (name, o) in names.iter().zip(&ours) {
+            let Some(t) = their_names.iter().position(|n| n == name) else {
+                continue;
+            };
+            let b = base.as_ref().and_then(|(base_names, base)| {
+                base.get(base_names.iter().position(|n| n == name)?)
+            });
+            steps.push(Step::Merge(b.cloned(), o.clone(), theirs[t].clone()));
+        }
+        self.schedule(steps);
+    }
+
+    /// Pairs elements by index, then merges the length.
+    fn elements(&mut self, base: Option<&Match<'a>>, ours: &Match<'a>, theirs: &Match<'a>) {
+        let o = self.ours.evaluator.elements(ours).unwrap_or_default();
+        let t = self.theirs.evaluator.elements(theirs).unwrap_or_default();
+        let b = base.and_then(|b| self.base.evaluator.elements(b));
+        let mut steps: Vec<Step> = o
+            .iter()
+            .zip(&t)
+            .enumerate()
+            .map(|(i, (o, t))| {
+                let b = b.as_ref().and_then(|b| b.get(i)).cloned();
+                Step::Merge(b, o.clone(), t.clone())
+            })
+            .collect();
+        if o.len() != t.len() {
+            steps.push(Step::Resize {
+                base: base.cloned(),
+                lengths: (b.as_ref().map(Vec::len), o.len(), t.len()),
+                ours: ours.clone(),
+                theirs: theirs.clone(),
+                added: t.iter().skip(o.len()).cloned().collect(),
+                removed: o.iter().skip(t.len()).cloned().collect(),
+            });
+        }
+        self.schedule(steps);
+    }
+
+    /// Takes the elements theirs added or removed if only theirs changed
+    /// the length.
+    ///
+    /// Removing an element the other side changed is a conflict.
+    fn resize(
+        &mut self,
+        base: Option<&Match<'a>>,
+        (base_len, ours_len, theirs_len): (Option<usize>, usize, usize),
+        ours: &Match<'a>,
+        theirs: &Match<'a>,
+        added: Vec<Match<'a>>,
+        removed: Vec<Match<'a>>,
+    ) {
+        let elements = base
+            .and_then(|b| self.base.evaluator.elements(b))
+            .unwrap_or_default();
+        // Whether the side that kept the elements from `from` on changed any.
+        let changed = |side: Side, kept: &[Match<'a>], from: usize| {
+            let tree = match side {
+                Side::Ours => &self.ours,
+                Side::Theirs => &self.theirs,
+            };
+            kept.iter()
+                .zip(elements.iter().skip(from))
+                .any(|(k, b)| !self.equal((&self.base, b), (tree, k)))
+        };
+        if base_len == Some(theirs_len) && !changed(Side::Theirs, &added, ours_len) {
+            return;
+        }
+        let values: Option<Vec<Value>> = added.iter().map(|a| self.copy(a)).collect();
+        let take_theirs = if base_len == Some(ours_len)
+            && values.is_some()
+            && !changed(Side::Ours, &removed, theirs_len)
+        {
+            true
+        } else {
+            self.conflict(base, ours, theirs, values.is_some()) == Some(Side::Theirs)
+        };
+        let Some(values) = values.filter(|_| take_theirs) else {
+            return;
+        };
+        for value in values {
+            self.edits.push(Edit::Push(ours.path(), value));
+        }
+        for element in removed.into_iter().rev() {
+            self.edits.push(Edit::Remove(element.path()));
+        }
+    }
+
+    /// Converts theirs into a value for ours, or returns `None` if it
+    /// cannot be copied.
+    fn copy(&self, theirs: &Match<'a>) -> Option<Value> {
+        self.theirs
+            .value(theirs, &self.objects, &mut HashSet::new())
+            .ok()
+    }
+
+    /// Records a conflict, asking the policy to settle it if theirs can be
+    /// copied into ours.
+    fn conflict(
+        &mut self,
+        base: Option<&Match<'a>>,
+        ours: &Match<'a>,
+        theirs: &Match<'a>,
+        copyable: bool,
+    ) -> Option<Side> {
+        let mut conflict = Conflict {
+            path: ours.path(),
+            base: base.map(|b| b.node),
+            ours: ours.node,
+            theirs: theirs.node,
+            resolution: None,
+        };
+        if copyable {
+            conflict.resolution = self.policy.resolve(&conflict);
+        }
+        let resolution = conflict.resolution;
+        self.conflicts.push(conflict);
+        resolution
+    }
+
+    fn equal(
+        &self,
+        (x, a): (&'a Tree<'a>, &Match<'a>),
+        (y, b): (&'a Tree<'a>, &Match<'a>),
+    ) -> bool {
+        match (x.view(a), y.view(b)) {
+            (View::Null, View::Null) => true,
+            (View::Leaf(p), View::Leaf(q)) => same_leaf(p, q),
+            (View::Elsewhere(p), View::Elsewhere(q)) => p == q,
+            (View::Here(_), View::Here(_)) => same(x, a, y, b),
+            _ => false,
+        }
+    }
+}
+
+/// Compares two values structurally, ignoring object ids.
+fn same<'a>(x: &Tree<'a>, a: &Match<'a>, y: &Tree<'a>, b: &Match<'a>) -> bool {
+    let mut seen = HashSet::new();
+    let mut pairs = vec![(a.clone(), b.clone())];
+    while let Some((a, b)) = pairs.pop() {
+        match (a.node, b.node) {
+            (Node::Class(..) | Node::Object(_), Node::Class(..) | Node::Object(_))
+                if a.node.as_str().is_none() && b.node.as_str().is_none() =>
+            {
+                if type_name(&a.node) != type_name(&b.node) {
+                    return false;
+                }
+                let ids = (
+                    object_id(a.node).unwrap_or_default(),
+                    object_id(b.node).unwrap_or_default(),
+                );
+                if !seen.insert(ids) {
+                    continue;
+                }
+                let xs = x.evaluator.children(&a);
+                let ys = y.evaluator.children(&b);
+                if x.evaluator.member_names(&a) != y.evaluator.member_names(&b)
+                    || xs.len() != ys.len()
+                {
+                    return false;
+                }
+                pairs.extend(xs.into_iter().zip(ys));
+            }
+            (p, q) if !same_leaf(p, q) => return false,
+            _ => {}
+        }
+    }
+    true
+}
+
+fn same_leaf(a: Node, b: Node) -> bool {
+    match (a, b) {
+        (Node::Null | Node::Primitive(PrimitiveValue::Null), q) => {
+            matches!(q, Node::Null | Node::Primitive(PrimitiveValue::Null))
+        }
+        (Node::Primitive(p), Node::Primitive(q)) => {
+            p.primitive_type() == q.primitive_type() && p.to_text() == q.to_text()
+        }
+        _ => a.as_str().is_some() && a.as_str() == b.as_str(),
+    }
+}
+
+/// Records where each object is first reached, depth first.
+fn find_homes<'a>(
+    evaluator: &Evaluator<'a>,
+    root: &Match<'a>,
+    places: &mut Places<'a>,
+    homes: &mut HashMap<i32, Home>,
+) {
+    let mut stack = vec![(root.clone(), 0)];
+    while let Some((m, place)) = stack.pop() {
+        if let Some(id) = object_id(m.node)
+            && !homes.contains_key(&id)
+        {
+            homes.insert(
+                id,
+                Home {
+                    place,
+                    slot: m.slot,
+                },
+            );
+            for child in evaluator.children(&m).into_iter().rev() {
+                let place = places.child(place, child.path.segment());
+                stack.push((child, place));
+            }
+        }
+    }
+}
+
+fn object_id(node: Node) -> Option<i32> {
+    node.record()?.object_id()
+}
+
+fn invalid(path: &str, message: impl Into<String>) -> Error {
+    Error::Patch {
+        path: path.to_string(),
+        message: message.into(),
+    }
+}
@@ -0,0 +1,757 @@
+// nrbf-parser - A high-performance MS-NRBF binary parser and encoder.
+// Copyright (C) 2026  driedpampas@proton.me
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Editing decoded messages in place.
+//!
+//! [`Patch`] finds values with [query](crate::query) paths and checks every
+//! new value against the declared `PrimitiveType` or `BinaryType` of the
+//! slot it goes into. New values are [`builder::Value`](crate::builder::Value)s,
+//! whose objects get fresh ids.
+//!
+//! After each edit the records are made consistent again: array lengths and
+//! null runs are rewritten, dropped objects that are still referenced stay
+//! as top-level records, `ClassWithId` records whose metadata was dropped or
+//! now comes later become full class records, and libraries are moved before
+//! their first use. The records can then go straight to an
+//! [`Encoder`](crate::Encoder).
+
+use crate::builder::{RecordBuilder, Value, null_run};
+use crate::decoder::ClassInfoWithTypes;
+use crate::error::{Error, Result};
+use crate::plain::{Graph, null_count};
+use crate::query::{Query, Slot};
+use crate::records::*;
+use std::collections::{HashMap, HashSet};
+
+/// A message being edited.
+pub struct Patch {
+    pub(crate) records: Vec<Record>,
+    pub(crate) builder: RecordBuilder,
+    /// Metadata of every class seen so far, including classes whose defining
+    /// record has been dropped.
+    pub(crate) metadata: HashMap<i32, ClassInfoWithTypes>,
+}
+
+/// What a slot may hold.
+pub(crate) enum Kind {
+    /// An element of an `ArraySinglePrimitive`.
+    Element(PrimitiveType),
+    /// A member or element with a declared type; untyped slots are objects.
+    Typed(BinaryType, AdditionalTypeInfo),
+}
+
+/// What a new value is, with references resolved.
+#[derive(PartialEq)]
+pub(crate) enum Shape {
+    Null,
+    Primitive(PrimitiveType),
+    String,
+    Class(String),
+    PrimitiveArray(PrimitiveType),
+    ObjectArray,
+    StringArray,
+    /// A `BinaryArray`, accepted by any array slot.
+    Array,
+}
+
+/// A value built for a slot.
+pub(crate) enum Stored {
+    Element(PrimitiveValue),
+    Value(ObjectValue),
+}
+
+#[derive(Clone, Copy)]
+enum Edit {
+    Replace(usize),
+    Insert(usize),
+    Remove(usize),
+}
+
+impl Patch {
+    /// Starts editing a message.
+    pub fn new(records: Vec<Record>) -> Self {
+        let graph = Graph::new(&records);
+        let metadata = graph.metadata.clone();
+        let mut next_id = graph.objects.keys().max().copied().unwrap_or(0);
+        let mut libraries = HashMap::new();
+        for record in &records {
+            if let Record::BinaryLibrary(l) = record {
+                libraries
+                    .entry(l.library_name.clone())
+                    .or_insert(l.library_id);
+                next_id = next_id.max(l.library_id);
+            }
+        }
+        let mut builder = RecordBuilder::new(next_id.saturating_add(1));
+        builder.libraries = libraries;
+        Self {
+            records,
+            builder,
+            metadata,
+        }
+    }
+
+    /// Returns the edited records.
+    pub fn records(&self) -> &[Record] {
+        &self.records
+    }
+
+    /// Finishes editing and returns the records.
+    pub fn into_records(self) -> Vec<Record> {
+        self.records
+    }
+
+    /// Sets the primitive at `path`, which must have the slot's primitive
+    /// type.
+    pub fn set(&mut self, path: &str, value: PrimitiveValue) -> Result<()> {
+        self.replace(path, &Value::Primitive(value))
+    }
+
+    /// Replaces the string at `path` with a new string object.
+    ///
+    /// Other places that referenced the old string keep it.
+    pub fn set_string(&mut self, path: &str, value: impl Into<String>) -> Result<()> {
+        self.replace(path, &Value::String(value.into()))
+    }
+
+    /// Replaces the value at `path`, and with it any objects written inline
+    /// there.
+    ///
+    /// A path to an object that is not stored in a member or element, such
+    /// as `$` or `#12`, replaces that object under the same id with a value
+    /// of the same class or array type.
+    pub fn replace(&mut self, path: &str, value: &Value) -> Result<()> {
+        let (slot, object) = self.locate(path)?;
+        match slot {
+            Some(slot) => {
+                let stored = self.build(path, slot.container, slot.index, value)?;
+                self.apply(
+                    path,
+                    slot.container,
+                    Edit::Replace(slot.index),
+                    Some(stored),
+                )?;
+            }
+            None => {
+                let id = object.ok_or_else(|| invalid(path, "does not select an object"))?;
+                let shape = self
+                    .shape(value)
+                    .map_err(|message| invalid(path, message))?;
+                let current = self
+                    .shape(&Value::Reference(id))
+                    .map_err(|message| invalid(path, message))?;
+                if shape != current {
+                    return Err(invalid(
+                        path,
+                        format!("expected {}, found {}", found(&current), found(&shape)),
+                    ));
+                }
+                let record = self.builder.build_record(Some(id), value)?.ok_or_else(|| {
+                    invalid(path, "can only be replaced by an object, string or array")
+                })?;
+                let target = find_object(&mut self.records, id)
+                    .ok_or_else(|| invalid(path, "does not select an object"))?;
+                let old = std::mem::replace(target, record);
+                self.drop_records(inline_records(&old));
+            }
+        }
+        self.fix_up();
+        Ok(())
+    }
+
+    /// Inserts an element into the array at `path`, before `index`.
+    pub fn insert(&mut self, path: &str, index: usize, value: &Value) -> Result<()> {
+        let (_, object) = self.locate(path)?;
+        let id = object.ok_or_else(|| invalid(path, "does not select an array"))?;
+        let stored = self.build(path, id, index, value)?;
+        self.apply(path, id, Edit::Insert(index), Some(stored))?;
+        self.fix_up();
+        Ok(())
+    }
+
+    /// Appends an element to the array at `path`.
+    pub fn push(&mut self, path: &str, value: &Value) -> Result<()> {
+        let (_, object) = self.locate(path)?;
+        let len = object
+            .and_then(|id| array_len(&mut self.records, id))
+            .ok_or_else(|| invalid(path, "does not select an array"))?;
+        self.insert(path, len, value)
+    }
+
+    /// Removes the array element at `path`.
+    pub fn remove(&mut self, path: &str) -> Result<()> {
+        let (slot, _) = self.locate(path)?;
+        let slot = slot
+            .filter(|slot| array_len(&mut self.records, slot.container).is_some())
+            .ok_or_else(|| invalid(path, "does not select an array element"))?;
+        self.apply(path, slot.container, Edit::Remove(slot.index), None)?;
+        self.fix_up();
+        Ok(())
+    }
+
+    /// Finds the one value `path` selects: its slot, if any, and its object
+    /// id, if it is an object.
+    fn locate(&self, path: &str) -> Result<(Option<Slot>, Option<i32>)> {
+        let query: Query = path.parse()?;
+        let matches = query.evaluate(&self.records);
+        let [m] = matches.as_slice() else {
+            return Err(invalid(
+                path,
+                format!("selects {} values instead of one", matches.len()),
+            ));
+        };
+        Ok((m.slot, m.node.record().and_then(Record::object_id)))
+    }
+
+    /// Returns what the members or elements of `container` may hold.
+    pub(crate) fn kind(&self, container: i32, index: usize) -> Option<Kind> {
+        let graph = Graph::new(&self.records);
+        let record = graph.objects.get(&container)?;
+        Some(match record {
+            Record::ArraySinglePrimitive(a) => Kind::Element(a.primitive_type_enum),
+            Record::ArraySingleObject(_) => {
+                Kind::Typed(BinaryType::Object, AdditionalTypeInfo::None)
+            }
+            Record::ArraySingleString(_) => {
+                Kind::Typed(BinaryType::String, AdditionalTypeInfo::None)
+            }
+            Record::BinaryArray(a) => Kind::Typed(a.type_enum, a.additional_type_info.clone()),
+            _ => match &graph.class_of(record)?.member_type_info {
+                Some(types) => Kind::Typed(
+                    *types.binary_type_enums.get(index)?,
+                    types.additional_infos.get(index)?.clone(),
+                ),
+                None => Kind::Typed(BinaryType::Object, AdditionalTypeInfo::None),
+            },
+        })
+    }
+
+    /// Checks `value` against a slot of `container` and builds it.
+    fn build(&mut self, path: &str, container: i32, index: usize, value: &Value) -> Result<Stored> {
+        let kind = self
+            .kind(container, index)
+            .ok_or_else(|| invalid(path, "does not select a member or element"))?;
+        let shape = self
+            .shape(value)
+            .map_err(|message| invalid(path, message))?;
+        if !accepts(&kind, &shape) {
+            return Err(invalid(
+                path,
+                format!("expected {}, found {}", expected(&kind), found(&shape)),
+            ));
+        }
+        Ok(match (&kind, value) {
+            (Kind::Element(_), Value::Primitive(p)) => Stored::Element(p.clone()),
+            (Kind::Typed(BinaryType::Primitive, _), Value::Primitive(p)) => {
+                Stored::Value(ObjectValue::Primitive(p.clone()))
+            }
+            _ => Stored::Value(self.builder.build_value(value)?),
+        })
+    }
+
+    pub(crate) fn shape(&self, value: &Value) -> std::result::Result<Shape, String> {
+        Ok(match value {
+            Value::Null => Shape::Null,
+            Value::Primitive(p) => Shape::Primitive(p.primitive_type()),
+            Value::String(_) => Shape::String,
+            Value::Object(class, _) => Shape::Class(class.name.clone()),
+            Value::PrimitiveArray(t, _) => Shape::PrimitiveArray(*t),
+            Value::ObjectArray(_) => Shape::ObjectArray,
+            Value::StringArray(_) => Shape::StringArray,
+            Value::Reference(id) => {
+                let graph = Graph::new(&self.records);
+                let target = graph
+                    .objects
+                    .get(id)
+                    .ok_or_else(|| format!("no object has id {}", id))?;
+                match target {
+                    Record::BinaryObjectString { .. } => Shape::String,
+                    Record::ArraySinglePrimitive(a) => Shape::PrimitiveArray(a.primitive_type_enum),
+                    Record::ArraySingleObject(_) => Shape::ObjectArray,
+                    Record::ArraySingleString(_) => Shape::StringArray,
+                    Record::BinaryArray(_) => Shape::Array,
+                    _ => match graph.class_of(target) {
+                        Some(metadata) => Shape::Class(metadata.class_info.name.clone()),
+                        None => return Err(format!("object {} is not a class", id)),
+                    },
+                }
+            }
+        })
+    }
+
+    /// Stores a value in, inserts it into or removes it from `container`.
+    fn apply(
+        &mut self,
+        path: &str,
+        container: i32,
+        edit: Edit,
+        stored: Option<Stored>,
+    ) -> Result<()> {
+        let record = find_object(&mut self.records, container)
+            .ok_or_else(|| invalid(path, "does not select a member or element"))?;
+        let mut dropped = Vec::new();
+        match record {
+            Record::ArraySinglePrimitive(a) => {
+                let values = &mut a.element_values;
+                match (edit, stored) {
+                    (Edit::Replace(index), Some(Stored::Element(value))) => values[index] = value,
+                    (Edit::Insert(index), Some(Stored::Element(value)))
+                        if index <= values.len() =>
+                    {
+                        values.insert(index, value)
+                    }
+                    (Edit::Remove(index), None) => {
+                        values.remove(index);
+                    }
+                    _ => return Err(out_of_range(path, edit, values.len())),
+                }
+                a.length = a.element_values.len() as i32;
+            }
+            Record::BinaryArray(_)
+            | Record::ArraySingleObject(_)
+            | Record::ArraySingleString(_) => {
+                if let (Record::BinaryArray(a), Edit::Insert(_) | Edit::Remove(_)) = (&record, edit)
+                    && a.rank != 1
+                {
+                    return Err(invalid(path, "multi-dimensional arrays cannot be resized"));
+                }
+                let mut values = expand(record.child_values());
+                let value = match stored {
+                    Some(Stored::Value(value)) => Some(value),
+                    _ => None,
+                };
+                match (edit, value) {
+                    (Edit::Replace(index), Some(value)) => {
+                        dropped.push(std::mem::replace(&mut values[index], value))
+                    }
+                    (Edit::Insert(index), Some(value)) if index <= values.len() => {
+                        values.insert(index, value)
+                    }
+                    (Edit::Remove(index), None) => dropped.push(values.remove(index)),
+                    _ => return Err(out_of_range(path, edit, values.len())),
+                }
+                let len = values.len() as i32;
+                let values = compress(values);
+                match record {
+                    Record::BinaryArray(a) => {
+                        a.element_values = values;
+                        if a.rank == 1 {
+                            a.lengths[0] = len;
+                        }
+                    }
+                    Record::ArraySingleObject(a) => {
+                        a.element_values = values;
+                        a.length = len;
+                    }
+                    Record::ArraySingleString(a) => {
+                        a.element_values = values;
+                        a.length = len;
+                    }
+                    _ => {}
+                }
+            }
+            _ => {
+                let (Edit::Replace(index), Some(Stored::Value(value))) = (edit, stored) else {
+                    return Err(invalid(path, "class members cannot be inserted or removed"));
+                };
+                dropped.push(std::mem::replace(
+                    &mut record.child_values_mut()[index],
+                    value,
+                ));
+            }
+        }
+        let dropped = dropped
+            .into_iter()
+            .filter_map(|value| match value {
+                ObjectValue::Record(record) => Some(*record),
+                ObjectValue::Primitive(_) => None,
+            })
+            .collect();
+        self.drop_records(dropped);
+        Ok(())
+    }
+
+    /// Keeps the objects in `dropped` that other records still reference,
+    /// moving them to the top level.
+    ///
+    /// Objects that are back in the records, e.g. because they were moved,
+    /// are left alone.
+    pub(crate) fn drop_records(&mut self, dropped: Vec<Record>) {
+        let present: HashSet<i32> = Graph::new(&self.records).objects.into_keys().collect();
+        let mut objects = HashMap::new();
+        for record in &dropped {
+            index_objects(record, &mut objects);
+        }
+        objects.retain(|id, _| !present.contains(id));
+        let mut needed = HashSet::new();
+        let mut pending = Vec::new();
+        for record in &self.records {
+            references(record, &mut pending);
+        }
+        while let Some(id) = pending.pop() {
+            if let Some(record) = objects.get(&id)
+                && needed.insert(id)
+            {
+                references(record, &mut pending);
+            }
+        }
+
+        let mut kept = Vec::new();
+        let mut stack = dropped;
+        while let Some(record) = stack.pop() {
+            if record.object_id().is_some_and(|id| needed.contains(&id)) {
+                kept.push(record);
+            } else {
+                stack.extend(inline_records(&record));
+            }
+        }
+        let end = self
+            .records
+            .iter()
+            .rposition(|r| matches!(r, Record::MessageEnd))
+            .unwrap_or(self.records.len());
+        self.records.splice(end..end, kept);
+    }
+
+    /// Restores the stream invariants an edit may have broken.
+    pub(crate) fn fix_up(&mut self) {
+        let libraries = self.builder.take_libraries();
+        let at = usize::from(matches!(
+            self.records.first(),
+            Some(Record::SerializationHeader(_))
+        ));
+        self.records
+            .splice(at..at, libraries.into_iter().map(Record::BinaryLibrary));
+
+        let graph = Graph::new(&self.records);
+        for (id, metadata) in graph.metadata {
+            self.metadata.insert(id, metadata);
+        }
+        let mut defined = HashSet::new();
+        let mut moved = HashMap::new();
+        for record in &mut self.records {
+            fix_class_ids(record, &self.metadata, &mut defined, &mut moved);
+        }
+        self.fix_libraries();
+    }
+
+    /// Moves each `BinaryLibrary` before the first top-level record using it.
+    fn fix_libraries(&mut self) {
+        let mut defined = HashSet::new();
+        let mut i = 0;
+        while i < self.records.len() {
+            if let Record::BinaryLibrary(l) = &self.records[i] {
+                defined.insert(l.library_id);
+                i += 1;
+                continue;
+            }
+            let mut used = Vec::new();
+            library_uses(&self.records[i], &mut used);
+            for id in used {
+                if !defined.insert(id) {
+                    continue;
+                }
+                let later = self.records[i..]
+                    .iter()
+                    .position(|r| matches!(r, Record::BinaryLibrary(l) if l.library_id == id));
+                if let Some(offset) = later {
+                    let library = self.records.remove(i + offset);
+                    self.records.insert(i, library);
+                    i += 1;
+                }
+            }
+            i += 1;
+        }
+    }
+}
+
+/// Turns `ClassWithId` records whose metadata is not defined earlier into
+/// full class records, in stream order.
+fn fix_class_ids(
+    record: &mut Record,
+    metadata: &HashMap<i32, ClassInfoWithTypes>,
+    defined: &mut HashSet<i32>,
+    moved: &mut HashMap<i32, i32>,
+) {
+    if let Record::ClassWithId(c) = record {
+        let target = moved.get(&c.metadata_id).copied().unwrap_or(c.metadata_id);
+        if defined.contains(&target) {
+            c.metadata_id = target;
+        } else if let Some(class) = metadata.get(&c.metadata_id) {
+            moved.insert(c.metadata_id, c.object_id);
+            let values = std::mem::take(&mut c.member_values);
+            *record = class_record(c.object_id, class, values);
+        }
+    }
+    if let Some(info) = record.class_info() {
+        defined.insert(info.object_id);
+    }
+    for value in record.child_values_mut() {
+        if let ObjectValue::Record(r) = value {
+            fix_class_ids(r, metadata, defined, moved);
+        }
+    }
+}
+
+/// Builds a record that defines `class` for object `object_id`.
+pub(crate) fn class_record(
+    object_id: i32,
+    class: &ClassInfoWithTypes,
+    member_values: Vec<ObjectValue>,
+) -> Record {
+    let class_info = ClassInfo {
+        object_id,
+        ..class.class_info.clone()
+    };
+    match (class.member_type_info.clone(), class.library_id) {
+        (Some(member_type_info), Some(library_id)) => {
+            Record::ClassWithMembersAndTypes(ClassWithMembersAndTypes {
+                class_info,
+                member_type_info,
+                library_id,
+                member_values,
+            })
+        }
+        (Some(member_type_info), None) => {
+            Record::SystemClassWithMembersAndTypes(SystemClassWithMembersAndTypes {
+                class_info,
+                member_type_info,
+                member_values,
+            })
+        }
+        (None, Some(library_id)) => Record::ClassWithMembers(ClassWithMembers {
+            class_info,
+            library_id,
+            member_values,
+        }),
+        (None, None) => Record::SystemClassWithMembers(SystemClassWithMembers {
+            class_info,
+            member_values,
+        }),
+    }
+}
+
+pub(crate) fn accepts(kind: &Kind, shape: &Shape) -> bool {
+    match (kind, shape) {
+        (Kind::Element(t), Shape::Primitive(p)) => t == p,
+        (Kind::Element(_), _) => false,
+        (
+            Kind::Typed(BinaryType::Primitive, AdditionalTypeInfo::Primitive(t)),
+            Shape::Primitive(p),
+        ) => t == p,
+        (Kind::Typed(BinaryType::Primitive, _), _) => false,
+        (Kind::Typed(BinaryType::Object, _), _) => true,
+        (_, Shape::Null) => true,
+        (Kind::Typed(BinaryType::String, _), Shape::String) => true,
+        (
+            Kind::Typed(BinaryType::SystemClass, AdditionalTypeInfo::SystemClass(t)),
+            Shape::Class(name),
+        ) => t == name,
+        (Kind::Typed(BinaryType::Class, AdditionalTypeInfo::Class(t)), Shape::Class(name)) => {
+            t.type_name == *name
+        }
+        (
+            Kind::Typed(
+                BinaryType::ObjectArray | BinaryType::StringArray | BinaryType::PrimitiveArray,
+                _,
+            ),
+            Shape::Array,
+        ) => true,
+        (Kind::Typed(BinaryType::ObjectArray, _), Shape::ObjectArray) => true,
+        (Kind::Typed(BinaryType::StringArray, _), Shape::StringArray) => true,
+        (
+            Kind::Typed(BinaryType::PrimitiveArray, AdditionalTypeInfo::Primitive(t)),
+            Shape::PrimitiveArray(p),
+        ) => t == p,
+        _ => false,
+    }
+}
+
+pub(crate) fn expected(kind: &Kind) -> String {
+    match kind {
+        Kind::Element(t) => format!("{:?}", t),
+        Kind::Typed(BinaryType::Primitive, AdditionalTypeInfo::Primitive(t)) => format!("{:?}", t),
+        Kind::Typed(BinaryType::PrimitiveArray, AdditionalTypeInfo::Primitive(t)) => {
+            format!("{:?} array or null", t)
+        }
+        Kind::Typed(_, AdditionalTypeInfo::SystemClass(name)) => format!("{} or null", name),
+        Kind::Typed(_, AdditionalTypeInfo::Class(c)) => format!("{} or null", c.type_name),
+        Kind::Typed(BinaryType::String, _) => "string or null".to_string(),
+        Kind::Typed(BinaryType::StringArray, _) => "string array or null".to_string(),
+        Kind::Typed(t, _) => format!("{:?} or null", t),
+    }
+}
+
+pub(crate) fn found(shape: &Shape) -> String {
+    match shape {
+        Shape::Null => "null".to_string(),
+        Shape::Primitive(t) => format!("{:?}", t),
+        Shape::String => "string".to_string(),
+        Shape::Class(name) => name.clone(),
+        Shape::PrimitiveArray(t) => format!("{:?} array", t),
+        Shape::ObjectArray => "object array".to_string(),
+        Shape::StringArray => "string array".to_string(),
+        Shape::Array => "array".to_string(),
+    }
+}
+
+fn invalid(path: &str, message: impl Into<String>) -> Error {
+    Error::Patch {
+        path: path.to_string(),
+        message: message.into(),
+    }
+}
+
+fn out_of_range(path: &str, edit: Edit, len: usize) -> Error {
+    let index = match edit {
+        Edit::Replace(i) | Edit::Insert(i) | Edit::Remove(i) => i,
+    };
+    invalid(
+        path,
+        format!("index {} is out of range for {} elements", index, len),
+    )
+}
+
+/// Finds the record of object `id`, wherever it is nested.
+pub(crate) fn find_object(records: &mut [Record], id: i32) -> Option<&mut Record> {
+    for record in records {
+        if record.object_id() == Some(id) {
+            return Some(record);
+        }
+        for value in record.child_values_mut() {
+            if let ObjectValue::Record(r) = value
+                && let Some(found) = find_object(std::slice::from_mut(r.as_mut()), id)
+            {
+                return Some(found);
+            }
+        }
+    }
+    None
+}
+
+/// Returns the number of elements of array `id`.
+fn array_len(records: &mut [Record], id: i32) -> Option<usize> {
+    match find_object(records, id)? {
+        Record::ArraySinglePrimitive(a) => Some(a.element_values.len()),
+        record @ (Record::BinaryArray(_)
+        | Record::ArraySingleObject(_)
+        | Record::ArraySingleString(_)) => Some(expand(record.child_values()).len()),
+        _ => None,
+    }
+}
+
+/// Returns the records written inline inside `record`.
+fn inline_records(record: &Record) -> Vec<Record> {
+    record
+        .child_values()
+        .iter()
+        .filter_map(|value| match value {
+            ObjectValue::Record(r) => Some(r.as_ref().clone()),
+            ObjectValue::Primitive(_) => None,
+        })
+        .collect()
+}
+
+pub(crate) fn index_objects<'a>(record: &'a Record, objects: &mut HashMap<i32, &'a Record>) {
+    if let Some(id) = record.object_id() {
+        objects.insert(id, record);
+    }
+    for value in record.child_values() {
+        if let ObjectValue::Record(r) = value {
+            index_objects(r, objects);
+        }
+    }
+}
+
+fn references(record: &Record, out: &mut Vec<i32>) {
+    if let Record::MemberReference { id_ref } = record {
+        out.push(*id_ref);
+    }
+    for value in record.child_values() {
+        if let ObjectValue::Record(r) = value {
+            references(r, out);
+        }
+    }
+}
+
+pub(crate) fn library_uses(record: &Record, out: &mut Vec<i32>) {
+    let infos: &[AdditionalTypeInfo] = match record {
+        Record::ClassWithMembersAndTypes(c) => {
+            out.push(c.library_id);
+            &c.member_type_info.additional_infos
+        }
+        Record::SystemClassWithMembersAndTypes(c) => &c.member_type_info.additional_infos,
+        Record::ClassWithMembers(c) => {
+            out.push(c.library_id);
+            &[]
+        }
+        Record::BinaryArray(a) => std::slice::from_ref(&a.additional_type_info),
+        _ => &[],
+    };
+    for info in infos {
+        if let AdditionalTypeInfo::Class(c) = info {
+            out.push(c.library_id);
+        }
+    }
+    for value in record.child_values() {
+        if let ObjectValue::Record(r) = value {
+            library_uses(r, out);
+        }
+    }
+}
+
+/// Expands null runs into one `ObjectNull` per element.
+pub(crate) fn expand(values: &[ObjectValue]) -> Vec<ObjectValue> {
+    let mut expanded = Vec::with_capacity(values.len());
+    for value in values {
+        match value {
+            ObjectValue::Record(r) if null_count(r).is_some() => {
+                let count = null_count(r).unwrap_or(0);
+                expanded.extend(
+                    std::iter::repeat_with(|| ObjectValue::Record(Box::new(Record::ObjectNull)))
+                        .take(count),
+                );
+            }
+            _ => expanded.push(value.clone()),
+        }
+    }
+    expanded
+}
+
+/// Collapses consecutive nulls into null runs.
+pub(crate) fn compress(values: Vec<ObjectValue>) -> Vec<ObjectValue> {
+    let mut compressed = Vec::with_capacity(values.len());
+    let mut nulls = 0;
+    for value in values {
+        let null = match &value {
+            ObjectValue::Record(r) => matches!(r.as_ref(), Record::ObjectNull),
+            ObjectValue::Primitive(p) => matches!(p, PrimitiveValue::Null),
+        };
+        if null {
+            nulls += 1;
+            continue;
+        }
+        if nulls > 0 {
+            compressed.push(ObjectValue::Record(Box::new(null_run(nulls))));
+            nulls = 0;
+        }
+        compressed.push(value);
+    }
+    if nulls > 0 {
+        compressed.push(ObjectValue::Record(Box::new(null_run(nulls))));
+    }
+    compressed
+}
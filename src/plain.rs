@@ -0,0 +1,889 @@
+// nrbf-parser - A high-performance MS-NRBF binary parser and encoder.
+// Copyright (C) 2026  driedpampas@proton.me
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! A "plain" JSON projection of the object graph, with a skeleton sidecar.
+//!
+//! [`to_plain`] renders the root object as ordinary JSON: classes become
+//! objects keyed by member name, strings and primitives become JSON values,
+//! arrays become arrays, and references are resolved to nested objects.
+//! A reference back into an object that is still being rendered, or to an
+//! object the message does not contain, becomes `{"$ref": id}`.
+//!
+//! Everything the plain JSON leaves out lives in the [`Skeleton`], and
+//! [`from_plain`] combines the two back into records.
+
+use crate::decoder::ClassInfoWithTypes;
+use crate::error::{Error, Result};
+use crate::query::Path;
+use crate::records::*;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value, json};
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+/// Everything needed to turn plain JSON back into records.
+///
+/// This is not a description of the JSON, for which see
+/// [`json_schema`](crate::json_schema), but the message itself with the
+/// values of the objects rendered in the plain JSON blanked out: the
+/// header, libraries, class metadata, object ids and stream layout are
+/// kept, so unedited objects encode to the same bytes. Records the root
+/// does not reach are kept whole.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Skeleton {
+    pub records: Vec<Record>,
+}
+
+/// Renders the message rooted at the header's `root_id` as plain JSON.
+pub fn to_plain(records: &[Record]) -> Result<(Value, Skeleton)> {
+    let graph = Graph::new(records);
+    let mut renderer = Renderer::new(&graph, None);
+    let plain = renderer.render_root(records)?;
+
+    let records = records
+        .iter()
+        .map(|record| {
+            let mut record = record.clone();
+            if record
+                .object_id()
+                .is_some_and(|id| renderer.shown.contains_key(&id))
+            {
+                blank(&mut record);
+            }
+            record
+        })
+        .collect();
+    Ok((plain, Skeleton { records }))
+}
+
+/// Merges plain JSON, possibly edited, with its skeleton back into records.
+///
+/// Primitive values, strings and primitive or string array contents may
+/// change freely. Members may be set to `null`, to a string, or to
+/// `{"$ref": id}` for an existing object, but new objects cannot be created.
+/// An object shown at several places takes its values from the first one;
+/// a shared string edited at a later place becomes a new string there.
+pub fn from_plain(plain: &Value, skeleton: &Skeleton) -> Result<Vec<Record>> {
+    let graph = Graph::new(&skeleton.records);
+    let mut renderer = Renderer::new(&graph, Some(plain));
+    renderer.render_root(&skeleton.records)?;
+
+    let mut merger = Merger {
+        graph: &graph,
+        shown: renderer.shown,
+        next_id: graph
+            .objects
+            .keys()
+            .max()
+            .map_or(1, |id| id.saturating_add(1)),
+    };
+    skeleton
+        .records
+        .iter()
+        .map(|record| merger.rebuild_record(record))
+        .collect()
+}
+
+/// Objects and class metadata by id.
+pub(crate) struct Graph<'a> {
+    pub(crate) objects: HashMap<i32, &'a Record>,
+    pub(crate) metadata: HashMap<i32, ClassInfoWithTypes>,
+}
+
+impl<'a> Graph<'a> {
+    pub(crate) fn new(records: &'a [Record]) -> Self {
+        let mut graph = Self {
+            objects: HashMap::new(),
+            metadata: HashMap::new(),
+        };
+        for record in records {
+            graph.add(record);
+        }
+        graph
+    }
+
+    fn add(&mut self, record: &'a Record) {
+        if let Some(id) = record.object_id() {
+            self.objects.entry(id).or_insert(record);
+        }
+        if let Some(metadata) = ClassInfoWithTypes::from_record(record) {
+            self.metadata
+                .insert(metadata.class_info.object_id, metadata);
+        }
+        for value in record.child_values() {
+            if let ObjectValue::Record(r) = value {
+                self.add(r);
+            }
+        }
+    }
+
+    /// Returns the member names and type info of a class record.
+    pub(crate) fn class_of(&self, record: &Record) -> Option<&ClassInfoWithTypes> {
+        let metadata_id = match record {
+            Record::ClassWithId(c) => c.metadata_id,
+            _ => record.class_info()?.object_id,
+        };
+        self.metadata.get(&metadata_id)
+    }
+}
+
+/// Renders plain JSON, recording where each object is first shown.
+///
+/// Objects are rendered from an explicit stack of [`Frame`]s rather than by
+/// recursion, so long reference chains cannot overflow the call stack.
+struct Renderer<'a> {
+    graph: &'a Graph<'a>,
+    /// Plain JSON to find the nodes of rendered objects in, when merging.
+    plain: Option<&'a Value>,
+    /// The first place each object is rendered.
+    shown: HashMap<i32, Shown<'a>>,
+    /// Objects currently being rendered.
+    open: HashSet<i32>,
+}
+
+/// Where an object is first rendered.
+struct Shown<'a> {
+    path: Rc<Path<'a>>,
+    /// The node at `path` in the plain JSON being merged, if it has one.
+    node: Option<&'a Value>,
+}
+
+/// A class or array whose members or elements are being rendered.
+struct Frame<'a> {
+    id: Option<i32>,
+    path: Rc<Path<'a>>,
+    node: Option<&'a Value>,
+    /// Member names of a class, or `None` for an array.
+    names: Option<&'a [String]>,
+    values: &'a [ObjectValue],
+    next: usize,
+    members: Map<String, Value>,
+    elements: Vec<Value>,
+}
+
+impl<'a> Frame<'a> {
+    /// Returns the next value to render, its path and its plain JSON node.
+    fn next_slot(&mut self) -> Option<(&'a ObjectValue, Rc<Path<'a>>, Option<&'a Value>)> {
+        let (path, node) = match self.names {
+            Some(names) => {
+                let name = names.get(self.next)?;
+                (self.path.member(name), self.node.and_then(|n| n.get(name)))
+            }
+            None => {
+                while let Some(ObjectValue::Record(r)) = self.values.get(self.next)
+                    && let Some(count) = null_count(r)
+                {
+                    self.elements
+                        .extend(std::iter::repeat_n(Value::Null, count));
+                    self.next += 1;
+                }
+                let index = self.elements.len();
+                (self.path.index(index), self.node.and_then(|n| n.get(index)))
+            }
+        };
+        let value = self.values.get(self.next)?;
+        self.next += 1;
+        Some((value, path, node))
+    }
+
+    /// Stores the rendered value of the slot last returned.
+    fn push(&mut self, value: Value) {
+        match self.names {
+            Some(names) => {
+                self.members.insert(names[self.next - 1].clone(), value);
+            }
+            None => self.elements.push(value),
+        }
+    }
+
+    fn finish(self) -> Value {
+        match self.names {
+            Some(_) => Value::Object(self.members),
+            None => Value::Array(self.elements),
+        }
+    }
+}
+
+impl<'a> Renderer<'a> {
+    fn new(graph: &'a Graph<'a>, plain: Option<&'a Value>) -> Self {
+        Self {
+            graph,
+            plain,
+            shown: HashMap::new(),
+            open: HashSet::new(),
+        }
+    }
+
+    fn render_root(&mut self, records: &[Record]) -> Result<Value> {
+        let root_id = records
+            .iter()
+            .find_map(|record| match record {
+                Record::SerializationHeader(h) => Some(h.root_id),
+                _ => None,
+            })
+            .ok_or_else(|| invalid("", "message has no SerializationHeader"))?;
+
+        let mut frames = Vec::new();
+        let mut rendered = self.render_reference(root_id, Path::root(), self.plain, &mut frames)?;
+        while let Some(frame) = frames.last_mut() {
+            if let Some(value) = rendered.take() {
+                frame.push(value);
+            }
+            match frame.next_slot() {
+                Some((value, path, node)) => {
+                    rendered = self.render_slot(value, path, node, &mut frames)?
+                }
+                None => {
+                    let Some(frame) = frames.pop() else { break };
+                    if let Some(id) = frame.id {
+                        self.open.remove(&id);
+                    }
+                    rendered = Some(frame.finish());
+                }
+            }
+        }
+        Ok(rendered.unwrap_or(Value::Null))
+    }
+
+    /// Renders a leaf record, or pushes a frame for a class or array and
+    /// returns `None`.
+    fn render_record(
+        &mut self,
+        record: &'a Record,
+        path: Rc<Path<'a>>,
+        node: Option<&'a Value>,
+        frames: &mut Vec<Frame<'a>>,
+    ) -> Result<Option<Value>> {
+        let id = record.object_id();
+        if let Some(id) = id {
+            self.shown.entry(id).or_insert_with(|| Shown {
+                path: path.clone(),
+                node,
+            });
+        }
+        let names = match record {
+            Record::ClassWithMembersAndTypes(_)
+            | Record::SystemClassWithMembersAndTypes(_)
+            | Record::SystemClassWithMembers(_)
+            | Record::ClassWithMembers(_)
+            | Record::ClassWithId(_) => {
+                let graph = self.graph;
+                let class = graph
+                    .class_of(record)
+                    .ok_or_else(|| invalid(&path.pointer(), "class metadata not found"))?;
+                Some(class.class_info.member_names.as_slice())
+            }
+            Record::BinaryArray(_)
+            | Record::ArraySingleObject(_)
+            | Record::ArraySingleString(_) => None,
+            Record::BinaryObjectString { value, .. } => {
+                return Ok(Some(Value::String(value.clone())));
+            }
+            Record::ArraySinglePrimitive(a) => {
+                return Ok(Some(Value::Array(
+                    a.element_values.iter().map(primitive_to_json).collect(),
+                )));
+            }
+            Record::MemberPrimitiveTyped { value, .. } => {
+                return Ok(Some(primitive_to_json(value)));
+            }
+            Record::MemberReference { id_ref } => {
+                return self.render_reference(*id_ref, path, node, frames);
+            }
+            _ => return Ok(Some(Value::Null)),
+        };
+        if let Some(id) = id {
+            self.open.insert(id);
+        }
+        frames.push(Frame {
+            id,
+            path,
+            node,
+            names,
+            values: record.child_values(),
+            next: 0,
+            members: Map::new(),
+            elements: Vec::new(),
+        });
+        Ok(None)
+    }
+
+    fn render_slot(
+        &mut self,
+        value: &'a ObjectValue,
+        path: Rc<Path<'a>>,
+        node: Option<&'a Value>,
+        frames: &mut Vec<Frame<'a>>,
+    ) -> Result<Option<Value>> {
+        match value {
+            ObjectValue::Primitive(p) => Ok(Some(primitive_to_json(p))),
+            ObjectValue::Record(r) => self.render_record(r, path, node, frames),
+        }
+    }
+
+    fn render_reference(
+        &mut self,
+        id: i32,
+        path: Rc<Path<'a>>,
+        node: Option<&'a Value>,
+        frames: &mut Vec<Frame<'a>>,
+    ) -> Result<Option<Value>> {
+        match self.graph.objects.get(&id) {
+            Some(record) if !self.open.contains(&id) => {
+                self.render_record(record, path, node, frames)
+            }
+            _ => Ok(Some(json!({ "$ref": id }))),
+        }
+    }
+}
+
+/// Rebuilds records from plain JSON, following the skeleton's layout.
+///
+/// Each object is rebuilt from the node where it was first rendered, and
+/// pointers are only formatted for errors, so deep graphs stay linear.
+struct Merger<'a> {
+    graph: &'a Graph<'a>,
+    shown: HashMap<i32, Shown<'a>>,
+    next_id: i32,
+}
+
+impl<'a> Merger<'a> {
+    fn fresh_id(&mut self) -> i32 {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+
+    /// Returns the value of a string object where it is first shown.
+    fn string_value(&self, id: i32) -> Option<&'a str> {
+        self.shown.get(&id)?.node?.as_str()
+    }
+
+    /// Returns whether `node` is where object `id` is first shown.
+    fn is_first(&self, id: i32, node: &Value) -> bool {
+        self.shown
+            .get(&id)
+            .and_then(|shown| shown.node)
+            .is_some_and(|first| std::ptr::eq(first, node))
+    }
+
+    fn rebuild_record(&mut self, record: &Record) -> Result<Record> {
+        let Some(shown) = record.object_id().and_then(|id| self.shown.get(&id)) else {
+            return Ok(record.clone());
+        };
+        let path = shown.path.clone();
+        let node = shown
+            .node
+            .ok_or_else(|| invalid(&path.pointer(), "value missing"))?;
+        let mut rebuilt = record.clone();
+        match &mut rebuilt {
+            Record::ClassWithMembersAndTypes(_)
+            | Record::SystemClassWithMembersAndTypes(_)
+            | Record::SystemClassWithMembers(_)
+            | Record::ClassWithMembers(_)
+            | Record::ClassWithId(_) => {
+                let graph = self.graph;
+                let class = graph
+                    .class_of(record)
+                    .ok_or_else(|| invalid(&path.pointer(), "class metadata not found"))?;
+                let members = node
+                    .as_object()
+                    .ok_or_else(|| invalid(&path.pointer(), "expected an object"))?;
+                for (i, (name, value)) in class
+                    .class_info
+                    .member_names
+                    .iter()
+                    .zip(rebuilt.child_values_mut())
+                    .enumerate()
+                {
+                    let member_path = path.member(name);
+                    let node = members
+                        .get(name)
+                        .ok_or_else(|| invalid(&member_path.pointer(), "member missing"))?;
+                    let slot = class
+                        .member_type_info
+                        .as_ref()
+                        .and_then(|info| info.primitive_slot(i));
+                    *value = self.rebuild_slot(Some(value), node, slot, &member_path)?;
+                }
+            }
+            Record::BinaryObjectString { value, .. } => {
+                if let Some(s) = node.as_str() {
+                    *value = s.to_string();
+                }
+            }
+            Record::ArraySinglePrimitive(a) => {
+                a.element_values = primitive_elements(node, a.primitive_type_enum, &path)?;
+                a.length = a.element_values.len() as i32;
+            }
+            Record::BinaryArray(a) => {
+                if let (BinaryType::Primitive, AdditionalTypeInfo::Primitive(t)) =
+                    (&a.type_enum, &a.additional_type_info)
+                {
+                    a.element_values = primitive_elements(node, *t, &path)?
+                        .into_iter()
+                        .map(ObjectValue::Primitive)
+                        .collect();
+                } else {
+                    a.element_values = self.rebuild_elements(&a.element_values, node, &path)?;
+                }
+                let count = node.as_array().map_or(0, Vec::len) as i32;
+                let total = a
+                    .lengths
+                    .iter()
+                    .try_fold(1i32, |product, l| product.checked_mul(*l))
+                    .ok_or_else(|| {
+                        invalid(
+                            &path.pointer(),
+                            format!("array lengths {:?} overflow", a.lengths),
+                        )
+                    })?;
+                if total != count {
+                    if a.rank != 1 {
+                        return Err(invalid(
+                            &path.pointer(),
+                            "the length of a multidimensional array cannot change",
+                        ));
+                    }
+                    a.lengths = vec![count];
+                }
+            }
+            Record::ArraySingleObject(a) => {
+                a.element_values = self.rebuild_elements(&a.element_values, node, &path)?;
+                a.length = node.as_array().map_or(0, Vec::len) as i32;
+            }
+            Record::ArraySingleString(a) => {
+                a.element_values = self.rebuild_elements(&a.element_values, node, &path)?;
+                a.length = node.as_array().map_or(0, Vec::len) as i32;
+            }
+            _ => {}
+        }
+        Ok(rebuilt)
+    }
+
+    /// Rebuilds array elements, keeping null runs that are still null.
+    fn rebuild_elements(
+        &mut self,
+        values: &[ObjectValue],
+        node: &Value,
+        path: &Rc<Path<'a>>,
+    ) -> Result<Vec<ObjectValue>> {
+        let nodes = node
+            .as_array()
+            .ok_or_else(|| invalid(&path.pointer(), "expected an array"))?;
+        let mut rebuilt = Vec::new();
+        let mut index = 0;
+        for value in values {
+            if index >= nodes.len() {
+                break;
+            }
+            if let ObjectValue::Record(r) = value
+                && let Some(count) = null_count(r)
+            {
+                let end = (index + count).min(nodes.len());
+                if end == index + count && nodes[index..end].iter().all(Value::is_null) {
+                    rebuilt.push(value.clone());
+                } else {
+                    for (i, node) in nodes.iter().enumerate().take(end).skip(index) {
+                        rebuilt.push(self.rebuild_slot(None, node, None, &path.index(i))?);
+                    }
+                }
+                index = end;
+                continue;
+            }
+            rebuilt.push(self.rebuild_slot(
+                Some(value),
+                &nodes[index],
+                None,
+                &path.index(index),
+            )?);
+            index += 1;
+        }
+        for (i, node) in nodes.iter().enumerate().skip(index) {
+            rebuilt.push(self.rebuild_slot(None, node, None, &path.index(i))?);
+        }
+        Ok(rebuilt)
+    }
+
+    /// Rebuilds a member or element from its original value and plain JSON.
+    fn rebuild_slot(
+        &mut self,
+        original: Option<&ObjectValue>,
+        node: &Value,
+        slot: Option<PrimitiveType>,
+        path: &Rc<Path<'a>>,
+    ) -> Result<ObjectValue> {
+        if let Some(t) = slot {
+            return Ok(ObjectValue::Primitive(json_to_primitive(node, t, path)?));
+        }
+        let record = match original {
+            Some(ObjectValue::Primitive(PrimitiveValue::Null)) | None => {
+                return self.new_slot(node, path);
+            }
+            Some(ObjectValue::Primitive(p)) => {
+                return Ok(ObjectValue::Primitive(json_to_primitive(
+                    node,
+                    p.primitive_type(),
+                    path,
+                )?));
+            }
+            Some(ObjectValue::Record(r)) => r.as_ref(),
+        };
+        let rebuilt = match record {
+            Record::MemberPrimitiveTyped {
+                primitive_type_enum,
+                ..
+            } => Record::MemberPrimitiveTyped {
+                primitive_type_enum: *primitive_type_enum,
+                value: json_to_primitive(node, *primitive_type_enum, path)?,
+            },
+            Record::MemberReference { id_ref } => {
+                return self.rebuild_reference(*id_ref, node, path);
+            }
+            Record::BinaryObjectString { object_id, .. } => {
+                let first = self.is_first(*object_id, node);
+                match node {
+                    Value::Null if first => Record::ObjectNull,
+                    Value::String(s) if first || self.string_value(*object_id) == Some(s) => {
+                        Record::BinaryObjectString {
+                            object_id: *object_id,
+                            value: s.clone(),
+                        }
+                    }
+                    _ if first => return self.new_slot(node, path),
+                    _ => {
+                        return Err(invalid(
+                            &path.pointer(),
+                            format!("conflicting edits to shared string {}", object_id),
+                        ));
+                    }
+                }
+            }
+            _ if record.object_id().is_some() => match node {
+                Value::Null => Record::ObjectNull,
+                Value::Object(_) | Value::Array(_) if reference_target(node).is_none() => {
+                    self.rebuild_record(record)?
+                }
+                _ => {
+                    return Err(invalid(
+                        &path.pointer(),
+                        "an object written in place can only be edited or set to null",
+                    ));
+                }
+            },
+            _ => return self.new_slot(node, path),
+        };
+        Ok(ObjectValue::Record(Box::new(rebuilt)))
+    }
+
+    fn rebuild_reference(
+        &mut self,
+        id: i32,
+        node: &Value,
+        path: &Rc<Path<'a>>,
+    ) -> Result<ObjectValue> {
+        if let Some(target) = reference_target(node) {
+            return Ok(reference(target));
+        }
+        match self.graph.objects.get(&id) {
+            Some(Record::BinaryObjectString { .. }) => match node {
+                Value::String(s) if self.string_value(id) == Some(s) => Ok(reference(id)),
+                _ => self.new_slot(node, path),
+            },
+            Some(_) => match node {
+                Value::Object(_) | Value::Array(_) => Ok(reference(id)),
+                _ => self.new_slot(node, path),
+            },
+            None => Ok(reference(id)),
+        }
+    }
+
+    /// Builds a value for a slot that held no object before.
+    fn new_slot(&mut self, node: &Value, path: &Path) -> Result<ObjectValue> {
+        let record = match node {
+            Value::Null => Record::ObjectNull,
+            Value::String(s) => Record::BinaryObjectString {
+                object_id: self.fresh_id(),
+                value: s.clone(),
+            },
+            _ => match reference_target(node) {
+                Some(target) => Record::MemberReference { id_ref: target },
+                None => {
+                    return Err(invalid(
+                        &path.pointer(),
+                        "only null, strings and {\"$ref\": id} can be added in plain JSON",
+                    ));
+                }
+            },
+        };
+        Ok(ObjectValue::Record(Box::new(record)))
+    }
+}
+
+fn reference(id: i32) -> ObjectValue {
+    ObjectValue::Record(Box::new(Record::MemberReference { id_ref: id }))
+}
+
+/// Returns the id of a `{"$ref": id}` node.
+fn reference_target(node: &Value) -> Option<i32> {
+    let obj = node.as_object().filter(|obj| obj.len() == 1)?;
+    i32::try_from(obj.get("$ref")?.as_i64()?).ok()
+}
+
+/// Returns the number of nulls a null run record stands for.
+pub(crate) fn null_count(record: &Record) -> Option<usize> {
+    match record {
+        Record::ObjectNullMultiple(n) => Some(usize::try_from(n.null_count).unwrap_or(0)),
+        Record::ObjectNullMultiple256(n) => Some(n.null_count as usize),
+        _ => None,
+    }
+}
+
+fn primitive_elements(
+    node: &Value,
+    t: PrimitiveType,
+    path: &Rc<Path>,
+) -> Result<Vec<PrimitiveValue>> {
+    node.as_array()
+        .ok_or_else(|| invalid(&path.pointer(), "expected an array"))?
+        .iter()
+        .enumerate()
+        .map(|(i, v)| json_to_primitive(v, t, &path.index(i)))
+        .collect()
+}
+
+/// Serde helpers for the numbers plain JSON writes as text when a JSON
+/// number cannot hold them exactly: NaNs, infinities and integers beyond
+/// 2^53 - 1.
+///
+/// Use on `i64`, `u64`, `f32` and `f64` fields with
+/// `#[serde(with = "nrbf_parser::plain::number")]`.
+pub mod number {
+    use super::{json_to_primitive, primitive_to_json};
+    use crate::query::Path;
+    use crate::records::{PrimitiveType, PrimitiveValue};
+    use serde::de::Error as _;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use serde_json::Value;
+
+    /// A number that [`serialize`] and [`deserialize`] can handle.
+    pub trait Number: Sized {
+        const TYPE: PrimitiveType;
+        fn to_primitive(&self) -> PrimitiveValue;
+        fn from_primitive(value: PrimitiveValue) -> Option<Self>;
+    }
+
+    impl Number for i64 {
+        const TYPE: PrimitiveType = PrimitiveType::Int64;
+        fn to_primitive(&self) -> PrimitiveValue {
+            PrimitiveValue::Int64(*self)
+        }
+        fn from_primitive(value: PrimitiveValue) -> Option<Self> {
+            match value {
+                PrimitiveValue::Int64(i) => Some(i),
+                _ => None,
+            }
+        }
+    }
+
+    impl Number for u64 {
+        const TYPE: PrimitiveType = PrimitiveType::UInt64;
+        fn to_primitive(&self) -> PrimitiveValue {
+            PrimitiveValue::UInt64(*self)
+        }
+        fn from_primitive(value: PrimitiveValue) -> Option<Self> {
+            match value {
+                PrimitiveValue::UInt64(u) => Some(u),
+                _ => None,
+            }
+        }
+    }
+
+    impl Number for f32 {
+        const TYPE: PrimitiveType = PrimitiveType::Single;
+        fn to_primitive(&self) -> PrimitiveValue {
+            PrimitiveValue::Single(*self)
+        }
+        fn from_primitive(value: PrimitiveValue) -> Option<Self> {
+            match value {
+                PrimitiveValue::Single(f) => Some(f),
+                _ => None,
+            }
+        }
+    }
+
+    impl Number for f64 {
+        const TYPE: PrimitiveType = PrimitiveType::Double;
+        fn to_primitive(&self) -> PrimitiveValue {
+            PrimitiveValue::Double(*self)
+        }
+        fn from_primitive(value: PrimitiveValue) -> Option<Self> {
+            match value {
+                PrimitiveValue::Double(f) => Some(f),
+                _ => None,
+            }
+        }
+    }
+
+    pub fn serialize<T: Number, S: Serializer>(
+        value: &T,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        primitive_to_json(&value.to_primitive()).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, T: Number, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<T, D::Error> {
+        let value = Value::deserialize(deserializer)?;
+        json_to_primitive(&value, T::TYPE, &Path::root())
+            .ok()
+            .and_then(T::from_primitive)
+            .ok_or_else(|| {
+                D::Error::custom(format!("expected a {:?} value, found {}", T::TYPE, value))
+            })
+    }
+}
+
+/// A class member as plain JSON writes it: the object, or `{"$ref": id}`
+/// where the JSON refers to an object instead of nesting it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum RefOr<T> {
+    Ref {
+        #[serde(rename = "$ref")]
+        id: i32,
+    },
+    Value(T),
+}
+
+/// Serde helpers for boxed enum members, which plain JSON writes as
+/// `{"value__": n}`.
+///
+/// Use on `Option<T>` fields, where `T` serializes as the enum's integer,
+/// with `#[serde(with = "nrbf_parser::plain::boxed_enum")]`.
+pub mod boxed_enum {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    #[derive(Serialize, Deserialize)]
+    struct Boxed<T> {
+        #[serde(rename = "value__")]
+        value: T,
+    }
+
+    pub fn serialize<T: Serialize, S: Serializer>(
+        value: &Option<T>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        value
+            .as_ref()
+            .map(|value| Boxed { value })
+            .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, T: Deserialize<'de>, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<T>, D::Error> {
+        Ok(Option::<Boxed<T>>::deserialize(deserializer)?.map(|boxed| boxed.value))
+    }
+}
+
+/// Writes a primitive as a JSON value, using text for numbers JSON cannot
+/// carry exactly.
+fn primitive_to_json(value: &PrimitiveValue) -> Value {
+    match value {
+        PrimitiveValue::Boolean(b) => Value::Bool(*b),
+        PrimitiveValue::Null => Value::Null,
+        PrimitiveValue::Char(_) | PrimitiveValue::Decimal(_) | PrimitiveValue::String(_) => {
+            Value::String(value.to_text())
+        }
+        _ if value.needs_text() => Value::String(value.to_text()),
+        PrimitiveValue::Double(f) => json!(f),
+        PrimitiveValue::Single(f) => json!(f),
+        _ => serde_json::from_str(&value.to_text()).unwrap_or(Value::Null),
+    }
+}
+
+fn json_to_primitive(node: &Value, t: PrimitiveType, path: &Path) -> Result<PrimitiveValue> {
+    let value = match node {
+        Value::String(s) => PrimitiveValue::from_text(t, s),
+        Value::Bool(b) if t == PrimitiveType::Boolean => Some(PrimitiveValue::Boolean(*b)),
+        Value::Number(n)
+            if !matches!(
+                t,
+                PrimitiveType::Boolean
+                    | PrimitiveType::Char
+                    | PrimitiveType::Decimal
+                    | PrimitiveType::String
+            ) =>
+        {
+            PrimitiveValue::from_text(t, &n.to_string())
+        }
+        Value::Null if t == PrimitiveType::Null => Some(PrimitiveValue::Null),
+        _ => None,
+    };
+    value.ok_or_else(|| {
+        invalid(
+            &path.pointer(),
+            format!("expected a {:?} value, found {}", t, node),
+        )
+    })
+}
+
+/// Clears the values [`from_plain`] takes from the plain JSON.
+fn blank(record: &mut Record) {
+    match record {
+        Record::BinaryObjectString { value, .. } => value.clear(),
+        Record::MemberPrimitiveTyped { value, .. } => *value = zero(value.primitive_type()),
+        Record::ArraySinglePrimitive(a) => a.element_values.clear(),
+        _ => {}
+    }
+    for value in record.child_values_mut() {
+        match value {
+            ObjectValue::Primitive(p) => *p = zero(p.primitive_type()),
+            ObjectValue::Record(r) => blank(r),
+        }
+    }
+}
+
+fn zero(t: PrimitiveType) -> PrimitiveValue {
+    match t {
+        PrimitiveType::Boolean => PrimitiveValue::Boolean(false),
+        PrimitiveType::Byte => PrimitiveValue::Byte(0),
+        PrimitiveType::Char => PrimitiveValue::Char('\0'),
+        PrimitiveType::Decimal => PrimitiveValue::Decimal("0".repeat(32)),
+        PrimitiveType::Double => PrimitiveValue::Double(0.0),
+        PrimitiveType::Int16 => PrimitiveValue::Int16(0),
+        PrimitiveType::Int32 => PrimitiveValue::Int32(0),
+        PrimitiveType::Int64 => PrimitiveValue::Int64(0),
+        PrimitiveType::SByte => PrimitiveValue::SByte(0),
+        PrimitiveType::Single => PrimitiveValue::Single(0.0),
+        PrimitiveType::TimeSpan => PrimitiveValue::TimeSpan(0),
+        PrimitiveType::DateTime => PrimitiveValue::DateTime(0),
+        PrimitiveType::UInt16 => PrimitiveValue::UInt16(0),
+        PrimitiveType::UInt32 => PrimitiveValue::UInt32(0),
+        PrimitiveType::UInt64 => PrimitiveValue::UInt64(0),
+        PrimitiveType::String => PrimitiveValue::String(String::new()),
+        PrimitiveType::Null => PrimitiveValue::Null,
+    }
+}
+
+fn invalid(pointer: &str, message: impl Into<String>) -> Error {
+    Error::Plain {
+        pointer: pointer.to_string(),
+        message: message.into(),
+    }
+}
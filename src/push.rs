@@ -0,0 +1,119 @@
+// nrbf-parser - A high-performance MS-NRBF binary parser and encoder.
+// Copyright (C) 2026  driedpampas@proton.me
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! A sans-IO decoder for data that arrives in chunks.
+
+use crate::decoder::Decoder;
+use crate::error::{Error, Result};
+use crate::records::Record;
+use std::collections::HashMap;
+use std::io;
+
+/// A decoder that is fed bytes instead of reading them.
+///
+/// Bytes that do not yet form a complete record are kept until the next
+/// call to [`PushDecoder::feed`]. An incomplete record is parsed again from
+/// its start on every call, so feeding larger chunks is cheaper.
+pub struct PushDecoder {
+    decoder: Decoder<io::Empty>,
+    buffer: Vec<u8>,
+}
+
+impl Default for PushDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PushDecoder {
+    /// Creates a new push decoder.
+    pub fn new() -> Self {
+        Self {
+            decoder: Decoder::new(io::empty()),
+            buffer: Vec::new(),
+        }
+    }
+
+    /// Appends `data` to the stream and returns every record it completed.
+    pub fn feed(&mut self, data: &[u8]) -> Result<Vec<Record>> {
+        self.push(data);
+        let mut records = Vec::new();
+        while let Some(record) = self.try_decode()? {
+            records.push(record);
+        }
+        Ok(records)
+    }
+
+    /// Appends `data` to the buffered bytes without decoding them.
+    pub(crate) fn push(&mut self, data: &[u8]) {
+        self.buffer.extend_from_slice(data);
+    }
+
+    /// Checks that the stream did not stop in the middle of a record.
+    pub fn finish(&self) -> Result<()> {
+        if self.buffer.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::Io(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "stream ended in the middle of a record",
+            )))
+        }
+    }
+
+    /// Number of bytes held back for an incomplete record.
+    pub fn buffered(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Offset of the end of the last completed record.
+    pub fn offset(&self) -> usize {
+        self.decoder.offset
+    }
+
+    /// Registry of libraries by ID.
+    pub fn library_registry(&self) -> &HashMap<i32, String> {
+        &self.decoder.library_registry
+    }
+
+    /// Attempts to decode one record from the buffered bytes.
+    ///
+    /// Returns `Ok(None)` if the buffer holds only part of a record.
+    pub(crate) fn try_decode(&mut self) -> Result<Option<Record>> {
+        if self.buffer.is_empty() {
+            return Ok(None);
+        }
+        let parked = std::mem::replace(&mut self.decoder, Decoder::new(io::empty()));
+        let offset = parked.offset;
+        let (mut decoder, _) = parked.replace_reader(&self.buffer[..]);
+        let result = decoder.decode_next();
+        let (decoder, rest) = decoder.replace_reader(io::empty());
+        let consumed = self.buffer.len() - rest.len();
+        self.decoder = decoder;
+
+        match result {
+            Ok(record) => {
+                self.buffer.drain(..consumed);
+                Ok(record)
+            }
+            Err(Error::Io(e)) if e.kind() == io::ErrorKind::UnexpectedEof => {
+                self.decoder.offset = offset;
+                Ok(None)
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
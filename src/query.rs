@@ -0,0 +1,1001 @@
+// nrbf-parser - A high-performance MS-NRBF binary parser and encoder.
+// Copyright (C) 2026  driedpampas@proton.me
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! A path query language over the decoded object graph.
+//!
+//! A query starts at the root object, at an object id or at every object of
+//! a type, and then walks the graph one step at a time. `MemberReference`s
+//! are followed transparently, so a path reads the same whether an object
+//! is written inline or referenced.
+//!
+//! | Syntax | Selects |
+//! |---|---|
+//! | `PlayerData`, `$` | the root object; its class name is optional |
+//! | `#12` | the object with id 12 |
+//! | `//Game.Item`, `//*` | every object of a class, by full or short name, or of any class |
+//! | `.name`, `["name"]` | a member; `.Level` also finds `<Level>k__BackingField` |
+//! | `[3]`, `[-1]` | an array element, counting from the end if negative |
+//! | `[*]`, `.*` | every element or member |
+//! | `[?filter]` | the elements of an array, or the object itself, that pass |
+//!
+//! Filters compare a relative path (`itemId`, `@` for the value itself) to a
+//! number, string, `true`, `false` or `null` with `==`, `!=`, `<`, `<=`,
+//! `>`, `>=`, and combine with `&&`, `||`, `!` and parentheses. A path
+//! without a comparison tests that it leads to a non-null value.
+//!
+//! ```text
+//! PlayerData.inventory[*].itemId
+//! //Game.Item[?itemId >= 100 && name != "Sword"].name
+//! #12.scores[-1]
+//! ```
+
+use crate::codegen::backing_field_property;
+use crate::error::{Error, Result};
+use crate::interleaved::escape;
+use crate::plain::{Graph, null_count};
+use crate::records::*;
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::fmt::{self, Write};
+use std::rc::Rc;
+use std::str::FromStr;
+
+/// Runs `query` over a message.
+pub fn query<'a>(records: &'a [Record], query: &str) -> Result<Vec<Match<'a>>> {
+    Ok(query.parse::<Query>()?.evaluate(records))
+}
+
+/// A parsed query.
+#[derive(Debug, Clone)]
+pub struct Query {
+    start: Start,
+    steps: Vec<Step>,
+}
+
+/// A value selected by a query, with a path that selects it again.
+#[derive(Debug, Clone)]
+pub struct Match<'a> {
+    pub(crate) path: Rc<Path<'a>>,
+    pub node: Node<'a>,
+    /// Where the value is stored, unless it is an object reached directly.
+    pub(crate) slot: Option<Slot>,
+}
+
+impl Match<'_> {
+    /// Returns the path from the object the walk started at, e.g.
+    /// `#1.inventory[0].itemId`.
+    pub fn path(&self) -> String {
+        self.path.to_string()
+    }
+}
+
+/// The path of a match, linked to the path of the match it was reached
+/// from, so walking deep graphs does not copy every prefix.
+pub(crate) struct Path<'a> {
+    parent: Option<Rc<Path<'a>>>,
+    segment: Segment<'a>,
+}
+
+/// One step of a [`Path`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum Segment<'a> {
+    /// `$`, the root of a diff or merge.
+    Root,
+    Object(i32),
+    Member(&'a str),
+    Index(usize),
+}
+
+impl<'a> Path<'a> {
+    /// Returns the path `$`.
+    pub(crate) fn root() -> Rc<Self> {
+        Rc::new(Path {
+            parent: None,
+            segment: Segment::Root,
+        })
+    }
+
+    fn object(id: i32) -> Rc<Self> {
+        Rc::new(Path {
+            parent: None,
+            segment: Segment::Object(id),
+        })
+    }
+
+    fn child(self: &Rc<Self>, segment: Segment<'a>) -> Rc<Self> {
+        Rc::new(Path {
+            parent: Some(self.clone()),
+            segment,
+        })
+    }
+
+    /// Returns the path of a member of the value at this path.
+    pub(crate) fn member(self: &Rc<Self>, name: &'a str) -> Rc<Self> {
+        self.child(Segment::Member(name))
+    }
+
+    /// Returns the path of an element of the array at this path.
+    pub(crate) fn index(self: &Rc<Self>, index: usize) -> Rc<Self> {
+        self.child(Segment::Index(index))
+    }
+
+    /// Returns the last step of the path.
+    pub(crate) fn segment(&self) -> Segment<'a> {
+        self.segment
+    }
+
+    /// Formats the members and indices of the path as a JSON pointer.
+    pub(crate) fn pointer(&self) -> String {
+        let mut segments = vec![&self.segment];
+        let mut parent = self.parent.as_deref();
+        while let Some(path) = parent {
+            segments.push(&path.segment);
+            parent = path.parent.as_deref();
+        }
+        let mut pointer = String::new();
+        for segment in segments.into_iter().rev() {
+            match segment {
+                Segment::Root | Segment::Object(_) => {}
+                Segment::Member(name) => {
+                    pointer.push('/');
+                    pointer.push_str(&escape(name));
+                }
+                Segment::Index(index) => {
+                    let _ = write!(pointer, "/{}", index);
+                }
+            }
+        }
+        pointer
+    }
+}
+
+impl fmt::Display for Path<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut segments = vec![&self.segment];
+        let mut parent = self.parent.as_deref();
+        while let Some(path) = parent {
+            segments.push(&path.segment);
+            parent = path.parent.as_deref();
+        }
+        for segment in segments.into_iter().rev() {
+            match segment {
+                Segment::Root => write!(f, "$")?,
+                Segment::Object(id) => write!(f, "#{}", id)?,
+                Segment::Member(name) if !name.is_empty() && name.chars().all(is_name_char) => {
+                    write!(f, ".{}", name)?
+                }
+                Segment::Member(name) => write!(f, "[{:?}]", name)?,
+                Segment::Index(index) => write!(f, "[{}]", index)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Path<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.to_string())
+    }
+}
+
+impl Drop for Path<'_> {
+    /// Unlinks the parents one at a time, so dropping a deep path does not
+    /// recurse.
+    fn drop(&mut self) {
+        let mut parent = self.parent.take();
+        while let Some(path) = parent {
+            parent = match Rc::try_unwrap(path) {
+                Ok(mut path) => path.parent.take(),
+                Err(_) => None,
+            };
+        }
+    }
+}
+
+/// A member or element of a class or array record.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Slot {
+    /// The object id of the class or array.
+    pub(crate) container: i32,
+    /// The member index, or the element index with null runs expanded.
+    pub(crate) index: usize,
+}
+
+/// A value in the object graph, with references resolved.
+#[derive(Debug, Clone, Copy)]
+pub enum Node<'a> {
+    Null,
+    Primitive(&'a PrimitiveValue),
+    /// A class record, with the class info it was written with or, for a
+    /// `ClassWithId`, reuses.
+    Class(&'a Record, &'a ClassInfo),
+    /// An array or `BinaryObjectString` record.
+    Object(&'a Record),
+}
+
+impl<'a> Node<'a> {
+    /// Returns the text of a string value.
+    pub fn as_str(&self) -> Option<&'a str> {
+        match self {
+            Node::Primitive(PrimitiveValue::String(s)) => Some(s),
+            Node::Object(Record::BinaryObjectString { value, .. }) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Returns the record of a class, array or string.
+    pub fn record(&self) -> Option<&'a Record> {
+        match self {
+            Node::Class(record, _) | Node::Object(record) => Some(record),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Node<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(s) = self.as_str() {
+            return write!(f, "{:?}", s);
+        }
+        match self {
+            Node::Null | Node::Primitive(PrimitiveValue::Null) => f.write_str("null"),
+            Node::Primitive(p @ (PrimitiveValue::Char(_) | PrimitiveValue::Decimal(_))) => {
+                write!(f, "{:?}", p.to_text())
+            }
+            Node::Primitive(p) => f.write_str(&p.to_text()),
+            Node::Class(record, class) => {
+                write!(
+                    f,
+                    "{} #{}",
+                    class.name,
+                    record.object_id().unwrap_or_default()
+                )
+            }
+            Node::Object(record) => {
+                let id = record.object_id().unwrap_or_default();
+                match record {
+                    Record::ArraySinglePrimitive(a) => {
+                        write!(f, "{:?}[{}] #{}", a.primitive_type_enum, a.length, id)
+                    }
+                    Record::ArraySingleObject(a) => write!(f, "Object[{}] #{}", a.length, id),
+                    Record::ArraySingleString(a) => write!(f, "String[{}] #{}", a.length, id),
+                    Record::BinaryArray(a) => {
+                        let element = match &a.additional_type_info {
+                            AdditionalTypeInfo::Primitive(t) => format!("{:?}", t),
+                            AdditionalTypeInfo::SystemClass(name) => name.clone(),
+                            AdditionalTypeInfo::Class(c) => c.type_name.clone(),
+                            AdditionalTypeInfo::None => format!("{:?}", a.type_enum),
+                        };
+                        let lengths: Vec<String> =
+                            a.lengths.iter().map(|l| l.to_string()).collect();
+                        write!(f, "{}[{}] #{}", element, lengths.join(","), id)
+                    }
+                    _ => write!(f, "#{}", id),
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Start {
+    /// The root object, then member names; the leading names may spell the
+    /// root's class name.
+    Root(Vec<String>),
+    Id(i32),
+    /// Every object whose class is named by the leading names, then the
+    /// remaining names as members.
+    Types(Vec<String>),
+}
+
+#[derive(Debug, Clone)]
+enum Step {
+    Member(String),
+    Index(i64),
+    Children,
+    /// Objects reachable from the current value, as in [`Start::Types`];
+    /// no names means any object.
+    Descendants(Vec<String>),
+    Filter(Expr),
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    Or(Box<Expr>, Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Test(Vec<Step>, Option<(Op, Literal)>),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone)]
+enum Literal {
+    Null,
+    Bool(bool),
+    Int(i128),
+    Float(f64),
+    String(String),
+}
+
+impl FromStr for Query {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Parser { src: s, pos: 0 }.parse_query()
+    }
+}
+
+impl Query {
+    /// Parses a query.
+    pub fn parse(query: &str) -> Result<Self> {
+        query.parse()
+    }
+
+    /// Evaluates the query over a message, returning matches in graph order.
+    pub fn evaluate<'a>(&self, records: &'a [Record]) -> Vec<Match<'a>> {
+        let evaluator = Evaluator::new(records);
+        let mut matches = evaluator.start(&self.start);
+        for step in &self.steps {
+            matches = evaluator.step(step, matches);
+        }
+        matches
+    }
+}
+
+struct Parser<'s> {
+    src: &'s str,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn parse_query(mut self) -> Result<Query> {
+        self.skip_ws();
+        let start = if self.eat("//") {
+            Start::Types(self.parse_type()?)
+        } else if self.eat("#") {
+            let id = self.parse_int()?;
+            Start::Id(i32::try_from(id).map_err(|_| self.error("object id out of range"))?)
+        } else if self.eat("$") {
+            Start::Root(Vec::new())
+        } else {
+            Start::Root(self.parse_names()?)
+        };
+        let steps = self.parse_steps()?;
+        self.skip_ws();
+        if self.pos < self.src.len() {
+            return Err(self.error("unexpected character"));
+        }
+        Ok(Query { start, steps })
+    }
+
+    fn parse_steps(&mut self) -> Result<Vec<Step>> {
+        let mut steps = Vec::new();
+        loop {
+            self.skip_ws();
+            if self.eat("//") {
+                steps.push(Step::Descendants(self.parse_type()?));
+            } else if self.eat(".") {
+                if self.eat("*") {
+                    steps.push(Step::Children);
+                } else {
+                    steps.push(Step::Member(self.parse_name()?));
+                }
+            } else if self.eat("[") {
+                self.skip_ws();
+                let step = if self.eat("*") {
+                    Step::Children
+                } else if self.eat("?") {
+                    Step::Filter(self.parse_or()?)
+                } else if matches!(self.peek(), Some('"' | '\'')) {
+                    Step::Member(self.parse_quoted()?)
+                } else {
+                    Step::Index(self.parse_int()?)
+                };
+                self.skip_ws();
+                if !self.eat("]") {
+                    return Err(self.error("expected ']'"));
+                }
+                steps.push(step);
+            } else {
+                return Ok(steps);
+            }
+        }
+    }
+
+    /// Parses the type after `//`: names, or `*` (no names) for any object.
+    fn parse_type(&mut self) -> Result<Vec<String>> {
+        if self.eat("*") {
+            return Ok(Vec::new());
+        }
+        self.parse_names()
+    }
+
+    /// Parses dot-separated names, which may spell a type name.
+    fn parse_names(&mut self) -> Result<Vec<String>> {
+        let mut names = vec![self.parse_name()?];
+        while self.rest().starts_with('.')
+            && self.rest()[1..].starts_with(|c| is_name_char(c) || c == '"' || c == '\'')
+        {
+            self.pos += 1;
+            names.push(self.parse_name()?);
+        }
+        Ok(names)
+    }
+
+    fn parse_name(&mut self) -> Result<String> {
+        if matches!(self.peek(), Some('"' | '\'')) {
+            return self.parse_quoted();
+        }
+        let len = self
+            .rest()
+            .find(|c| !is_name_char(c))
+            .unwrap_or(self.rest().len());
+        if len == 0 {
+            return Err(self.error("expected a name"));
+        }
+        let name = self.rest()[..len].to_string();
+        self.pos += len;
+        Ok(name)
+    }
+
+    fn parse_quoted(&mut self) -> Result<String> {
+        let start = self.pos;
+        let Some(quote) = self.peek() else {
+            return Err(self.error("expected a string"));
+        };
+        self.pos += 1;
+        let mut value = String::new();
+        let mut chars = self.rest().char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '\\' => match chars.next() {
+                    Some((_, escaped)) => value.push(escaped),
+                    None => break,
+                },
+                _ if c == quote => {
+                    self.pos += i + 1;
+                    return Ok(value);
+                }
+                _ => value.push(c),
+            }
+        }
+        self.pos = start;
+        Err(self.error("unterminated string"))
+    }
+
+    fn parse_int(&mut self) -> Result<i64> {
+        let rest = self.rest();
+        let len = rest
+            .char_indices()
+            .find(|&(i, c)| !(c.is_ascii_digit() || (i == 0 && c == '-')))
+            .map_or(rest.len(), |(i, _)| i);
+        let value = rest[..len]
+            .parse()
+            .map_err(|_| self.error("expected an integer"))?;
+        self.pos += len;
+        Ok(value)
+    }
+
+    fn parse_or(&mut self) -> Result<Expr> {
+        let mut expr = self.parse_and()?;
+        loop {
+            self.skip_ws();
+            if !self.eat("||") {
+                return Ok(expr);
+            }
+            expr = Expr::Or(Box::new(expr), Box::new(self.parse_and()?));
+        }
+    }
+
+    fn parse_and(&mut self) -> Result<Expr> {
+        let mut expr = self.parse_unary()?;
+        loop {
+            self.skip_ws();
+            if !self.eat("&&") {
+                return Ok(expr);
+            }
+            expr = Expr::And(Box::new(expr), Box::new(self.parse_unary()?));
+        }
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr> {
+        self.skip_ws();
+        if self.eat("!") {
+            return Ok(Expr::Not(Box::new(self.parse_unary()?)));
+        }
+        if self.eat("(") {
+            let expr = self.parse_or()?;
+            self.skip_ws();
+            if !self.eat(")") {
+                return Err(self.error("expected ')'"));
+            }
+            return Ok(expr);
+        }
+
+        let path = if self.eat("@") {
+            self.parse_steps()?
+        } else {
+            let mut path = vec![Step::Member(self.parse_name()?)];
+            path.extend(self.parse_steps()?);
+            path
+        };
+        self.skip_ws();
+        let ops = [
+            ("==", Op::Eq),
+            ("!=", Op::Ne),
+            ("<=", Op::Le),
+            (">=", Op::Ge),
+            ("<", Op::Lt),
+            (">", Op::Gt),
+        ];
+        let Some(op) = ops
+            .iter()
+            .find(|(token, _)| self.eat(token))
+            .map(|(_, op)| *op)
+        else {
+            return Ok(Expr::Test(path, None));
+        };
+        self.skip_ws();
+        Ok(Expr::Test(path, Some((op, self.parse_literal()?))))
+    }
+
+    fn parse_literal(&mut self) -> Result<Literal> {
+        if matches!(self.peek(), Some('"' | '\'')) {
+            return Ok(Literal::String(self.parse_quoted()?));
+        }
+        let rest = self.rest();
+        let len = rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '-' | '+' | '.')))
+            .unwrap_or(rest.len());
+        let token = &rest[..len];
+        let literal = match token {
+            "null" => Literal::Null,
+            "true" => Literal::Bool(true),
+            "false" => Literal::Bool(false),
+            _ => match (token.parse(), token.parse()) {
+                (Ok(i), _) => Literal::Int(i),
+                (_, Ok(f)) => Literal::Float(f),
+                _ => return Err(self.error("expected a number, string, true, false or null")),
+            },
+        };
+        self.pos += len;
+        Ok(literal)
+    }
+
+    fn rest(&self) -> &str {
+        &self.src[self.pos..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        let found = self.rest().starts_with(token);
+        if found {
+            self.pos += token.len();
+        }
+        found
+    }
+
+    fn skip_ws(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn error(&self, message: &str) -> Error {
+        Error::Query {
+            offset: self.pos,
+            message: message.to_string(),
+        }
+    }
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | '`' | '+')
+}
+
+/// Walks the object graph of a message.
+pub(crate) struct Evaluator<'a> {
+    graph: Graph<'a>,
+    records: &'a [Record],
+}
+
+impl<'a> Evaluator<'a> {
+    pub(crate) fn new(records: &'a [Record]) -> Self {
+        Self {
+            graph: Graph::new(records),
+            records,
+        }
+    }
+
+    fn start(&self, start: &Start) -> Vec<Match<'a>> {
+        match start {
+            Start::Root(names) => {
+                let Some(root) = self.root() else {
+                    return Vec::new();
+                };
+                let Some(record) = root.node.record() else {
+                    return vec![root];
+                };
+                let skip = (1..=names.len())
+                    .rev()
+                    .find(|&n| self.is_type(record, &names[..n]))
+                    .unwrap_or(0);
+                self.members(vec![root], &names[skip..])
+            }
+            Start::Id(id) => match self.graph.objects.get(id) {
+                Some(record) => vec![Match {
+                    path: Path::object(*id),
+                    node: self.object(record),
+                    slot: None,
+                }],
+                None => Vec::new(),
+            },
+            Start::Types(names) => {
+                let mut objects = Vec::new();
+                let mut seen = HashSet::new();
+                if let Some(root) = self.root() {
+                    seen.extend(root_id(&root));
+                    objects.push(root.clone());
+                    self.descendants(&root, &mut objects, &mut seen);
+                }
+                let mut unreached = Vec::new();
+                collect_objects(self.records, &mut unreached);
+                for record in unreached {
+                    if let Some(id) = record.object_id()
+                        && seen.insert(id)
+                    {
+                        objects.push(Match {
+                            path: Path::object(id),
+                            node: self.object(record),
+                            slot: None,
+                        });
+                    }
+                }
+                self.of_type(objects, names)
+            }
+        }
+    }
+
+    fn step(&self, step: &Step, matches: Vec<Match<'a>>) -> Vec<Match<'a>> {
+        match step {
+            Step::Member(name) => self.members(matches, std::slice::from_ref(name)),
+            Step::Index(index) => matches
+                .iter()
+                .filter_map(|m| {
+                    let elements = self.elements(m)?;
+                    let len = elements.len() as i64;
+                    let i = if *index < 0 { len + index } else { *index };
+                    usize::try_from(i)
+                        .ok()
+                        .and_then(|i| elements.into_iter().nth(i))
+                })
+                .collect(),
+            Step::Children => matches.iter().flat_map(|m| self.children(m)).collect(),
+            Step::Descendants(names) => {
+                let mut objects = Vec::new();
+                for m in &matches {
+                    let mut seen: HashSet<i32> = root_id(m).into_iter().collect();
+                    self.descendants(m, &mut objects, &mut seen);
+                }
+                self.of_type(objects, names)
+            }
+            Step::Filter(expr) => matches
+                .into_iter()
+                .flat_map(|m| match self.elements(&m) {
+                    Some(elements) => elements,
+                    None => vec![m],
+                })
+                .filter(|m| self.test(expr, m))
+                .collect(),
+        }
+    }
+
+    pub(crate) fn root(&self) -> Option<Match<'a>> {
+        let root_id = self.records.iter().find_map(|record| match record {
+            Record::SerializationHeader(h) => Some(h.root_id),
+            _ => None,
+        })?;
+        let record = self.graph.objects.get(&root_id)?;
+        Some(Match {
+            path: Path::object(root_id),
+            node: self.object(record),
+            slot: None,
+        })
+    }
+
+    /// Keeps the objects whose class the leading `names` spell, and walks
+    /// the remaining names as members.
+    fn of_type(&self, objects: Vec<Match<'a>>, names: &[String]) -> Vec<Match<'a>> {
+        objects
+            .into_iter()
+            .flat_map(|m| {
+                let Some(record) = m.node.record() else {
+                    return Vec::new();
+                };
+                if names.is_empty() {
+                    return vec![m];
+                }
+                match (1..=names.len())
+                    .rev()
+                    .find(|&n| self.is_type(record, &names[..n]))
+                {
+                    Some(n) => self.members(vec![m], &names[n..]),
+                    None => Vec::new(),
+                }
+            })
+            .collect()
+    }
+
+    /// Returns whether `names`, joined with dots, is the full or short name
+    /// of the record's class, ignoring generic arguments.
+    fn is_type(&self, record: &Record, names: &[String]) -> bool {
+        let class = match record {
+            Record::BinaryObjectString { .. } => "System.String",
+            _ => match self.object(record) {
+                Node::Class(_, class) => class.name.as_str(),
+                _ => return false,
+            },
+        };
+        let wanted = names.join(".");
+        [class, class.split('[').next().unwrap_or(class)]
+            .iter()
+            .any(|name| {
+                name.strip_suffix(wanted.as_str())
+                    .is_some_and(|prefix| prefix.is_empty() || prefix.ends_with(['.', '+']))
+            })
+    }
+
+    fn members(&self, mut matches: Vec<Match<'a>>, names: &[String]) -> Vec<Match<'a>> {
+        for name in names {
+            matches = matches
+                .iter()
+                .filter_map(|m| {
+                    let members = self.children(m);
+                    let names = self.member_names(m);
+                    let index = names.iter().position(|n| n == name).or_else(|| {
+                        names
+                            .iter()
+                            .position(|n| backing_field_property(n) == Some(name))
+                    })?;
+                    members.into_iter().nth(index)
+                })
+                .collect();
+        }
+        matches
+    }
+
+    pub(crate) fn member_names(&self, m: &Match<'a>) -> &'a [String] {
+        match m.node {
+            Node::Class(_, class) => &class.member_names,
+            _ => &[],
+        }
+    }
+
+    /// Returns the members of a class or the elements of an array.
+    pub(crate) fn children(&self, m: &Match<'a>) -> Vec<Match<'a>> {
+        if let Some(elements) = self.elements(m) {
+            return elements;
+        }
+        let Some(record) = m.node.record() else {
+            return Vec::new();
+        };
+        let container = record.object_id().unwrap_or_default();
+        self.member_names(m)
+            .iter()
+            .zip(record.child_values())
+            .enumerate()
+            .map(|(index, (name, value))| Match {
+                path: m.path.child(Segment::Member(name)),
+                node: self.resolve(value),
+                slot: Some(Slot { container, index }),
+            })
+            .collect()
+    }
+
+    pub(crate) fn elements(&self, m: &Match<'a>) -> Option<Vec<Match<'a>>> {
+        let record = m.node.record()?;
+        let nodes: Vec<Node<'a>> = match record {
+            Record::ArraySinglePrimitive(a) => {
+                a.element_values.iter().map(Node::Primitive).collect()
+            }
+            Record::BinaryArray(_)
+            | Record::ArraySingleObject(_)
+            | Record::ArraySingleString(_) => {
+                let mut nodes = Vec::new();
+                for value in record.child_values() {
+                    match value {
+                        ObjectValue::Record(r) if null_count(r).is_some() => {
+                            nodes.extend(std::iter::repeat_n(Node::Null, null_count(r)?))
+                        }
+                        _ => nodes.push(self.resolve(value)),
+                    }
+                }
+                nodes
+            }
+            _ => return None,
+        };
+        Some(
+            nodes
+                .into_iter()
+                .enumerate()
+                .map(|(index, node)| Match {
+                    path: m.path.child(Segment::Index(index)),
+                    node,
+                    slot: Some(Slot {
+                        container: record.object_id().unwrap_or_default(),
+                        index,
+                    }),
+                })
+                .collect(),
+        )
+    }
+
+    /// Appends the objects reachable from `from` in depth-first order, each
+    /// with the first path found to it.
+    fn descendants(&self, from: &Match<'a>, out: &mut Vec<Match<'a>>, seen: &mut HashSet<i32>) {
+        let mut stack = vec![from.clone()];
+        while let Some(m) = stack.pop() {
+            let children: Vec<Match<'a>> = self
+                .children(&m)
+                .into_iter()
+                .filter(|child| match child.node {
+                    Node::Class(record, _) | Node::Object(record) => {
+                        record.object_id().is_none_or(|id| seen.insert(id))
+                    }
+                    _ => false,
+                })
+                .collect();
+            out.extend(children.iter().cloned());
+            stack.extend(children.into_iter().rev());
+        }
+    }
+
+    /// Wraps an object record, finding the class info of a class.
+    fn object(&self, record: &'a Record) -> Node<'a> {
+        let class = match record {
+            Record::ClassWithId(c) => self
+                .graph
+                .objects
+                .get(&c.metadata_id)
+                .and_then(|metadata| metadata.class_info()),
+            _ => record.class_info(),
+        };
+        match class {
+            Some(class) => Node::Class(record, class),
+            None => Node::Object(record),
+        }
+    }
+
+    fn resolve(&self, value: &'a ObjectValue) -> Node<'a> {
+        match value {
+            ObjectValue::Primitive(p) => Node::Primitive(p),
+            ObjectValue::Record(record) => match record.as_ref() {
+                Record::MemberReference { id_ref } => self
+                    .graph
+                    .objects
+                    .get(id_ref)
+                    .map_or(Node::Null, |target| self.object(target)),
+                Record::MemberPrimitiveTyped { value, .. } => Node::Primitive(value),
+                Record::ObjectNull
+                | Record::ObjectNullMultiple(_)
+                | Record::ObjectNullMultiple256(_) => Node::Null,
+                record => self.object(record),
+            },
+        }
+    }
+
+    fn test(&self, expr: &Expr, m: &Match<'a>) -> bool {
+        match expr {
+            Expr::Or(a, b) => self.test(a, m) || self.test(b, m),
+            Expr::And(a, b) => self.test(a, m) && self.test(b, m),
+            Expr::Not(e) => !self.test(e, m),
+            Expr::Test(path, comparison) => {
+                let mut found = vec![m.clone()];
+                for step in path {
+                    found = self.step(step, found);
+                }
+                found.iter().any(|found| match comparison {
+                    None => !matches!(
+                        found.node,
+                        Node::Null | Node::Primitive(PrimitiveValue::Null)
+                    ),
+                    Some((op, literal)) => compare(found.node, *op, literal),
+                })
+            }
+        }
+    }
+}
+
+fn root_id(m: &Match) -> Option<i32> {
+    m.node.record()?.object_id()
+}
+
+/// Collects every object record in stream order, nested ones included.
+fn collect_objects<'a>(records: &'a [Record], out: &mut Vec<&'a Record>) {
+    for record in records {
+        if record.object_id().is_some() {
+            out.push(record);
+        }
+        for value in record.child_values() {
+            if let ObjectValue::Record(r) = value {
+                collect_objects(std::slice::from_ref(r.as_ref()), out);
+            }
+        }
+    }
+}
+
+/// A value reduced to what filters compare.
+enum Scalar<'a> {
+    Null,
+    Bool(bool),
+    Int(i128),
+    Float(f64),
+    Str(std::borrow::Cow<'a, str>),
+}
+
+fn scalar<'a>(node: Node<'a>) -> Option<Scalar<'a>> {
+    if let Some(s) = node.as_str() {
+        return Some(Scalar::Str(s.into()));
+    }
+    let Node::Primitive(p) = node else {
+        return matches!(node, Node::Null).then_some(Scalar::Null);
+    };
+    Some(match p {
+        PrimitiveValue::Null => Scalar::Null,
+        PrimitiveValue::Boolean(b) => Scalar::Bool(*b),
+        PrimitiveValue::Char(_) | PrimitiveValue::Decimal(_) | PrimitiveValue::String(_) => {
+            Scalar::Str(p.to_text().into())
+        }
+        PrimitiveValue::Double(f) => Scalar::Float(*f),
+        PrimitiveValue::Single(f) => Scalar::Float(f64::from(*f)),
+        _ => Scalar::Int(p.to_text().parse().ok()?),
+    })
+}
+
+fn compare(node: Node, op: Op, literal: &Literal) -> bool {
+    let Some(value) = scalar(node) else {
+        return matches!(op, Op::Ne);
+    };
+    let ordering = match (&value, literal) {
+        (Scalar::Null, Literal::Null) => Some(Ordering::Equal),
+        (Scalar::Bool(a), Literal::Bool(b)) => Some(a.cmp(b)),
+        (Scalar::Int(a), Literal::Int(b)) => Some(a.cmp(b)),
+        (Scalar::Int(a), Literal::Float(b)) => (*a as f64).partial_cmp(b),
+        (Scalar::Float(a), Literal::Int(b)) => a.partial_cmp(&(*b as f64)),
+        (Scalar::Float(a), Literal::Float(b)) => a.partial_cmp(b),
+        (Scalar::Str(a), Literal::String(b)) => Some(a.as_ref().cmp(b.as_str())),
+        _ => None,
+    };
+    match op {
+        Op::Eq => ordering == Some(Ordering::Equal),
+        Op::Ne => ordering != Some(Ordering::Equal),
+        Op::Lt => ordering == Some(Ordering::Less),
+        Op::Le => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+        Op::Gt => ordering == Some(Ordering::Greater),
+        Op::Ge => matches!(ordering, Some(Ordering::Greater | Ordering::Equal)),
+    }
+}
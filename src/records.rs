@@ -16,8 +16,9 @@
 
 use crate::error::Result;
 use serde::{Deserialize, Serialize};
+use std::hash::{Hash, Hasher};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum RecordType {
     SerializedStreamHeader = 0,
     ClassWithId = 1,
@@ -71,7 +72,32 @@ impl TryFrom<u8> for RecordType {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+impl From<&Record> for RecordType {
+    fn from(record: &Record) -> Self {
+        match record {
+            Record::SerializationHeader(_) => RecordType::SerializedStreamHeader,
+            Record::BinaryLibrary(_) => RecordType::BinaryLibrary,
+            Record::ClassWithMembersAndTypes(_) => RecordType::ClassWithMembersAndTypes,
+            Record::SystemClassWithMembersAndTypes(_) => RecordType::SystemClassWithMembersAndTypes,
+            Record::SystemClassWithMembers(_) => RecordType::SystemClassWithMembers,
+            Record::ClassWithMembers(_) => RecordType::ClassWithMembers,
+            Record::ClassWithId(_) => RecordType::ClassWithId,
+            Record::BinaryObjectString { .. } => RecordType::BinaryObjectString,
+            Record::BinaryArray(_) => RecordType::BinaryArray,
+            Record::ArraySingleObject(_) => RecordType::ArraySingleObject,
+            Record::ArraySinglePrimitive(_) => RecordType::ArraySinglePrimitive,
+            Record::ArraySingleString(_) => RecordType::ArraySingleString,
+            Record::MemberPrimitiveTyped { .. } => RecordType::MemberPrimitiveTyped,
+            Record::MemberReference { .. } => RecordType::MemberReference,
+            Record::ObjectNull => RecordType::ObjectNull,
+            Record::ObjectNullMultiple(_) => RecordType::ObjectNullMultiple,
+            Record::ObjectNullMultiple256(_) => RecordType::ObjectNullMultiple256,
+            Record::MessageEnd => RecordType::MessageEnd,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum BinaryType {
     Primitive = 0,
     String = 1,
@@ -101,7 +127,7 @@ impl TryFrom<u8> for BinaryType {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum PrimitiveType {
     Boolean = 1,
     Byte = 2,
@@ -149,7 +175,7 @@ impl TryFrom<u8> for PrimitiveType {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct SerializationHeader {
     pub root_id: i32,
     pub header_id: i32,
@@ -157,13 +183,13 @@ pub struct SerializationHeader {
     pub minor_version: i32,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct BinaryLibrary {
     pub library_id: i32,
     pub library_name: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct ClassInfo {
     pub object_id: i32,
     pub name: String,
@@ -171,7 +197,7 @@ pub struct ClassInfo {
     pub member_names: Vec<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct ClassWithMembersAndTypes {
     pub class_info: ClassInfo,
     pub member_type_info: MemberTypeInfo,
@@ -179,20 +205,33 @@ pub struct ClassWithMembersAndTypes {
     pub member_values: Vec<ObjectValue>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct SystemClassWithMembersAndTypes {
     pub class_info: ClassInfo,
     pub member_type_info: MemberTypeInfo,
     pub member_values: Vec<ObjectValue>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct MemberTypeInfo {
     pub binary_type_enums: Vec<BinaryType>,
     pub additional_infos: Vec<AdditionalTypeInfo>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl MemberTypeInfo {
+    /// Returns the primitive type of member `index` if it is a primitive slot.
+    pub(crate) fn primitive_slot(&self, index: usize) -> Option<PrimitiveType> {
+        match (
+            self.binary_type_enums.get(index)?,
+            self.additional_infos.get(index)?,
+        ) {
+            (BinaryType::Primitive, AdditionalTypeInfo::Primitive(p)) => Some(*p),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum AdditionalTypeInfo {
     Primitive(PrimitiveType),
     SystemClass(String),
@@ -200,13 +239,13 @@ pub enum AdditionalTypeInfo {
     None,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct ClassTypeInfo {
     pub type_name: String,
     pub library_id: i32,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum ObjectValue {
     Primitive(PrimitiveValue),
     Record(Box<Record>),
@@ -233,36 +272,192 @@ pub enum PrimitiveValue {
     Null,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Largest integer magnitude a JSON number read as a double holds exactly.
+const MAX_SAFE_INTEGER: u64 = (1 << 53) - 1;
+
+impl PrimitiveValue {
+    /// Returns true if a JSON number cannot carry this value exactly.
+    pub(crate) fn needs_text(&self) -> bool {
+        match self {
+            PrimitiveValue::Double(f) => !f.is_finite(),
+            PrimitiveValue::Single(f) => !f.is_finite(),
+            PrimitiveValue::Int64(i) | PrimitiveValue::TimeSpan(i) => {
+                i.unsigned_abs() > MAX_SAFE_INTEGER
+            }
+            PrimitiveValue::UInt64(u) | PrimitiveValue::DateTime(u) => *u > MAX_SAFE_INTEGER,
+            _ => false,
+        }
+    }
+
+    /// Formats the value as text that [`PrimitiveValue::from_text`] reads back
+    /// exactly. NaNs are written as their bit pattern, e.g. `0xffc00000`.
+    pub(crate) fn to_text(&self) -> String {
+        match self {
+            PrimitiveValue::Boolean(b) => b.to_string(),
+            PrimitiveValue::Byte(b) => b.to_string(),
+            PrimitiveValue::Char(c) => c.to_string(),
+            PrimitiveValue::Decimal(s) | PrimitiveValue::String(s) => s.clone(),
+            PrimitiveValue::Double(f) if f.is_nan() => format!("0x{:016x}", f.to_bits()),
+            PrimitiveValue::Double(f) => f.to_string(),
+            PrimitiveValue::Int16(i) => i.to_string(),
+            PrimitiveValue::Int32(i) => i.to_string(),
+            PrimitiveValue::Int64(i) | PrimitiveValue::TimeSpan(i) => i.to_string(),
+            PrimitiveValue::SByte(i) => i.to_string(),
+            PrimitiveValue::Single(f) if f.is_nan() => format!("0x{:08x}", f.to_bits()),
+            PrimitiveValue::Single(f) => f.to_string(),
+            PrimitiveValue::UInt16(u) => u.to_string(),
+            PrimitiveValue::UInt32(u) => u.to_string(),
+            PrimitiveValue::UInt64(u) | PrimitiveValue::DateTime(u) => u.to_string(),
+            PrimitiveValue::Null => String::new(),
+        }
+    }
+
+    /// Parses text written by [`PrimitiveValue::to_text`] as a value of type `t`.
+    pub(crate) fn from_text(t: PrimitiveType, text: &str) -> Option<Self> {
+        fn bits(text: &str) -> Option<u64> {
+            u64::from_str_radix(text.strip_prefix("0x")?, 16).ok()
+        }
+        Some(match t {
+            PrimitiveType::Boolean => PrimitiveValue::Boolean(text.parse().ok()?),
+            PrimitiveType::Byte => PrimitiveValue::Byte(text.parse().ok()?),
+            PrimitiveType::Char => {
+                let mut chars = text.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => PrimitiveValue::Char(c),
+                    _ => return None,
+                }
+            }
+            PrimitiveType::Decimal => PrimitiveValue::Decimal(text.to_string()),
+            PrimitiveType::Double => PrimitiveValue::Double(match bits(text) {
+                Some(bits) => f64::from_bits(bits),
+                None => text.parse().ok()?,
+            }),
+            PrimitiveType::Int16 => PrimitiveValue::Int16(text.parse().ok()?),
+            PrimitiveType::Int32 => PrimitiveValue::Int32(text.parse().ok()?),
+            PrimitiveType::Int64 => PrimitiveValue::Int64(text.parse().ok()?),
+            PrimitiveType::SByte => PrimitiveValue::SByte(text.parse().ok()?),
+            PrimitiveType::Single => PrimitiveValue::Single(match bits(text) {
+                Some(bits) => f32::from_bits(u32::try_from(bits).ok()?),
+                None => text.parse().ok()?,
+            }),
+            PrimitiveType::TimeSpan => PrimitiveValue::TimeSpan(text.parse().ok()?),
+            PrimitiveType::DateTime => PrimitiveValue::DateTime(text.parse().ok()?),
+            PrimitiveType::UInt16 => PrimitiveValue::UInt16(text.parse().ok()?),
+            PrimitiveType::UInt32 => PrimitiveValue::UInt32(text.parse().ok()?),
+            PrimitiveType::UInt64 => PrimitiveValue::UInt64(text.parse().ok()?),
+            PrimitiveType::String => PrimitiveValue::String(text.to_string()),
+            PrimitiveType::Null => PrimitiveValue::Null,
+        })
+    }
+
+    /// Returns the primitive type this value is encoded as.
+    pub fn primitive_type(&self) -> PrimitiveType {
+        match self {
+            PrimitiveValue::Boolean(_) => PrimitiveType::Boolean,
+            PrimitiveValue::Byte(_) => PrimitiveType::Byte,
+            PrimitiveValue::Char(_) => PrimitiveType::Char,
+            PrimitiveValue::Decimal(_) => PrimitiveType::Decimal,
+            PrimitiveValue::Double(_) => PrimitiveType::Double,
+            PrimitiveValue::Int16(_) => PrimitiveType::Int16,
+            PrimitiveValue::Int32(_) => PrimitiveType::Int32,
+            PrimitiveValue::Int64(_) => PrimitiveType::Int64,
+            PrimitiveValue::SByte(_) => PrimitiveType::SByte,
+            PrimitiveValue::Single(_) => PrimitiveType::Single,
+            PrimitiveValue::TimeSpan(_) => PrimitiveType::TimeSpan,
+            PrimitiveValue::DateTime(_) => PrimitiveType::DateTime,
+            PrimitiveValue::UInt16(_) => PrimitiveType::UInt16,
+            PrimitiveValue::UInt32(_) => PrimitiveType::UInt32,
+            PrimitiveValue::UInt64(_) => PrimitiveType::UInt64,
+            PrimitiveValue::String(_) => PrimitiveType::String,
+            PrimitiveValue::Null => PrimitiveType::Null,
+        }
+    }
+}
+
+/// Floats compare by bit pattern, so a NaN equals itself and `0.0` differs
+/// from `-0.0`, as the encoder would write them.
+impl PartialEq for PrimitiveValue {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (PrimitiveValue::Boolean(a), PrimitiveValue::Boolean(b)) => a == b,
+            (PrimitiveValue::Byte(a), PrimitiveValue::Byte(b)) => a == b,
+            (PrimitiveValue::Char(a), PrimitiveValue::Char(b)) => a == b,
+            (PrimitiveValue::Decimal(a), PrimitiveValue::Decimal(b)) => a == b,
+            (PrimitiveValue::Double(a), PrimitiveValue::Double(b)) => a.to_bits() == b.to_bits(),
+            (PrimitiveValue::Int16(a), PrimitiveValue::Int16(b)) => a == b,
+            (PrimitiveValue::Int32(a), PrimitiveValue::Int32(b)) => a == b,
+            (PrimitiveValue::Int64(a), PrimitiveValue::Int64(b)) => a == b,
+            (PrimitiveValue::SByte(a), PrimitiveValue::SByte(b)) => a == b,
+            (PrimitiveValue::Single(a), PrimitiveValue::Single(b)) => a.to_bits() == b.to_bits(),
+            (PrimitiveValue::TimeSpan(a), PrimitiveValue::TimeSpan(b)) => a == b,
+            (PrimitiveValue::DateTime(a), PrimitiveValue::DateTime(b)) => a == b,
+            (PrimitiveValue::UInt16(a), PrimitiveValue::UInt16(b)) => a == b,
+            (PrimitiveValue::UInt32(a), PrimitiveValue::UInt32(b)) => a == b,
+            (PrimitiveValue::UInt64(a), PrimitiveValue::UInt64(b)) => a == b,
+            (PrimitiveValue::String(a), PrimitiveValue::String(b)) => a == b,
+            (PrimitiveValue::Null, PrimitiveValue::Null) => true,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for PrimitiveValue {}
+
+impl Hash for PrimitiveValue {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match self {
+            PrimitiveValue::Boolean(v) => v.hash(state),
+            PrimitiveValue::Byte(v) => v.hash(state),
+            PrimitiveValue::Char(v) => v.hash(state),
+            PrimitiveValue::Decimal(v) => v.hash(state),
+            PrimitiveValue::Double(v) => v.to_bits().hash(state),
+            PrimitiveValue::Int16(v) => v.hash(state),
+            PrimitiveValue::Int32(v) => v.hash(state),
+            PrimitiveValue::Int64(v) => v.hash(state),
+            PrimitiveValue::SByte(v) => v.hash(state),
+            PrimitiveValue::Single(v) => v.to_bits().hash(state),
+            PrimitiveValue::TimeSpan(v) => v.hash(state),
+            PrimitiveValue::DateTime(v) => v.hash(state),
+            PrimitiveValue::UInt16(v) => v.hash(state),
+            PrimitiveValue::UInt32(v) => v.hash(state),
+            PrimitiveValue::UInt64(v) => v.hash(state),
+            PrimitiveValue::String(v) => v.hash(state),
+            PrimitiveValue::Null => {}
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct ValueWithCode {
     pub primitive_type_enum: PrimitiveType,
     pub value: PrimitiveValue,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct SystemClassWithMembers {
     pub class_info: ClassInfo,
     pub member_values: Vec<ObjectValue>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct ClassWithMembers {
     pub class_info: ClassInfo,
     pub library_id: i32,
     pub member_values: Vec<ObjectValue>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct ObjectNullMultiple {
     pub null_count: i32,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct ObjectNullMultiple256 {
     pub null_count: u8,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct BinaryArray {
     pub object_id: i32,
     pub binary_array_type_enum: u8, // BinaryArrayTypeEnumeration
@@ -274,14 +469,14 @@ pub struct BinaryArray {
     pub element_values: Vec<ObjectValue>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct ArraySingleObject {
     pub object_id: i32,
     pub length: i32,
     pub element_values: Vec<ObjectValue>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct ArraySinglePrimitive {
     pub object_id: i32,
     pub length: i32,
@@ -289,21 +484,21 @@ pub struct ArraySinglePrimitive {
     pub element_values: Vec<PrimitiveValue>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct ArraySingleString {
     pub object_id: i32,
     pub length: i32,
     pub element_values: Vec<ObjectValue>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct ClassWithId {
     pub object_id: i32,
     pub metadata_id: i32,
     pub member_values: Vec<ObjectValue>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Record {
     SerializationHeader(SerializationHeader),
     BinaryLibrary(BinaryLibrary),
@@ -332,3 +527,69 @@ pub enum Record {
     ObjectNullMultiple256(ObjectNullMultiple256),
     MessageEnd,
 }
+
+impl Record {
+    /// Returns the object id defined by this record, if it defines one.
+    pub fn object_id(&self) -> Option<i32> {
+        match self {
+            Record::ClassWithMembersAndTypes(c) => Some(c.class_info.object_id),
+            Record::SystemClassWithMembersAndTypes(c) => Some(c.class_info.object_id),
+            Record::SystemClassWithMembers(c) => Some(c.class_info.object_id),
+            Record::ClassWithMembers(c) => Some(c.class_info.object_id),
+            Record::ClassWithId(c) => Some(c.object_id),
+            Record::BinaryObjectString { object_id, .. } => Some(*object_id),
+            Record::BinaryArray(a) => Some(a.object_id),
+            Record::ArraySingleObject(a) => Some(a.object_id),
+            Record::ArraySinglePrimitive(a) => Some(a.object_id),
+            Record::ArraySingleString(a) => Some(a.object_id),
+            _ => None,
+        }
+    }
+
+    /// Returns the class info of records that define class metadata.
+    pub fn class_info(&self) -> Option<&ClassInfo> {
+        match self {
+            Record::ClassWithMembersAndTypes(c) => Some(&c.class_info),
+            Record::SystemClassWithMembersAndTypes(c) => Some(&c.class_info),
+            Record::SystemClassWithMembers(c) => Some(&c.class_info),
+            Record::ClassWithMembers(c) => Some(&c.class_info),
+            _ => None,
+        }
+    }
+
+    /// Returns the member values of class records.
+    pub fn member_values(&self) -> Option<&[ObjectValue]> {
+        match self {
+            Record::ClassWithMembersAndTypes(c) => Some(&c.member_values),
+            Record::SystemClassWithMembersAndTypes(c) => Some(&c.member_values),
+            Record::SystemClassWithMembers(c) => Some(&c.member_values),
+            Record::ClassWithMembers(c) => Some(&c.member_values),
+            Record::ClassWithId(c) => Some(&c.member_values),
+            _ => None,
+        }
+    }
+    /// Returns the values nested directly inside a record.
+    pub(crate) fn child_values(&self) -> &[ObjectValue] {
+        match self {
+            Record::BinaryArray(a) => &a.element_values,
+            Record::ArraySingleObject(a) => &a.element_values,
+            Record::ArraySingleString(a) => &a.element_values,
+            _ => self.member_values().unwrap_or_default(),
+        }
+    }
+
+    /// Returns the values nested directly inside a record, mutably.
+    pub(crate) fn child_values_mut(&mut self) -> &mut [ObjectValue] {
+        match self {
+            Record::ClassWithMembersAndTypes(c) => &mut c.member_values,
+            Record::SystemClassWithMembersAndTypes(c) => &mut c.member_values,
+            Record::SystemClassWithMembers(c) => &mut c.member_values,
+            Record::ClassWithMembers(c) => &mut c.member_values,
+            Record::ClassWithId(c) => &mut c.member_values,
+            Record::BinaryArray(a) => &mut a.element_values,
+            Record::ArraySingleObject(a) => &mut a.element_values,
+            Record::ArraySingleString(a) => &mut a.element_values,
+            _ => &mut [],
+        }
+    }
+}
@@ -0,0 +1,138 @@
+// nrbf-parser - A high-performance MS-NRBF binary parser and encoder.
+// Copyright (C) 2026  driedpampas@proton.me
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Rewriting object and library ids.
+//!
+//! Objects spliced from one message into another keep ids that may already
+//! be taken. [`renumber`] rewrites the ids in a record set through an
+//! [`IdMap`]: the ids objects and libraries define, and every field that
+//! refers to them, i.e. `root_id`, `metadata_id`, `MemberReference` targets
+//! and the library ids of classes and class type info.
+
+use crate::records::*;
+use std::collections::HashMap;
+
+/// New ids for objects and libraries. Ids without an entry are kept.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IdMap {
+    /// New object ids by old id.
+    pub objects: HashMap<i32, i32>,
+    /// New library ids by old id.
+    pub libraries: HashMap<i32, i32>,
+}
+
+impl IdMap {
+    /// Numbers the objects and libraries defined in `records` consecutively
+    /// from `first`, in stream order.
+    ///
+    /// Objects and libraries share one sequence, as in streams
+    /// BinaryFormatter writes. `first` of 1 gives the most compact ids,
+    /// while a larger one moves the records clear of ids already in use.
+    pub fn compact(records: &[Record], first: i32) -> Self {
+        let mut map = IdMap::default();
+        let mut next = first;
+        for record in records {
+            map.number(record, &mut next);
+        }
+        map
+    }
+
+    fn number(&mut self, record: &Record, next: &mut i32) {
+        let (map, id) = match record {
+            Record::BinaryLibrary(l) => (&mut self.libraries, Some(l.library_id)),
+            _ => (&mut self.objects, record.object_id()),
+        };
+        if let Some(id) = id {
+            map.entry(id).or_insert_with(|| {
+                *next += 1;
+                *next - 1
+            });
+        }
+        for value in record.child_values() {
+            if let ObjectValue::Record(r) = value {
+                self.number(r, next);
+            }
+        }
+    }
+}
+
+/// Rewrites every object and library id in `records` through `map`.
+pub fn renumber(records: &mut [Record], map: &IdMap) {
+    for record in records {
+        renumber_record(record, map);
+    }
+}
+
+fn renumber_record(record: &mut Record, map: &IdMap) {
+    let object = |id: &mut i32| {
+        if let Some(new) = map.objects.get(id) {
+            *id = *new;
+        }
+    };
+    let library = |id: &mut i32| {
+        if let Some(new) = map.libraries.get(id) {
+            *id = *new;
+        }
+    };
+    let type_info = |info: &mut AdditionalTypeInfo| {
+        if let AdditionalTypeInfo::Class(c) = info {
+            library(&mut c.library_id);
+        }
+    };
+    match record {
+        Record::SerializationHeader(h) => object(&mut h.root_id),
+        Record::BinaryLibrary(l) => library(&mut l.library_id),
+        Record::ClassWithMembersAndTypes(c) => {
+            object(&mut c.class_info.object_id);
+            library(&mut c.library_id);
+            c.member_type_info
+                .additional_infos
+                .iter_mut()
+                .for_each(type_info);
+        }
+        Record::SystemClassWithMembersAndTypes(c) => {
+            object(&mut c.class_info.object_id);
+            c.member_type_info
+                .additional_infos
+                .iter_mut()
+                .for_each(type_info);
+        }
+        Record::SystemClassWithMembers(c) => object(&mut c.class_info.object_id),
+        Record::ClassWithMembers(c) => {
+            object(&mut c.class_info.object_id);
+            library(&mut c.library_id);
+        }
+        Record::ClassWithId(c) => {
+            object(&mut c.object_id);
+            object(&mut c.metadata_id);
+        }
+        Record::BinaryObjectString { object_id, .. } => object(object_id),
+        Record::BinaryArray(a) => {
+            object(&mut a.object_id);
+            type_info(&mut a.additional_type_info);
+        }
+        Record::ArraySingleObject(a) => object(&mut a.object_id),
+        Record::ArraySinglePrimitive(a) => object(&mut a.object_id),
+        Record::ArraySingleString(a) => object(&mut a.object_id),
+        Record::MemberReference { id_ref } => object(id_ref),
+        _ => {}
+    }
+    for value in record.child_values_mut() {
+        if let ObjectValue::Record(r) = value {
+            renumber_record(r, map);
+        }
+    }
+}
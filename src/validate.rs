@@ -0,0 +1,564 @@
+// nrbf-parser - A high-performance MS-NRBF binary parser and encoder.
+// Copyright (C) 2026  driedpampas@proton.me
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Stream-level validation of decoded records against MS-NRBF rules.
+
+use crate::decoder::ClassInfoWithTypes;
+use crate::records::*;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+/// How serious a diagnostic is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Severity {
+    /// The stream violates the specification and will not deserialize.
+    Error,
+    /// The stream is readable but unusual.
+    Warning,
+}
+
+/// Where in the record list a diagnostic applies.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Location {
+    /// Index of the top-level record.
+    pub record: usize,
+    /// Path of members and elements inside that record, e.g. `inventory[2].name`.
+    pub path: String,
+}
+
+/// A single finding produced by [`validate`].
+#[derive(Debug, Clone, Serialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub location: Location,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        write!(f, "{} at record {}", severity, self.location.record)?;
+        if !self.location.path.is_empty() {
+            write!(f, " ({})", self.location.path)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+/// Checks a decoded message against the stream-level invariants of MS-NRBF.
+///
+/// Returns every problem found; an empty list means the stream is valid.
+pub fn validate(records: &[Record]) -> Vec<Diagnostic> {
+    let mut validator = Validator::default();
+
+    match records.first() {
+        Some(Record::SerializationHeader(h)) => {
+            if h.major_version != 1 || h.minor_version != 0 {
+                validator.error(
+                    0,
+                    String::new(),
+                    format!(
+                        "Unsupported version {}.{}, expected 1.0",
+                        h.major_version, h.minor_version
+                    ),
+                );
+            }
+        }
+        _ => validator.error(
+            0,
+            String::new(),
+            "Stream must start with SerializationHeader".to_string(),
+        ),
+    }
+
+    for (index, record) in records.iter().enumerate() {
+        validator.record = index;
+        if validator.ended {
+            validator.warning(index, String::new(), "Record after MessageEnd".to_string());
+        }
+        validator.visit_record(record, String::new());
+        if matches!(record, Record::MessageEnd) {
+            validator.ended = true;
+        }
+    }
+
+    validator.finish(records);
+    validator.diagnostics
+}
+
+/// Incremental checker shared by [`validate`] and the encoder's strict mode.
+#[derive(Default)]
+pub(crate) struct Validator {
+    diagnostics: Vec<Diagnostic>,
+    /// Index of the top-level record being visited.
+    record: usize,
+    ended: bool,
+    object_ids: HashSet<i32>,
+    metadata: HashMap<i32, ClassInfoWithTypes>,
+    libraries: HashSet<i32>,
+    /// Member references seen so far, resolved once every id is known.
+    references: Vec<(i32, Location)>,
+    /// Library ids used by type info, which may be defined later in the stream.
+    type_libraries: Vec<(i32, Location)>,
+    /// Ids and metadata registered by the record being checked, so that
+    /// [`Validator::check_next`] can forget a rejected record.
+    journal: Vec<Registration>,
+}
+
+/// Something a record registered, with what it replaced.
+enum Registration {
+    ObjectId(i32),
+    Library(i32),
+    Metadata(i32, Option<ClassInfoWithTypes>),
+}
+
+impl Validator {
+    /// Checks the next top-level record, returning the first error it has.
+    ///
+    /// Checks that need the whole stream, such as resolving references, are
+    /// left to [`validate`]. A rejected record leaves no trace, so a
+    /// corrected one can be checked in its place.
+    pub(crate) fn check_next(&mut self, record: &Record) -> Option<Diagnostic> {
+        let start = self.diagnostics.len();
+        let references = self.references.len();
+        let type_libraries = self.type_libraries.len();
+        self.journal.clear();
+        self.visit_record(record, String::new());
+        let error = self
+            .diagnostics
+            .drain(start..)
+            .find(|d| d.severity == Severity::Error);
+        if error.is_none() {
+            self.record += 1;
+            return None;
+        }
+        self.references.truncate(references);
+        self.type_libraries.truncate(type_libraries);
+        while let Some(registration) = self.journal.pop() {
+            match registration {
+                Registration::ObjectId(id) => {
+                    self.object_ids.remove(&id);
+                }
+                Registration::Library(id) => {
+                    self.libraries.remove(&id);
+                }
+                Registration::Metadata(id, Some(previous)) => {
+                    self.metadata.insert(id, previous);
+                }
+                Registration::Metadata(id, None) => {
+                    self.metadata.remove(&id);
+                }
+            }
+        }
+        error
+    }
+
+    fn error(&mut self, record: usize, path: String, message: String) {
+        self.push(Severity::Error, record, path, message);
+    }
+
+    fn warning(&mut self, record: usize, path: String, message: String) {
+        self.push(Severity::Warning, record, path, message);
+    }
+
+    fn push(&mut self, severity: Severity, record: usize, path: String, message: String) {
+        self.diagnostics.push(Diagnostic {
+            severity,
+            location: Location { record, path },
+            message,
+        });
+    }
+
+    fn location(&self, path: &str) -> Location {
+        Location {
+            record: self.record,
+            path: path.to_string(),
+        }
+    }
+
+    fn visit_record(&mut self, record: &Record, path: String) {
+        if let Some(id) = record.object_id() {
+            if id == 0 {
+                self.error(self.record, path.clone(), "Object id must not be 0".into());
+            } else if self.object_ids.insert(id) {
+                self.journal.push(Registration::ObjectId(id));
+            } else {
+                self.error(
+                    self.record,
+                    path.clone(),
+                    format!("Duplicate object id {}", id),
+                );
+            }
+        }
+
+        if let Some(meta) = ClassInfoWithTypes::from_record(record) {
+            self.check_class_metadata(&meta, &path);
+            let id = meta.class_info.object_id;
+            let previous = self.metadata.insert(id, meta.clone());
+            self.journal.push(Registration::Metadata(id, previous));
+            let values = record.member_values().unwrap_or_default();
+            self.visit_members(&meta, values, &path);
+            return;
+        }
+
+        match record {
+            Record::SerializationHeader(_) if self.record != 0 || !path.is_empty() => {
+                self.error(
+                    self.record,
+                    path,
+                    "SerializationHeader must be the first record".into(),
+                );
+            }
+            Record::BinaryLibrary(l) => {
+                if self.libraries.insert(l.library_id) {
+                    self.journal.push(Registration::Library(l.library_id));
+                } else {
+                    self.error(
+                        self.record,
+                        path,
+                        format!("Duplicate library id {}", l.library_id),
+                    );
+                }
+            }
+            Record::ClassWithId(c) => match self.metadata.get(&c.metadata_id).cloned() {
+                Some(meta) => self.visit_members(&meta, &c.member_values, &path),
+                None => {
+                    self.error(
+                        self.record,
+                        path.clone(),
+                        format!(
+                            "metadata_id {} does not refer to an earlier class",
+                            c.metadata_id
+                        ),
+                    );
+                    for (i, value) in c.member_values.iter().enumerate() {
+                        self.visit_value(value, format!("{}[{}]", path, i));
+                    }
+                }
+            },
+            Record::BinaryArray(a) => {
+                if a.lengths.len() != a.rank as usize {
+                    self.error(
+                        self.record,
+                        path.clone(),
+                        format!("rank is {} but {} lengths given", a.rank, a.lengths.len()),
+                    );
+                }
+                if let Some(bounds) = &a.lower_bounds
+                    && bounds.len() != a.rank as usize
+                {
+                    self.error(
+                        self.record,
+                        path.clone(),
+                        format!("rank is {} but {} lower bounds given", a.rank, bounds.len()),
+                    );
+                }
+                self.check_type_info(a.type_enum, &a.additional_type_info, &path);
+                let expected = a
+                    .lengths
+                    .iter()
+                    .try_fold(1i64, |product, l| product.checked_mul(*l as i64));
+                if expected.is_none() {
+                    self.error(
+                        self.record,
+                        path.clone(),
+                        format!("lengths {:?} overflow the element count", a.lengths),
+                    );
+                }
+                let slot = match (a.type_enum, &a.additional_type_info) {
+                    (BinaryType::Primitive, AdditionalTypeInfo::Primitive(pt)) => Some(*pt),
+                    _ => None,
+                };
+                self.visit_elements(expected, &a.element_values, slot, &path);
+            }
+            Record::ArraySingleObject(a) => {
+                self.visit_elements(Some(a.length as i64), &a.element_values, None, &path);
+            }
+            Record::ArraySingleString(a) => {
+                self.visit_elements(Some(a.length as i64), &a.element_values, None, &path);
+            }
+            Record::ArraySinglePrimitive(a) => {
+                if a.element_values.len() != a.length as usize {
+                    self.error(
+                        self.record,
+                        path.clone(),
+                        format!(
+                            "length is {} but {} elements given",
+                            a.length,
+                            a.element_values.len()
+                        ),
+                    );
+                }
+                for (i, value) in a.element_values.iter().enumerate() {
+                    self.check_primitive(a.primitive_type_enum, value, format!("{}[{}]", path, i));
+                }
+            }
+            Record::MemberPrimitiveTyped {
+                primitive_type_enum,
+                value,
+            } => {
+                self.check_primitive(*primitive_type_enum, value, path);
+            }
+            Record::MemberReference { id_ref } => {
+                let location = self.location(&path);
+                self.references.push((*id_ref, location));
+            }
+            _ => {}
+        }
+    }
+
+    fn check_class_metadata(&mut self, meta: &ClassInfoWithTypes, path: &str) {
+        let info = &meta.class_info;
+        if info.member_count as usize != info.member_names.len() {
+            self.error(
+                self.record,
+                path.to_string(),
+                format!(
+                    "member_count is {} but {} member names given",
+                    info.member_count,
+                    info.member_names.len()
+                ),
+            );
+        }
+        if let Some(library_id) = meta.library_id
+            && !self.libraries.contains(&library_id)
+        {
+            self.error(
+                self.record,
+                path.to_string(),
+                format!(
+                    "library_id {} does not refer to an earlier BinaryLibrary",
+                    library_id
+                ),
+            );
+        }
+        if let Some(mti) = &meta.member_type_info {
+            if mti.binary_type_enums.len() != info.member_count as usize
+                || mti.additional_infos.len() != info.member_count as usize
+            {
+                self.error(
+                    self.record,
+                    path.to_string(),
+                    format!(
+                        "member_count is {} but type info describes {} members",
+                        info.member_count,
+                        mti.binary_type_enums.len()
+                    ),
+                );
+            }
+            for (i, (bt, info)) in mti
+                .binary_type_enums
+                .iter()
+                .zip(&mti.additional_infos)
+                .enumerate()
+            {
+                self.check_type_info(*bt, info, &member_path(path, &meta.class_info, i));
+            }
+        }
+    }
+
+    fn check_type_info(&mut self, bt: BinaryType, info: &AdditionalTypeInfo, path: &str) {
+        let consistent = matches!(
+            (bt, info),
+            (
+                BinaryType::Primitive | BinaryType::PrimitiveArray,
+                AdditionalTypeInfo::Primitive(_)
+            ) | (BinaryType::SystemClass, AdditionalTypeInfo::SystemClass(_))
+                | (BinaryType::Class, AdditionalTypeInfo::Class(_))
+                | (
+                    BinaryType::String
+                        | BinaryType::Object
+                        | BinaryType::ObjectArray
+                        | BinaryType::StringArray,
+                    AdditionalTypeInfo::None
+                )
+        );
+        if !consistent {
+            self.error(
+                self.record,
+                path.to_string(),
+                format!(
+                    "Binary type {:?} does not match additional info {:?}",
+                    bt, info
+                ),
+            );
+        }
+        if let AdditionalTypeInfo::Class(c) = info {
+            let location = self.location(path);
+            self.type_libraries.push((c.library_id, location));
+        }
+    }
+
+    /// Checks that a value can be written into a slot.
+    ///
+    /// `slot` is the primitive type of primitive-typed slots and `None` for
+    /// slots that hold records.
+    fn check_slot(&mut self, slot: Option<PrimitiveType>, value: &ObjectValue, path: &str) {
+        match (slot, value) {
+            (Some(pt), ObjectValue::Primitive(p)) => self.check_primitive(pt, p, path.to_string()),
+            (Some(pt), ObjectValue::Record(r)) => self.error(
+                self.record,
+                path.to_string(),
+                format!(
+                    "Expected {:?} value but found a {:?} record",
+                    pt,
+                    RecordType::from(r.as_ref())
+                ),
+            ),
+            (None, ObjectValue::Primitive(p)) if !matches!(p, PrimitiveValue::Null) => self.error(
+                self.record,
+                path.to_string(),
+                format!(
+                    "Bare {:?} value where a record is expected; wrap it in MemberPrimitiveTyped",
+                    p.primitive_type()
+                ),
+            ),
+            _ => {}
+        }
+    }
+
+    fn check_primitive(&mut self, pt: PrimitiveType, value: &PrimitiveValue, path: String) {
+        if value.primitive_type() != pt || matches!(value, PrimitiveValue::Null) {
+            self.error(
+                self.record,
+                path,
+                format!("Expected {:?} value but found {:?}", pt, value),
+            );
+        }
+    }
+
+    fn visit_members(&mut self, meta: &ClassInfoWithTypes, values: &[ObjectValue], path: &str) {
+        let info = &meta.class_info;
+        if values.len() != info.member_count as usize {
+            self.error(
+                self.record,
+                path.to_string(),
+                format!(
+                    "member_count is {} but {} member values given",
+                    info.member_count,
+                    values.len()
+                ),
+            );
+        }
+        for (i, value) in values.iter().enumerate() {
+            let path = member_path(path, info, i);
+            let slot = meta
+                .member_type_info
+                .as_ref()
+                .and_then(|mti| mti.primitive_slot(i));
+            self.check_slot(slot, value, &path);
+            self.visit_value(value, path);
+        }
+    }
+
+    fn visit_elements(
+        &mut self,
+        expected: Option<i64>,
+        values: &[ObjectValue],
+        slot: Option<PrimitiveType>,
+        path: &str,
+    ) {
+        let mut count: i64 = 0;
+        for value in values {
+            count += match value {
+                ObjectValue::Record(r) => match r.as_ref() {
+                    Record::ObjectNullMultiple(n) => n.null_count as i64,
+                    Record::ObjectNullMultiple256(n) => n.null_count as i64,
+                    _ => 1,
+                },
+                ObjectValue::Primitive(_) => 1,
+            };
+        }
+        if let Some(expected) = expected
+            && count != expected
+        {
+            self.error(
+                self.record,
+                path.to_string(),
+                format!("length is {} but {} elements given", expected, count),
+            );
+        }
+        for (i, value) in values.iter().enumerate() {
+            let path = format!("{}[{}]", path, i);
+            self.check_slot(slot, value, &path);
+            self.visit_value(value, path);
+        }
+    }
+
+    fn visit_value(&mut self, value: &ObjectValue, path: String) {
+        if let ObjectValue::Record(r) = value {
+            self.visit_record(r, path);
+        }
+    }
+
+    fn finish(&mut self, records: &[Record]) {
+        if let Some(Record::SerializationHeader(h)) = records.first()
+            && !self.object_ids.contains(&h.root_id)
+        {
+            self.error(
+                0,
+                String::new(),
+                format!("root_id {} does not refer to any object", h.root_id),
+            );
+        }
+
+        for (id, location) in std::mem::take(&mut self.references) {
+            if !self.object_ids.contains(&id) {
+                self.error(
+                    location.record,
+                    location.path,
+                    format!("Reference to unknown object id {}", id),
+                );
+            } else if id < 0 {
+                self.error(
+                    location.record,
+                    location.path,
+                    format!("Referenced object id {} must be positive", id),
+                );
+            }
+        }
+
+        for (id, location) in std::mem::take(&mut self.type_libraries) {
+            if !self.libraries.contains(&id) {
+                self.error(
+                    location.record,
+                    location.path,
+                    format!("Type info refers to unknown library id {}", id),
+                );
+            }
+        }
+
+        if !matches!(records.last(), Some(Record::MessageEnd)) {
+            self.error(
+                records.len().saturating_sub(1),
+                String::new(),
+                "Stream must end with MessageEnd".into(),
+            );
+        }
+    }
+}
+
+fn member_path(path: &str, info: &ClassInfo, index: usize) -> String {
+    match info.member_names.get(index) {
+        Some(name) if path.is_empty() => name.clone(),
+        Some(name) => format!("{}.{}", path, name),
+        None => format!("{}[{}]", path, index),
+    }
+}
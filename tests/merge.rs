@@ -0,0 +1,167 @@
+// nrbf-parser - A high-performance MS-NRBF binary parser and encoder.
+// Copyright (C) 2026  driedpampas@proton.me
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use nrbf_parser::builder::{ClassDef, MemberType, Value};
+use nrbf_parser::diff::diff;
+use nrbf_parser::merge::{Policy, PreferOurs, PreferTheirs, Side, merge};
+use nrbf_parser::records::{
+    AdditionalTypeInfo, BinaryArray, BinaryType, ObjectValue, PrimitiveType, PrimitiveValue,
+};
+use nrbf_parser::{Record, StreamBuilder};
+
+fn player() -> ClassDef {
+    ClassDef::new("Game.Player", Some("Game"))
+        .member("scores", MemberType::PrimitiveArray(PrimitiveType::Int32))
+        .member("pet", MemberType::Object)
+}
+
+fn node() -> ClassDef {
+    ClassDef::new("Game.Node", Some("Game")).member("next", MemberType::Object)
+}
+
+/// Writes a player with the given scores and no pet.
+fn scores(values: &[i32]) -> Vec<Record> {
+    let values = values.iter().map(|v| PrimitiveValue::Int32(*v)).collect();
+    let root = Value::Object(
+        player(),
+        vec![
+            Value::PrimitiveArray(PrimitiveType::Int32, values),
+            Value::Null,
+        ],
+    );
+    write(|builder| builder.write_root(&root).map(drop))
+}
+
+fn write(
+    build: impl FnOnce(&mut StreamBuilder<&mut Vec<u8>>) -> nrbf_parser::error::Result<()>,
+) -> Vec<Record> {
+    let mut bytes = Vec::new();
+    let mut builder = StreamBuilder::new(&mut bytes);
+    build(&mut builder).unwrap();
+    builder.finish().unwrap();
+    nrbf_parser::parse(bytes.as_slice())
+        .collect::<Result<_, _>>()
+        .unwrap()
+}
+
+/// Merges and checks the conflicts and the merged message.
+fn check(
+    (base, ours, theirs): (&[Record], &[Record], &[Record]),
+    policy: &dyn Policy,
+    conflicts: &[(&str, Option<Side>)],
+    expected: &[Record],
+) {
+    let merged = merge(base, ours, theirs, policy).unwrap();
+    let found: Vec<_> = merged
+        .conflicts
+        .iter()
+        .map(|c| (c.path.as_str(), c.resolution))
+        .collect();
+    assert_eq!(found, conflicts);
+    let changes: Vec<_> = diff(expected, &merged.records)
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+    assert!(changes.is_empty(), "{:?}", changes);
+}
+
+#[test]
+fn takes_an_unchanged_removal() {
+    let (base, ours, theirs) = (scores(&[1, 2, 3]), scores(&[1, 2, 3]), scores(&[1, 2]));
+    check((&base, &ours, &theirs), &PreferOurs, &[], &theirs);
+    check((&base, &theirs, &ours), &PreferOurs, &[], &theirs);
+}
+
+#[test]
+fn theirs_removing_what_ours_changed_conflicts() {
+    let (base, ours, theirs) = (scores(&[1, 2, 3]), scores(&[1, 2, 30]), scores(&[1, 2]));
+    let messages = (&base[..], &ours[..], &theirs[..]);
+    check(
+        messages,
+        &PreferOurs,
+        &[("$.scores", Some(Side::Ours))],
+        &ours,
+    );
+    check(
+        messages,
+        &PreferTheirs,
+        &[("$.scores", Some(Side::Theirs))],
+        &theirs,
+    );
+}
+
+#[test]
+fn ours_removing_what_theirs_changed_conflicts() {
+    let (base, ours, theirs) = (scores(&[1, 2, 3]), scores(&[1, 2]), scores(&[1, 2, 30]));
+    let messages = (&base[..], &ours[..], &theirs[..]);
+    check(
+        messages,
+        &PreferOurs,
+        &[("$.scores", Some(Side::Ours))],
+        &ours,
+    );
+    check(
+        messages,
+        &PreferTheirs,
+        &[("$.scores", Some(Side::Theirs))],
+        &theirs,
+    );
+}
+
+#[test]
+fn leaves_an_object_referring_back_to_itself_unresolved() {
+    let base = scores(&[]);
+    let theirs = write(|builder| {
+        let pet = builder.reserve_id();
+        let root = Value::Object(
+            player(),
+            vec![
+                Value::PrimitiveArray(PrimitiveType::Int32, Vec::new()),
+                Value::Reference(pet),
+            ],
+        );
+        builder.write_root(&root)?;
+        builder.write(pet, &Value::Object(node(), vec![Value::Reference(pet)]))
+    });
+    let messages = (&base[..], &base[..], &theirs[..]);
+    check(messages, &PreferTheirs, &[("$.pet", None)], &base);
+}
+
+#[test]
+fn leaves_an_object_holding_a_binary_array_unresolved() {
+    let base = scores(&[]);
+    let mut theirs = base.clone();
+    if let Some(Record::ClassWithMembersAndTypes(root)) = theirs.get_mut(2) {
+        root.member_values[1] =
+            ObjectValue::Record(Box::new(Record::MemberReference { id_ref: 100 }));
+    }
+    let end = theirs.len() - 1;
+    theirs.insert(
+        end,
+        Record::BinaryArray(BinaryArray {
+            object_id: 100,
+            binary_array_type_enum: 0,
+            rank: 1,
+            lengths: vec![1],
+            lower_bounds: None,
+            type_enum: BinaryType::Primitive,
+            additional_type_info: AdditionalTypeInfo::Primitive(PrimitiveType::Int32),
+            element_values: vec![ObjectValue::Primitive(PrimitiveValue::Int32(5))],
+        }),
+    );
+    let messages = (&base[..], &base[..], &theirs[..]);
+    check(messages, &PreferTheirs, &[("$.pet", None)], &base);
+}